@@ -0,0 +1,110 @@
+use crate::dynamic::Api;
+pub use crate::errors::FieldConflict;
+use crate::errors::KubernetesError;
+use crate::patch::Patch;
+use http::StatusCode;
+use k8s_openapi::PatchOptional;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Parameters of a server-side apply, matching `kubectl apply --server-side`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApplyOptions {
+    /// Name of the manager owning the applied fields. Required by the API server.
+    pub field_manager: String,
+    /// Take ownership of fields set by other managers instead of failing.
+    pub force: bool,
+    /// Validate and compute the result without persisting it.
+    pub dry_run: bool,
+}
+
+impl ApplyOptions {
+    pub fn new(field_manager: &str) -> Self {
+        ApplyOptions {
+            field_manager: field_manager.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+}
+
+impl<'a, K> Api<'a, K>
+where
+    K: Serialize + DeserializeOwned,
+{
+    /// Server-side applies `object`, creating it if needed, and returns the result.
+    ///
+    /// `object` only holds the fields the manager cares about. When other managers
+    /// own some of them, fails with `KubernetesError::ApplyConflictError` unless
+    /// `force` is set.
+    pub fn apply(
+        &self,
+        name: &str,
+        object: &K,
+        options: &ApplyOptions,
+    ) -> Result<K, KubernetesError> {
+        let value =
+            serde_json::to_value(object).map_err(|source| KubernetesError::JsonError { source })?;
+        let optional = PatchOptional {
+            dry_run: if options.dry_run { Some("All") } else { None },
+            field_manager: Some(&options.field_manager),
+            force: Some(options.force),
+            ..Default::default()
+        };
+        self.patch(name, &Patch::Apply(value), optional)
+            .map_err(|err| match err {
+                KubernetesError::ApiError {
+                    url,
+                    status_code: StatusCode::CONFLICT,
+                    status: Some(status),
+                    ..
+                } if !FieldConflict::from_status(&status).is_empty() => {
+                    KubernetesError::ApplyConflictError {
+                        url,
+                        conflicts: FieldConflict::from_status(&status),
+                        status,
+                    }
+                }
+                err => err,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+
+    #[test]
+    fn test_conflicts_from_status() {
+        let status: Status = serde_json::from_str(
+            r#"{
+                "kind": "Status", "metadata": {}, "status": "Failure", "reason": "Conflict", "code": 409,
+                "message": "Apply failed with 2 conflicts",
+                "details": {"causes": [
+                    {"reason": "FieldManagerConflict", "message": "conflict with \"kubectl-client-side-apply\" using apps/v1", "field": ".spec.replicas"},
+                    {"reason": "FieldManagerConflict", "message": "conflict with \"helm\"", "field": ".spec.template.spec.containers[name=\"app\"].image"}
+                ]}
+            }"#,
+        )
+        .unwrap();
+        let conflicts = FieldConflict::from_status(&status);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].manager, "kubectl-client-side-apply");
+        assert_eq!(conflicts[0].field, ".spec.replicas");
+        assert_eq!(conflicts[1].manager, "helm");
+        assert_eq!(
+            conflicts[1].field,
+            ".spec.template.spec.containers[name=\"app\"].image"
+        );
+    }
+}
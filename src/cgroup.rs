@@ -0,0 +1,248 @@
+use crate::containers::{ContainerIndex, ContainerInfo};
+use crate::errors::KubernetesError;
+use k8s_openapi::api::core::v1::Pod;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Quality of service class of a pod, which decides its cgroup parent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QosClass {
+    Guaranteed,
+    Burstable,
+    BestEffort,
+}
+
+impl QosClass {
+    /// Same value as `Pod.status.qosClass`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QosClass::Guaranteed => "Guaranteed",
+            QosClass::Burstable => "Burstable",
+            QosClass::BestEffort => "BestEffort",
+        }
+    }
+}
+
+impl fmt::Display for QosClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the cgroup of a process tells about the pod it runs in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PodCgroup {
+    pub pod_uid: String,
+    /// Bare container ID, `None` for processes in the cgroup of the pod itself.
+    pub container_id: Option<String>,
+    pub qos_class: QosClass,
+}
+
+impl PodCgroup {
+    /// The pod among `pods`, by UID.
+    pub fn find_pod<'p>(&self, pods: &'p [Pod]) -> Option<&'p Pod> {
+        pods.iter()
+            .find(|p| p.metadata.uid.as_deref() == Some(&self.pod_uid))
+    }
+
+    /// The container in `index`. The sandbox (pause) container isn't in the index.
+    pub fn find_container<'i>(&self, index: &'i ContainerIndex) -> Option<&'i ContainerInfo> {
+        let container = index.get(self.container_id.as_deref()?)?;
+        Some(container).filter(|c| c.pod_uid == self.pod_uid)
+    }
+}
+
+/// Parses the content of `/proc/<pid>/cgroup`, with cgroup v1 or v2. `None` if the
+/// process doesn't run in a pod.
+pub fn parse_proc_cgroup(content: &str) -> Option<PodCgroup> {
+    content.lines().find_map(|line| {
+        // hierarchy-ID:controller-list:cgroup-path
+        let path = line.splitn(3, ':').nth(2)?;
+        parse_cgroup_path(path)
+    })
+}
+
+/// Parses a cgroup path created by the kubelet, with the systemd driver, e.g.
+/// `/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod<uid>.slice/cri-containerd-<id>.scope`,
+/// or the cgroupfs driver, e.g. `/kubepods/burstable/pod<uid>/<id>`. The kubepods
+/// hierarchy may be nested, e.g. in kind nodes.
+pub fn parse_cgroup_path(path: &str) -> Option<PodCgroup> {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let kubepods = segments.iter().position(|s| s.contains("kubepods"))?;
+    let (index, pod_uid) = segments
+        .iter()
+        .enumerate()
+        .skip(kubepods)
+        .find_map(|(i, segment)| Some((i, pod_uid(segment)?)))?;
+    let qos_class = segments[kubepods..=index]
+        .iter()
+        .find_map(|segment| {
+            if segment.contains("besteffort") {
+                Some(QosClass::BestEffort)
+            } else if segment.contains("burstable") {
+                Some(QosClass::Burstable)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(QosClass::Guaranteed);
+    let container_id = match segments.get(index + 1) {
+        Some(segment) => Some(container_id(segment)?),
+        None => None,
+    };
+    Some(PodCgroup {
+        pod_uid,
+        container_id,
+        qos_class,
+    })
+}
+
+/// UID of a pod cgroup: `pod<uid>` with cgroupfs, `kubepods[-qos]-pod<uid>.slice`
+/// with systemd, where the dashes of the UID are replaced by underscores.
+fn pod_uid(segment: &str) -> Option<String> {
+    let uid = match segment.strip_suffix(".slice") {
+        Some(slice) => slice.rsplit_once("-pod")?.1.replace('_', "-"),
+        None => segment.strip_prefix("pod")?.to_string(),
+    };
+    let is_uid = uid.len() == 36 && uid.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+    Some(uid).filter(|_| is_uid)
+}
+
+/// ID of a container cgroup: the bare ID with cgroupfs, `<runtime>-<id>.scope` with
+/// systemd. `None` for the cgroups of other processes, e.g. the `crio-conmon-` one.
+fn container_id(segment: &str) -> Option<String> {
+    let name = segment.strip_suffix(".scope").unwrap_or(segment);
+    let id = ["cri-containerd-", "docker-", "crio-", "containerd-"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name);
+    let is_id = id.len() >= 12 && id.chars().all(|c| c.is_ascii_hexdigit());
+    Some(id.to_string()).filter(|_| is_id)
+}
+
+/// A procfs mount, `/proc` by default. Point it at the host's one from a container,
+/// e.g. `/host/proc`, or at a fixture tree in tests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcRoot {
+    pub path: PathBuf,
+}
+
+impl Default for ProcRoot {
+    fn default() -> Self {
+        ProcRoot::new("/proc")
+    }
+}
+
+impl ProcRoot {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        ProcRoot {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Pod cgroup of the process `pid`, `None` if it doesn't run in a pod.
+    pub fn pod_cgroup(&self, pid: u32) -> Result<Option<PodCgroup>, KubernetesError> {
+        let path = self.path.join(pid.to_string()).join("cgroup");
+        let content =
+            fs::read_to_string(path).map_err(|source| KubernetesError::IoError { source })?;
+        Ok(parse_proc_cgroup(&content))
+    }
+
+    /// Container of the process `pid` in `index`, `None` if it doesn't run in a
+    /// container of an indexed pod.
+    pub fn container_of<'i>(
+        &self,
+        pid: u32,
+        index: &'i ContainerIndex,
+    ) -> Result<Option<&'i ContainerInfo>, KubernetesError> {
+        Ok(self
+            .pod_cgroup(pid)?
+            .and_then(|cgroup| cgroup.find_container(index)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UID: &str = "0f3a9c2e-5b1d-4e8f-9a7c-2d6b8e1f4a3c";
+    const ID: &str = "7b4c1e9a2f5d8c3b6a0e1f4d7c2b5a8e9f3c6d1b4a7e0f2c5b8d1a4e7c0f3b6a";
+
+    fn parsed(path: &str) -> Option<PodCgroup> {
+        parse_cgroup_path(
+            &path
+                .replace("{uid_}", &UID.replace('-', "_"))
+                .replace("{uid}", UID)
+                .replace("{id}", ID),
+        )
+    }
+
+    fn expected(container: bool, qos_class: QosClass) -> Option<PodCgroup> {
+        Some(PodCgroup {
+            pod_uid: UID.to_string(),
+            container_id: Some(ID.to_string()).filter(|_| container),
+            qos_class,
+        })
+    }
+
+    #[test]
+    fn test_systemd_paths() {
+        assert_eq!(
+            parsed("/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod{uid_}.slice/cri-containerd-{id}.scope"),
+            expected(true, QosClass::Burstable)
+        );
+        assert_eq!(
+            parsed("/kubepods.slice/kubepods-pod{uid_}.slice/crio-{id}.scope"),
+            expected(true, QosClass::Guaranteed)
+        );
+        assert_eq!(
+            parsed("/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod{uid_}.slice/docker-{id}.scope"),
+            expected(true, QosClass::BestEffort)
+        );
+        // Nested in a kind node.
+        assert_eq!(
+            parsed("/kubelet.slice/kubelet-kubepods.slice/kubelet-kubepods-besteffort.slice/kubelet-kubepods-besteffort-pod{uid_}.slice/cri-containerd-{id}.scope"),
+            expected(true, QosClass::BestEffort)
+        );
+        assert_eq!(
+            parsed("/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod{uid_}.slice"),
+            expected(false, QosClass::Burstable)
+        );
+        assert_eq!(
+            parsed("/kubepods.slice/kubepods-pod{uid_}.slice/crio-conmon-{id}.scope"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cgroupfs_paths() {
+        assert_eq!(
+            parsed("/kubepods/burstable/pod{uid}/{id}"),
+            expected(true, QosClass::Burstable)
+        );
+        assert_eq!(
+            parsed("/kubepods/pod{uid}/{id}"),
+            expected(true, QosClass::Guaranteed)
+        );
+        assert_eq!(
+            parsed("/docker/3f2a/kubepods/besteffort/pod{uid}/{id}"),
+            expected(true, QosClass::BestEffort)
+        );
+        assert_eq!(parsed("/system.slice/containerd.service"), None);
+        assert_eq!(parsed("/kubepods/burstable/podman"), None);
+    }
+
+    #[test]
+    fn test_proc_cgroup() {
+        let v1 = format!(
+            "12:pids:/kubepods/burstable/pod{}/{}\n1:name=systemd:/kubepods/burstable/pod{}/{}\n0::/\n",
+            UID, ID, UID, ID
+        );
+        assert_eq!(parse_proc_cgroup(&v1), expected(true, QosClass::Burstable));
+        assert_eq!(
+            parse_proc_cgroup("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+    }
+}
@@ -20,6 +20,7 @@ pub fn default_kube_path() -> Option<PathBuf> {
     home_dir().map(|h| h.join(".kube").join("config"))
 }
 
+#[allow(clippy::needless_borrows_for_generic_args)]
 pub fn data_or_file_with_base64<P: AsRef<Path>>(
     data: &Option<String>,
     file: &Option<P>,
@@ -0,0 +1,350 @@
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use k8s_openapi::api::core::v1::{ContainerStatus, Pod};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::WatchEvent;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Container runtime, from the prefix of a container ID in a pod status.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ContainerRuntime {
+    Containerd,
+    Docker,
+    CriO,
+    Other(String),
+}
+
+impl ContainerRuntime {
+    pub fn from_prefix(prefix: &str) -> Self {
+        match prefix {
+            "containerd" => ContainerRuntime::Containerd,
+            "docker" => ContainerRuntime::Docker,
+            "cri-o" => ContainerRuntime::CriO,
+            other => ContainerRuntime::Other(other.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ContainerRuntime::Containerd => "containerd",
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::CriO => "cri-o",
+            ContainerRuntime::Other(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for ContainerRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Splits `containerd://4f3a...` into its runtime and bare ID. A bare ID is
+/// returned as is, without runtime.
+pub fn parse_container_id(id: &str) -> (Option<ContainerRuntime>, &str) {
+    match id.split_once("://") {
+        Some((prefix, id)) => (Some(ContainerRuntime::from_prefix(prefix)), id),
+        None => (None, id),
+    }
+}
+
+/// Which list of the pod spec a container comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContainerKind {
+    Regular,
+    Init,
+    Ephemeral,
+}
+
+/// A container of a pod, as found in the index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContainerInfo {
+    /// Bare ID, without the runtime prefix.
+    pub container_id: String,
+    pub runtime: Option<ContainerRuntime>,
+    pub namespace: String,
+    pub pod_name: String,
+    pub pod_uid: String,
+    pub container_name: String,
+    pub kind: ContainerKind,
+    /// Whether the ID is the previous instance of a restarted container, from
+    /// `lastState.terminated`. Its processes may still be seen shortly after.
+    pub terminated: bool,
+    pub pod_labels: BTreeMap<String, String>,
+}
+
+/// Index from container IDs to the containers of pods, as needed to map the
+/// processes of a host to pods.
+///
+/// Fill it from a list of pods (`refresh`), e.g. `Kubernetes::list_pods_on_node`,
+/// then keep it up to date with the events of a watch (`apply_event`).
+#[derive(Clone, Debug, Default)]
+pub struct ContainerIndex {
+    containers: HashMap<String, ContainerInfo>,
+    /// Container IDs of each pod, by pod UID.
+    pods: HashMap<String, Vec<String>>,
+}
+
+impl ContainerIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_pods(pods: &[Pod]) -> Self {
+        let mut index = Self::new();
+        index.refresh(pods);
+        index
+    }
+
+    /// Container of `container_id`, with or without runtime prefix.
+    pub fn get(&self, container_id: &str) -> Option<&ContainerInfo> {
+        self.containers.get(parse_container_id(container_id).1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.containers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.containers.is_empty()
+    }
+
+    pub fn containers(&self) -> impl Iterator<Item = &ContainerInfo> {
+        self.containers.values()
+    }
+
+    /// Replaces the content of the index by the containers of `pods`.
+    pub fn refresh(&mut self, pods: &[Pod]) {
+        self.containers.clear();
+        self.pods.clear();
+        for pod in pods {
+            self.update_pod(pod);
+        }
+    }
+
+    /// Indexes the current containers of `pod`, forgetting the ones it doesn't
+    /// have anymore.
+    pub fn update_pod(&mut self, pod: &Pod) {
+        self.remove_pod(pod);
+        let uid = pod_key(pod);
+        let mut ids = vec![];
+        for info in pod_containers(pod) {
+            let id = info.container_id.clone();
+            // An ID still running in a pod isn't taken over by the previous
+            // instance of a container of another pod.
+            let keep_existing = info.terminated
+                && self
+                    .containers
+                    .get(&id)
+                    .is_some_and(|existing| !existing.terminated && existing.pod_uid != uid);
+            if !keep_existing {
+                self.containers.insert(id.clone(), info);
+                ids.push(id);
+            }
+        }
+        self.pods.insert(uid, ids);
+    }
+
+    /// Forgets the containers of `pod`. IDs reused since by another pod are kept.
+    pub fn remove_pod(&mut self, pod: &Pod) {
+        let uid = pod_key(pod);
+        for id in self.pods.remove(&uid).unwrap_or_default() {
+            if self
+                .containers
+                .get(&id)
+                .is_some_and(|info| info.pod_uid == uid)
+            {
+                self.containers.remove(&id);
+            }
+        }
+    }
+
+    /// Updates the index with an event of a watch on pods.
+    pub fn apply_event(&mut self, event: &WatchEvent<Pod>) {
+        match event {
+            WatchEvent::Added(pod) | WatchEvent::Modified(pod) => self.update_pod(pod),
+            WatchEvent::Deleted(pod) => self.remove_pod(pod),
+            WatchEvent::Bookmark { .. }
+            | WatchEvent::ErrorStatus(_)
+            | WatchEvent::ErrorOther(_) => {}
+        }
+    }
+}
+
+impl Kubernetes {
+    /// Index of the containers of the pods on `node_name`, or on the node we run on
+    /// if `None`.
+    pub fn container_index(
+        &self,
+        node_name: Option<String>,
+    ) -> Result<ContainerIndex, KubernetesError> {
+        let pods = self.list_pods_on_node(node_name, Default::default())?;
+        Ok(ContainerIndex::from_pods(&pods))
+    }
+}
+
+/// UID of the pod, or its namespace and name if it has none (e.g. built by hand).
+fn pod_key(pod: &Pod) -> String {
+    match &pod.metadata.uid {
+        Some(uid) => uid.clone(),
+        None => format!(
+            "{}/{}",
+            pod.metadata.namespace.as_deref().unwrap_or_default(),
+            pod.metadata.name.as_deref().unwrap_or_default()
+        ),
+    }
+}
+
+/// Every container ID of `pod`: current and previous instances of its regular,
+/// init and ephemeral containers.
+fn pod_containers(pod: &Pod) -> Vec<ContainerInfo> {
+    let status = match &pod.status {
+        Some(status) => status,
+        None => return vec![],
+    };
+    let lists = [
+        (ContainerKind::Init, &status.init_container_statuses),
+        (ContainerKind::Regular, &status.container_statuses),
+        (
+            ContainerKind::Ephemeral,
+            &status.ephemeral_container_statuses,
+        ),
+    ];
+    let mut containers = vec![];
+    for (kind, statuses) in lists.iter() {
+        for status in statuses.iter().flatten() {
+            let previous = status
+                .last_state
+                .as_ref()
+                .and_then(|s| s.terminated.as_ref())
+                .and_then(|t| t.container_id.as_deref());
+            if let Some(id) = previous {
+                containers.push(container_info(pod, status, *kind, id, true));
+            }
+            if let Some(id) = status.container_id.as_deref() {
+                containers.push(container_info(pod, status, *kind, id, false));
+            }
+        }
+    }
+    containers.retain(|c| !c.container_id.is_empty());
+    containers
+}
+
+fn container_info(
+    pod: &Pod,
+    status: &ContainerStatus,
+    kind: ContainerKind,
+    id: &str,
+    terminated: bool,
+) -> ContainerInfo {
+    let (runtime, container_id) = parse_container_id(id);
+    ContainerInfo {
+        container_id: container_id.to_string(),
+        runtime,
+        namespace: pod.metadata.namespace.clone().unwrap_or_default(),
+        pod_name: pod.metadata.name.clone().unwrap_or_default(),
+        pod_uid: pod_key(pod),
+        container_name: status.name.clone(),
+        kind,
+        terminated,
+        pod_labels: pod.metadata.labels.clone().unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pod(uid: &str, name: &str, status: serde_json::Value) -> Pod {
+        serde_json::from_value(json!({
+            "metadata": {"name": name, "namespace": "shop", "uid": uid, "labels": {"app": name}},
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    fn web(container_id: &str, previous: Option<&str>) -> Pod {
+        let mut status = json!({
+            "name": "web", "image": "web", "imageID": "", "ready": true, "restartCount": 1,
+            "containerID": container_id,
+        });
+        if let Some(previous) = previous {
+            status["lastState"] = json!({"terminated": {"exitCode": 1, "containerID": previous}});
+        }
+        pod(
+            "u1",
+            "web-1",
+            json!({
+                "initContainerStatuses": [{"name": "migrate", "image": "m", "imageID": "", "ready": false, "restartCount": 0, "containerID": "docker://d0"}],
+                "containerStatuses": [status],
+                "ephemeralContainerStatuses": [{"name": "debugger", "image": "b", "imageID": "", "ready": false, "restartCount": 0, "containerID": "cri-o://e0"}],
+            }),
+        )
+    }
+
+    #[test]
+    fn test_parse_container_id() {
+        assert_eq!(
+            parse_container_id("containerd://4f3a"),
+            (Some(ContainerRuntime::Containerd), "4f3a")
+        );
+        assert_eq!(
+            parse_container_id("cri-o://4f3a"),
+            (Some(ContainerRuntime::CriO), "4f3a")
+        );
+        assert_eq!(parse_container_id("4f3a"), (None, "4f3a"));
+    }
+
+    #[test]
+    fn test_index_kinds() {
+        let index = ContainerIndex::from_pods(&[web("containerd://c1", None)]);
+        assert_eq!(index.len(), 3);
+        let web = index.get("containerd://c1").unwrap();
+        assert_eq!(
+            (
+                web.namespace.as_str(),
+                web.pod_name.as_str(),
+                web.container_name.as_str()
+            ),
+            ("shop", "web-1", "web")
+        );
+        assert_eq!(web.pod_labels.get("app").map(String::as_str), Some("web-1"));
+        assert_eq!(index.get("c1"), Some(web));
+        assert_eq!(index.get("d0").unwrap().kind, ContainerKind::Init);
+        let debugger = index.get("e0").unwrap();
+        assert_eq!(debugger.kind, ContainerKind::Ephemeral);
+        assert_eq!(debugger.runtime, Some(ContainerRuntime::CriO));
+    }
+
+    #[test]
+    fn test_restart_and_reuse() {
+        let mut index = ContainerIndex::from_pods(&[web("containerd://c1", None)]);
+        // Restarted: c1 is the previous instance, c2 the current one.
+        index.apply_event(&WatchEvent::Modified(web(
+            "containerd://c2",
+            Some("containerd://c1"),
+        )));
+        assert!(index.get("c1").unwrap().terminated);
+        assert!(!index.get("c2").unwrap().terminated);
+
+        // Restarted again: c1 is gone.
+        index.update_pod(&web("containerd://c3", Some("containerd://c2")));
+        assert_eq!(index.get("c1"), None);
+
+        // c3 is reused by another pod: deleting the first pod keeps it.
+        let other = pod(
+            "u2",
+            "api-1",
+            json!({"containerStatuses": [{"name": "api", "image": "a", "imageID": "", "ready": true, "restartCount": 0, "containerID": "containerd://c3"}]}),
+        );
+        index.update_pod(&other);
+        index.apply_event(&WatchEvent::Deleted(web("containerd://c3", None)));
+        assert_eq!(index.get("c3").unwrap().pod_name, "api-1");
+        assert_eq!(index.get("d0"), None);
+
+        index.refresh(&[]);
+        assert!(index.is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::dynamic::{build_request, list_query, to_json, with_query, Api, ObjectList};
+use crate::errors::KubernetesError;
+use crate::wait::wait_until;
+use http::Method;
+use k8s_openapi::ListOptional;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// What happens to the dependents of a deleted object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PropagationPolicy {
+    /// The object is removed after its dependents.
+    Foreground,
+    /// The object is removed at once, the garbage collector removes its dependents.
+    Background,
+    /// Dependents are kept, without owner.
+    Orphan,
+}
+
+impl PropagationPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PropagationPolicy::Foreground => "Foreground",
+            PropagationPolicy::Background => "Background",
+            PropagationPolicy::Orphan => "Orphan",
+        }
+    }
+}
+
+/// Parameters of a deletion, matching `metav1.DeleteOptions`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeleteOptions {
+    /// The API server default depends on the kind, usually `Background`.
+    pub propagation_policy: Option<PropagationPolicy>,
+    /// Overrides the grace period of pods. 0 deletes immediately.
+    pub grace_period_seconds: Option<i64>,
+    /// Only delete if the object still has this UID, i.e. wasn't recreated.
+    pub uid: Option<String>,
+    /// Only delete if the object wasn't modified since this version.
+    pub resource_version: Option<String>,
+    /// Validate the deletion without performing it.
+    pub dry_run: bool,
+    /// Block until the objects are actually gone, which takes time with
+    /// finalizers or foreground deletion, for at most this long.
+    pub wait: Option<Duration>,
+}
+
+impl DeleteOptions {
+    fn body(&self) -> Value {
+        let mut body = json!({"apiVersion": "v1", "kind": "DeleteOptions"});
+        if let Some(policy) = self.propagation_policy {
+            body["propagationPolicy"] = json!(policy.as_str());
+        }
+        if let Some(grace_period_seconds) = self.grace_period_seconds {
+            body["gracePeriodSeconds"] = json!(grace_period_seconds);
+        }
+        if self.uid.is_some() || self.resource_version.is_some() {
+            let mut preconditions = json!({});
+            if let Some(uid) = &self.uid {
+                preconditions["uid"] = json!(uid);
+            }
+            if let Some(resource_version) = &self.resource_version {
+                preconditions["resourceVersion"] = json!(resource_version);
+            }
+            body["preconditions"] = preconditions;
+        }
+        if self.dry_run {
+            body["dryRun"] = json!(["All"]);
+        }
+        body
+    }
+}
+
+impl<'a, K> Api<'a, K>
+where
+    K: Serialize + DeserializeOwned,
+{
+    /// Deletes the object `name`. Returns the object if it still exists, which is
+    /// the case when finalizers or foreground deletion delay its removal, unless
+    /// `wait` is set.
+    pub fn delete(
+        &self,
+        name: &str,
+        options: &DeleteOptions,
+    ) -> Result<Option<K>, KubernetesError> {
+        let request = build_request(
+            Method::DELETE,
+            self.object_path(name, None),
+            Some("application/json"),
+            to_json(&options.body())?,
+        )?;
+        let value: Value = self.client.request_json(request)?;
+        if is_status(&value) {
+            return Ok(None);
+        }
+        let uid = value.pointer("/metadata/uid").and_then(Value::as_str);
+        if let (Some(timeout), false) = (options.wait, options.dry_run) {
+            self.wait_deleted(name, uid, timeout)?;
+            return Ok(None);
+        }
+        serde_json::from_value(value)
+            .map(Some)
+            .map_err(|source| KubernetesError::JsonError { source })
+    }
+
+    /// Deletes every object matching the selectors of `list`, and returns the ones
+    /// the API server reported, if any.
+    ///
+    /// With `wait`, blocks until listing with the same selectors returns nothing.
+    pub fn delete_collection(
+        &self,
+        list: ListOptional,
+        options: &DeleteOptions,
+    ) -> Result<Vec<K>, KubernetesError> {
+        let request = build_request(
+            Method::DELETE,
+            with_query(self.collection_path(), list_query(&list)),
+            Some("application/json"),
+            to_json(&options.body())?,
+        )?;
+        let value: Value = self.client.request_json(request)?;
+        let deleted = if is_status(&value) {
+            vec![]
+        } else {
+            serde_json::from_value::<ObjectList<K>>(value)
+                .map_err(|source| KubernetesError::JsonError { source })?
+                .items
+        };
+        if let (Some(timeout), false) = (options.wait, options.dry_run) {
+            let what = format!(
+                "deletion of {} in {}",
+                self.resource().resource,
+                with_query(self.collection_path(), list_query(&list))
+            );
+            wait_until(&what, timeout, || {
+                let request = build_request(
+                    Method::GET,
+                    with_query(self.collection_path(), list_query(&list)),
+                    None,
+                    vec![],
+                )?;
+                let remaining: ObjectList<Value> = self.client.request_json(request)?;
+                Ok(remaining.items.is_empty())
+            })?;
+        }
+        Ok(deleted)
+    }
+
+    /// Blocks until the object `name` doesn't exist anymore, or was recreated with
+    /// another UID than `uid`.
+    pub fn wait_deleted(
+        &self,
+        name: &str,
+        uid: Option<&str>,
+        timeout: Duration,
+    ) -> Result<(), KubernetesError> {
+        let what = format!("deletion of {} {}", self.resource().resource, name);
+        wait_until(&what, timeout, || {
+            let request = build_request(Method::GET, self.object_path(name, None), None, vec![])?;
+            match self.client.request_json::<Value>(request) {
+                Ok(object) => {
+                    Ok(uid.is_some()
+                        && object.pointer("/metadata/uid").and_then(Value::as_str) != uid)
+                }
+                Err(err) if err.is_not_found() => Ok(true),
+                Err(err) => Err(err),
+            }
+        })
+    }
+}
+
+fn is_status(value: &Value) -> bool {
+    value.get("kind").and_then(Value::as_str) == Some("Status")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body() {
+        assert_eq!(
+            DeleteOptions::default().body(),
+            json!({"apiVersion": "v1", "kind": "DeleteOptions"})
+        );
+        let options = DeleteOptions {
+            propagation_policy: Some(PropagationPolicy::Foreground),
+            grace_period_seconds: Some(0),
+            uid: Some(String::from("5c1f")),
+            dry_run: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            options.body(),
+            json!({
+                "apiVersion": "v1",
+                "kind": "DeleteOptions",
+                "propagationPolicy": "Foreground",
+                "gracePeriodSeconds": 0,
+                "preconditions": {"uid": "5c1f"},
+                "dryRun": ["All"]
+            })
+        );
+    }
+}
@@ -0,0 +1,619 @@
+use crate::dynamic::GroupVersionResource;
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use dirs::home_dir;
+use http::{header::ACCEPT, Request};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{
+    APIGroup, APIGroupList, APIResource, APIResourceList, GroupVersionForDiscovery,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Accept header asking for the aggregated discovery documents (Kubernetes 1.26+),
+/// falling back to the legacy per group/version documents on older servers.
+const AGGREGATED_ACCEPT: &str = "application/json;g=apidiscovery.k8s.io;v=v2;as=APIGroupDiscoveryList,application/json;g=apidiscovery.k8s.io;v=v2beta1;as=APIGroupDiscoveryList,application/json";
+
+/// A resource served by the API server, as found through discovery.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiResource {
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+    /// Plural name, as used in URLs.
+    pub resource: String,
+    pub singular: String,
+    pub namespaced: bool,
+    pub verbs: Vec<String>,
+    pub short_names: Vec<String>,
+    /// Names of the subresources (`status`, `scale`, `log`...).
+    pub subresources: Vec<String>,
+}
+
+impl ApiResource {
+    pub fn gvr(&self) -> GroupVersionResource {
+        GroupVersionResource::new(&self.group, &self.version, &self.resource)
+    }
+
+    pub fn api_version(&self) -> String {
+        self.gvr().api_version()
+    }
+
+    pub fn supports(&self, verb: &str) -> bool {
+        self.verbs.iter().any(|v| v == verb)
+    }
+}
+
+/// An API group and the resources of each of its versions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiGroup {
+    /// Empty for the core (legacy) group.
+    pub name: String,
+    /// Versions, preferred first.
+    pub versions: Vec<String>,
+    pub preferred_version: String,
+    pub resources: Vec<ApiResource>,
+}
+
+/// A group version whose resources couldn't be discovered, e.g. because the
+/// aggregated API server behind it (`metrics.k8s.io/v1beta1`...) is down.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailedGroupVersion {
+    /// `group/version`, or `v1` for core.
+    pub group_version: String,
+    pub message: String,
+}
+
+/// What the API server serves: groups, versions and resources.
+///
+/// Like kubectl, discovery skips the group versions it can't fetch instead of
+/// failing: they are listed in `failed_groups`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Discovery {
+    pub groups: Vec<ApiGroup>,
+    pub failed_groups: Vec<FailedGroupVersion>,
+}
+
+impl Discovery {
+    pub fn group(&self, name: &str) -> Option<&ApiGroup> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    pub fn preferred_version(&self, group: &str) -> Option<&str> {
+        self.group(group).map(|g| g.preferred_version.as_str())
+    }
+
+    /// The failure of `api_version`, if its discovery failed.
+    pub fn failed(&self, api_version: &str) -> Option<&FailedGroupVersion> {
+        self.failed_groups
+            .iter()
+            .find(|f| f.group_version == api_version)
+    }
+
+    pub fn resources(&self) -> impl Iterator<Item = &ApiResource> {
+        self.groups.iter().flat_map(|g| g.resources.iter())
+    }
+
+    /// Resource serving `kind` in `api_version` (`group/version`, or `v1` for core).
+    pub fn resolve(&self, api_version: &str, kind: &str) -> Option<&ApiResource> {
+        let (group, version) = split_api_version(api_version);
+        self.resources()
+            .find(|r| r.group == group && r.version == version && r.kind == kind)
+    }
+
+    /// Resource serving `kind` in the preferred version of `group`.
+    pub fn resolve_preferred(&self, group: &str, kind: &str) -> Option<&ApiResource> {
+        let group = self.group(group)?;
+        group
+            .resources
+            .iter()
+            .find(|r| r.version == group.preferred_version && r.kind == kind)
+            .or_else(|| group.resources.iter().find(|r| r.kind == kind))
+    }
+
+    /// Resource by plural, singular or short name, as accepted by kubectl
+    /// (`deployments`, `deployment`, `deploy`), in the preferred version.
+    pub fn resolve_name(&self, name: &str) -> Option<&ApiResource> {
+        let name = name.to_lowercase();
+        self.groups.iter().find_map(|group| {
+            group.resources.iter().find(|r| {
+                r.version == group.preferred_version
+                    && (r.resource == name || r.singular == name || r.short_names.contains(&name))
+            })
+        })
+    }
+
+    fn from_documents(groups: APIGroupList, resources: Vec<APIResourceList>) -> Self {
+        let groups = groups
+            .groups
+            .into_iter()
+            .map(|group| {
+                let versions: Vec<String> =
+                    group.versions.iter().map(|v| v.version.clone()).collect();
+                let preferred_version = group
+                    .preferred_version
+                    .as_ref()
+                    .map(|v| v.version.clone())
+                    .or_else(|| versions.first().cloned())
+                    .unwrap_or_default();
+                let resources = resources
+                    .iter()
+                    .filter(|list| split_api_version(&list.group_version).0 == group.name)
+                    .flat_map(api_resources)
+                    .collect();
+                ApiGroup {
+                    name: group.name,
+                    versions,
+                    preferred_version,
+                    resources,
+                }
+            })
+            .collect();
+        Discovery {
+            groups,
+            failed_groups: vec![],
+        }
+    }
+}
+
+/// Splits `apps/v1` into `("apps", "v1")` and `v1` into `("", "v1")`.
+pub fn split_api_version(api_version: &str) -> (&str, &str) {
+    match api_version.split_once('/') {
+        Some((group, version)) => (group, version),
+        None => ("", api_version),
+    }
+}
+
+fn api_resources(list: &APIResourceList) -> Vec<ApiResource> {
+    let (group, version) = split_api_version(&list.group_version);
+    let mut resources: Vec<ApiResource> = list
+        .resources
+        .iter()
+        .filter(|r| !r.name.contains('/'))
+        .map(|r| ApiResource {
+            group: r.group.clone().unwrap_or_else(|| group.to_string()),
+            version: r.version.clone().unwrap_or_else(|| version.to_string()),
+            kind: r.kind.clone(),
+            resource: r.name.clone(),
+            singular: if r.singular_name.is_empty() {
+                r.kind.to_lowercase()
+            } else {
+                r.singular_name.clone()
+            },
+            namespaced: r.namespaced,
+            verbs: r.verbs.clone(),
+            short_names: r.short_names.clone().unwrap_or_default(),
+            subresources: vec![],
+        })
+        .collect();
+    for r in list.resources.iter() {
+        if let Some((parent, subresource)) = r.name.split_once('/') {
+            if let Some(parent) = resources.iter_mut().find(|p| p.resource == parent) {
+                parent.subresources.push(subresource.to_string());
+            }
+        }
+    }
+    resources
+}
+
+/// On-disk cache of the discovery documents, laid out like kubectl's:
+/// `<dir>/<host>_<port>/servergroups.json` and
+/// `<dir>/<host>_<port>/<group>/<version>/serverresources.json`.
+#[derive(Clone, Debug)]
+pub struct DiscoveryCache {
+    pub dir: PathBuf,
+    pub ttl: Duration,
+}
+
+impl DiscoveryCache {
+    /// Same TTL as kubectl.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+    pub fn new<P: AsRef<Path>>(dir: P, ttl: Duration) -> Self {
+        DiscoveryCache {
+            dir: dir.as_ref().to_path_buf(),
+            ttl,
+        }
+    }
+
+    /// `~/.kube/cache/discovery`, if the home directory is known.
+    pub fn default_dir() -> Option<PathBuf> {
+        home_dir().map(|h| h.join(".kube").join("cache").join("discovery"))
+    }
+
+    /// Directory holding the documents of the server at `base_uri`.
+    fn server_dir(&self, base_uri: &str) -> PathBuf {
+        let host = base_uri.split("://").last().unwrap_or(base_uri);
+        let host: String = host
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '.' || c == '/' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(host.trim_end_matches('/'))
+    }
+
+    fn groups_path(&self, base_uri: &str) -> PathBuf {
+        self.server_dir(base_uri).join("servergroups.json")
+    }
+
+    fn resources_path(&self, base_uri: &str, group_version: &str) -> PathBuf {
+        let (group, version) = split_api_version(group_version);
+        let mut path = self.server_dir(base_uri);
+        if !group.is_empty() {
+            path.push(group);
+        }
+        path.join(version).join("serverresources.json")
+    }
+
+    /// Reads a cached document, unless it is missing, invalid or older than the TTL.
+    fn read<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age > self.ttl {
+            return None;
+        }
+        serde_json::from_slice(&fs::read(path).ok()?).ok()
+    }
+
+    fn write<T: Serialize>(&self, path: &Path, document: &T) -> Result<(), KubernetesError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|source| KubernetesError::IoError { source })?;
+        }
+        let raw =
+            serde_json::to_vec(document).map_err(|source| KubernetesError::JsonError { source })?;
+        fs::write(path, raw).map_err(|source| KubernetesError::IoError { source })
+    }
+
+    /// Replaces the cached documents of the server at `base_uri`.
+    fn store(
+        &self,
+        base_uri: &str,
+        groups: &APIGroupList,
+        resources: &[APIResourceList],
+    ) -> Result<(), KubernetesError> {
+        self.invalidate(base_uri)?;
+        self.write(&self.groups_path(base_uri), groups)?;
+        for list in resources {
+            self.write(&self.resources_path(base_uri, &list.group_version), list)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the cached documents of the server at `base_uri`.
+    pub fn invalidate(&self, base_uri: &str) -> Result<(), KubernetesError> {
+        match fs::remove_dir_all(self.server_dir(base_uri)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(KubernetesError::IoError { source: err })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for DiscoveryCache {
+    fn default() -> Self {
+        DiscoveryCache::new(
+            Self::default_dir().unwrap_or_else(|| std::env::temp_dir().join("kube-discovery")),
+            Self::DEFAULT_TTL,
+        )
+    }
+}
+
+impl Kubernetes {
+    pub fn with_discovery_cache(mut self, discovery_cache: Option<DiscoveryCache>) -> Self {
+        self.discovery_cache = discovery_cache;
+        self
+    }
+
+    /// Discovers the groups, versions and resources served by the API server,
+    /// reading documents from the discovery cache while they are fresh.
+    pub fn discovery(&self) -> Result<Discovery, KubernetesError> {
+        if let Some(cache) = &self.discovery_cache {
+            if let Some(groups) = cache.read::<APIGroupList>(&cache.groups_path(&self.base_uri)) {
+                let cached: Option<Vec<APIResourceList>> = group_versions(&groups)
+                    .iter()
+                    .map(|gv| cache.read(&cache.resources_path(&self.base_uri, gv)))
+                    .collect();
+                if let Some(resources) = cached {
+                    return Ok(Discovery::from_documents(groups, resources));
+                }
+            }
+        }
+        self.refresh_discovery()
+    }
+
+    /// Like `discovery`, ignoring and then updating the cached documents.
+    pub fn refresh_discovery(&self) -> Result<Discovery, KubernetesError> {
+        let (groups, resources, failed_groups) = self.fetch_discovery()?;
+        if let Some(cache) = &self.discovery_cache {
+            // Incomplete documents aren't cached, so that the next discovery tries
+            // the failed group versions again.
+            if failed_groups.is_empty() {
+                // The cache only saves requests: failing to update it doesn't fail
+                // discovery.
+                let _ = cache.store(&self.base_uri, &groups, &resources);
+            }
+        }
+        Ok(Discovery {
+            failed_groups,
+            ..Discovery::from_documents(groups, resources)
+        })
+    }
+
+    /// Fetches the discovery documents, in the legacy format used by the cache,
+    /// skipping the group versions that fail.
+    fn fetch_discovery(
+        &self,
+    ) -> Result<(APIGroupList, Vec<APIResourceList>, Vec<FailedGroupVersion>), KubernetesError>
+    {
+        let mut groups = vec![];
+        let mut resources = vec![];
+        let mut failed_groups = vec![];
+        for path in &["/api", "/apis"] {
+            let document = self.discovery_document(path)?;
+            if document.get("kind").and_then(Value::as_str) == Some("APIGroupDiscoveryList") {
+                let aggregated: AggregatedDiscovery = serde_json::from_value(document)
+                    .map_err(|source| KubernetesError::JsonError { source })?;
+                for item in aggregated.items {
+                    let (group, lists) = item.into_legacy();
+                    groups.push(group);
+                    resources.extend(lists);
+                }
+                continue;
+            }
+            let legacy_groups = if *path == "/api" {
+                // APIVersions: the core group, which has no name.
+                let versions = document
+                    .get("versions")
+                    .and_then(Value::as_array)
+                    .map(|v| v.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                vec![api_group("", versions)]
+            } else {
+                serde_json::from_value::<APIGroupList>(document)
+                    .map_err(|source| KubernetesError::JsonError { source })?
+                    .groups
+            };
+            for group in &legacy_groups {
+                for version in &group.versions {
+                    let path = if group.name.is_empty() {
+                        format!("/api/{}", version.version)
+                    } else {
+                        format!("/apis/{}", version.group_version)
+                    };
+                    let list = self.discovery_document(&path).and_then(|document| {
+                        serde_json::from_value::<APIResourceList>(document)
+                            .map_err(|source| KubernetesError::JsonError { source })
+                    });
+                    match list {
+                        Ok(list) => resources.push(list),
+                        Err(err) => failed_groups.push(FailedGroupVersion {
+                            group_version: version.group_version.clone(),
+                            message: err.to_string(),
+                        }),
+                    }
+                }
+            }
+            groups.extend(legacy_groups);
+        }
+        Ok((APIGroupList { groups }, resources, failed_groups))
+    }
+
+    fn discovery_document(&self, path: &str) -> Result<Value, KubernetesError> {
+        let request = Request::get(path)
+            .header(ACCEPT, AGGREGATED_ACCEPT)
+            .body(vec![])
+            .map_err(|err| KubernetesError::HttpClientBuildError {
+                message: format!("Couldn't build request. Error: {:?}", err),
+            })?;
+        self.request_json(request)
+    }
+}
+
+fn group_versions(groups: &APIGroupList) -> Vec<String> {
+    groups
+        .groups
+        .iter()
+        .flat_map(|g| g.versions.iter().map(|v| v.group_version.clone()))
+        .collect()
+}
+
+fn api_group(name: &str, versions: Vec<&str>) -> APIGroup {
+    let versions: Vec<GroupVersionForDiscovery> = versions
+        .into_iter()
+        .map(|version| GroupVersionForDiscovery {
+            group_version: if name.is_empty() {
+                version.to_string()
+            } else {
+                format!("{}/{}", name, version)
+            },
+            version: version.to_string(),
+        })
+        .collect();
+    APIGroup {
+        name: name.to_string(),
+        preferred_version: versions.first().cloned(),
+        server_address_by_client_cidrs: None,
+        versions,
+    }
+}
+
+/// `apidiscovery.k8s.io` APIGroupDiscoveryList, only the fields used here.
+#[derive(Deserialize)]
+struct AggregatedDiscovery {
+    #[serde(default)]
+    items: Vec<AggregatedGroup>,
+}
+
+#[derive(Deserialize)]
+struct AggregatedGroup {
+    #[serde(default)]
+    metadata: AggregatedMetadata,
+    /// Versions, preferred first.
+    #[serde(default)]
+    versions: Vec<AggregatedVersion>,
+}
+
+#[derive(Default, Deserialize)]
+struct AggregatedMetadata {
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct AggregatedVersion {
+    version: String,
+    #[serde(default)]
+    resources: Vec<AggregatedResource>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AggregatedResource {
+    resource: String,
+    response_kind: Option<GroupVersionKind>,
+    scope: String,
+    #[serde(default)]
+    singular_resource: String,
+    #[serde(default)]
+    verbs: Vec<String>,
+    short_names: Option<Vec<String>>,
+    categories: Option<Vec<String>>,
+    #[serde(default)]
+    subresources: Vec<AggregatedSubresource>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AggregatedSubresource {
+    subresource: String,
+    response_kind: Option<GroupVersionKind>,
+    #[serde(default)]
+    verbs: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct GroupVersionKind {
+    #[serde(default)]
+    kind: String,
+}
+
+impl AggregatedGroup {
+    fn into_legacy(self) -> (APIGroup, Vec<APIResourceList>) {
+        let name = self.metadata.name;
+        let group = api_group(
+            &name,
+            self.versions.iter().map(|v| v.version.as_str()).collect(),
+        );
+        let lists = self
+            .versions
+            .into_iter()
+            .map(|version| {
+                let mut resources = vec![];
+                for r in version.resources {
+                    let namespaced = r.scope == "Namespaced";
+                    for sub in &r.subresources {
+                        resources.push(APIResource {
+                            name: format!("{}/{}", r.resource, sub.subresource),
+                            kind: sub
+                                .response_kind
+                                .as_ref()
+                                .map(|k| k.kind.clone())
+                                .unwrap_or_default(),
+                            namespaced,
+                            verbs: sub.verbs.clone(),
+                            ..Default::default()
+                        });
+                    }
+                    resources.push(APIResource {
+                        categories: r.categories,
+                        kind: r.response_kind.map(|k| k.kind).unwrap_or_default(),
+                        name: r.resource,
+                        namespaced,
+                        short_names: r.short_names,
+                        singular_name: r.singular_resource,
+                        verbs: r.verbs,
+                        ..Default::default()
+                    });
+                }
+                APIResourceList {
+                    group_version: if name.is_empty() {
+                        version.version
+                    } else {
+                        format!("{}/{}", name, version.version)
+                    },
+                    resources,
+                }
+            })
+            .collect();
+        (group, lists)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_api_version() {
+        assert_eq!(split_api_version("apps/v1"), ("apps", "v1"));
+        assert_eq!(split_api_version("v1"), ("", "v1"));
+    }
+
+    #[test]
+    fn test_cache_layout() {
+        let cache = DiscoveryCache::new("/cache", DiscoveryCache::DEFAULT_TTL);
+        assert_eq!(
+            cache.groups_path("https://10.0.0.1:6443"),
+            PathBuf::from("/cache/10.0.0.1_6443/servergroups.json")
+        );
+        assert_eq!(
+            cache.resources_path("https://10.0.0.1:6443", "apps/v1"),
+            PathBuf::from("/cache/10.0.0.1_6443/apps/v1/serverresources.json")
+        );
+        assert_eq!(
+            cache.resources_path("https://10.0.0.1:6443", "v1"),
+            PathBuf::from("/cache/10.0.0.1_6443/v1/serverresources.json")
+        );
+    }
+
+    #[test]
+    fn test_aggregated_to_legacy() {
+        let group: AggregatedGroup = serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "apps"},
+            "versions": [{"version": "v1", "resources": [{
+                "resource": "deployments",
+                "responseKind": {"group": "apps", "version": "v1", "kind": "Deployment"},
+                "scope": "Namespaced",
+                "singularResource": "deployment",
+                "shortNames": ["deploy"],
+                "verbs": ["get", "list", "patch"],
+                "subresources": [{"subresource": "scale", "responseKind": {"kind": "Scale"}, "verbs": ["get"]}]
+            }]}]
+        }))
+        .unwrap();
+        let (group, lists) = group.into_legacy();
+        let discovery = Discovery::from_documents(
+            APIGroupList {
+                groups: vec![group],
+            },
+            lists,
+        );
+        let deployments = discovery.resolve("apps/v1", "Deployment").unwrap();
+        assert!(deployments.namespaced);
+        assert!(deployments.supports("patch"));
+        assert_eq!(deployments.subresources, vec!["scale"]);
+        assert_eq!(discovery.resolve_name("deploy"), Some(deployments));
+        assert_eq!(discovery.preferred_version("apps"), Some("v1"));
+    }
+}
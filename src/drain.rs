@@ -0,0 +1,392 @@
+use crate::dynamic::{build_request, to_json};
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use crate::patch::Patch;
+use http::{Method, StatusCode};
+use k8s_openapi::api::core::v1::{Node, Pod};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIGroup;
+use k8s_openapi::{ListOptional, PatchOptional};
+use serde_json::json;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Annotation set by the kubelet on the API server copy of static pods.
+const MIRROR_ANNOTATION: &str = "kubernetes.io/config.mirror";
+
+/// Stand-in for "no timeout" when waiting.
+const FOREVER: Duration = Duration::from_secs(365 * 24 * 3600);
+
+/// Parameters of a drain, matching the `kubectl drain` flags.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrainOptions {
+    /// Skip pods managed by a DaemonSet instead of failing: they would be recreated
+    /// on the node anyway.
+    pub ignore_daemonsets: bool,
+    /// Evict pods using `emptyDir` volumes, whose data is lost.
+    pub delete_emptydir_data: bool,
+    /// Evict pods not managed by a controller, which won't be recreated.
+    pub force: bool,
+    /// Overrides the grace period of the evicted pods.
+    pub grace_period_seconds: Option<i64>,
+    /// Give up after this long. `None`, or a duration too large to be a deadline,
+    /// waits as long as needed.
+    pub timeout: Option<Duration>,
+    /// Validate every step with the API server without changing anything.
+    pub dry_run: bool,
+    /// Delay before retrying an eviction refused by a PodDisruptionBudget.
+    pub eviction_retry_interval: Duration,
+}
+
+impl Default for DrainOptions {
+    fn default() -> Self {
+        DrainOptions {
+            ignore_daemonsets: false,
+            delete_emptydir_data: false,
+            force: false,
+            grace_period_seconds: None,
+            timeout: None,
+            dry_run: false,
+            eviction_retry_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Outcome of a drain. Pods are named `namespace/name`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DrainReport {
+    pub evicted: Vec<String>,
+    /// Pods left on the node: DaemonSet and mirror pods.
+    pub skipped: Vec<String>,
+    /// Pods evicted despite the data or controller they'd lose.
+    pub warnings: Vec<String>,
+}
+
+/// What to do with a pod of the drained node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Disposition {
+    Evict,
+    /// Evict, with a warning about what it implies.
+    EvictWithWarning(String),
+    Skip,
+    /// The drain can't proceed without an option allowing it.
+    Blocked(String),
+}
+
+fn disposition(pod: &Pod, options: &DrainOptions) -> Disposition {
+    let metadata = &pod.metadata;
+    if metadata
+        .annotations
+        .as_ref()
+        .is_some_and(|a| a.contains_key(MIRROR_ANNOTATION))
+    {
+        return Disposition::Skip;
+    }
+    let phase = pod.status.as_ref().and_then(|s| s.phase.as_deref());
+    if matches!(phase, Some("Succeeded") | Some("Failed")) {
+        return Disposition::Evict;
+    }
+    let controller = metadata
+        .owner_references
+        .iter()
+        .flatten()
+        .find(|o| o.controller == Some(true));
+    let mut warnings = vec![];
+    match controller {
+        Some(owner) if owner.kind == "DaemonSet" => {
+            return if options.ignore_daemonsets {
+                Disposition::Skip
+            } else {
+                Disposition::Blocked(String::from(
+                    "managed by a DaemonSet (use ignore_daemonsets)",
+                ))
+            };
+        }
+        Some(_) => {}
+        None if options.force => {
+            warnings.push("not managed by a controller, won't be recreated");
+        }
+        None => {
+            return Disposition::Blocked(String::from("not managed by a controller (use force)"))
+        }
+    }
+    let empty_dir = pod
+        .spec
+        .as_ref()
+        .and_then(|s| s.volumes.as_ref())
+        .is_some_and(|v| v.iter().any(|v| v.empty_dir.is_some()));
+    if empty_dir {
+        if !options.delete_emptydir_data {
+            return Disposition::Blocked(String::from(
+                "uses emptyDir volumes (use delete_emptydir_data)",
+            ));
+        }
+        warnings.push("emptyDir data will be lost");
+    }
+    if warnings.is_empty() {
+        Disposition::Evict
+    } else {
+        Disposition::EvictWithWarning(warnings.join(", "))
+    }
+}
+
+fn pod_name(pod: &Pod) -> String {
+    format!(
+        "{}/{}",
+        pod.metadata.namespace.as_deref().unwrap_or_default(),
+        pod.metadata.name.as_deref().unwrap_or_default()
+    )
+}
+
+impl Kubernetes {
+    /// Marks a node unschedulable, like `kubectl cordon`.
+    pub fn cordon(&self, node: &str, dry_run: bool) -> Result<Node, KubernetesError> {
+        self.set_unschedulable(node, true, dry_run)
+    }
+
+    /// Marks a node schedulable again, like `kubectl uncordon`.
+    pub fn uncordon(&self, node: &str, dry_run: bool) -> Result<Node, KubernetesError> {
+        self.set_unschedulable(node, false, dry_run)
+    }
+
+    fn set_unschedulable(
+        &self,
+        node: &str,
+        unschedulable: bool,
+        dry_run: bool,
+    ) -> Result<Node, KubernetesError> {
+        let patch = Patch::Strategic(json!({ "spec": { "unschedulable": unschedulable } }));
+        let optional = PatchOptional {
+            dry_run: if dry_run { Some("All") } else { None },
+            ..Default::default()
+        };
+        self.api::<Node>(None).patch(node, &patch, optional)
+    }
+
+    /// Evicts a pod through its `eviction` subresource, which honors
+    /// PodDisruptionBudgets. Returns false when a budget refused the eviction
+    /// (`429 Too Many Requests`), in which case it can be tried again later.
+    pub fn evict(
+        &self,
+        namespace: &str,
+        pod: &str,
+        grace_period_seconds: Option<i64>,
+        dry_run: bool,
+    ) -> Result<bool, KubernetesError> {
+        let api_version = self.eviction_api_version()?;
+        self.evict_with(&api_version, namespace, pod, grace_period_seconds, dry_run)
+    }
+
+    fn evict_with(
+        &self,
+        api_version: &str,
+        namespace: &str,
+        pod: &str,
+        grace_period_seconds: Option<i64>,
+        dry_run: bool,
+    ) -> Result<bool, KubernetesError> {
+        let mut delete_options = json!({});
+        if let Some(grace_period_seconds) = grace_period_seconds {
+            delete_options["gracePeriodSeconds"] = json!(grace_period_seconds);
+        }
+        if dry_run {
+            delete_options["dryRun"] = json!(["All"]);
+        }
+        let eviction = json!({
+            "apiVersion": api_version,
+            "kind": "Eviction",
+            "metadata": { "name": pod, "namespace": namespace },
+            "deleteOptions": delete_options,
+        });
+        let request = build_request(
+            Method::POST,
+            self.api::<Pod>(Some(namespace))
+                .object_path(pod, Some("eviction")),
+            Some("application/json"),
+            to_json(&eviction)?,
+        )?;
+        match self.send(request) {
+            Ok(_) => Ok(true),
+            Err(err) if err.status_code() == Some(StatusCode::TOO_MANY_REQUESTS) => Ok(false),
+            // Already gone.
+            Err(err) if err.is_not_found() => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// `policy/v1` when served (Kubernetes 1.22+), `policy/v1beta1` otherwise.
+    fn eviction_api_version(&self) -> Result<String, KubernetesError> {
+        let request = build_request(Method::GET, String::from("/apis/policy"), None, vec![])?;
+        let group: APIGroup = self.request_json(request)?;
+        let v1 = group.versions.iter().any(|v| v.version == "v1");
+        Ok(String::from(if v1 {
+            "policy/v1"
+        } else {
+            "policy/v1beta1"
+        }))
+    }
+
+    /// Drains a node like `kubectl drain`: cordons it, evicts its pods and waits
+    /// until they are gone.
+    ///
+    /// Fails with `KubernetesError::DrainError` before evicting anything when some
+    /// pods can't be evicted with the given options, the node staying cordoned.
+    ///
+    /// Pods are evicted one at a time, in list order: a pod whose eviction a
+    /// PodDisruptionBudget refuses holds back the following ones until it goes
+    /// through or `timeout` runs out. Deletions are awaited once all are evicted.
+    pub fn drain(
+        &self,
+        node: &str,
+        options: &DrainOptions,
+    ) -> Result<DrainReport, KubernetesError> {
+        let deadline = options.timeout.and_then(|t| Instant::now().checked_add(t));
+        let remaining = || match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => FOREVER,
+        };
+
+        self.cordon(node, options.dry_run)?;
+        let field_selector = format!("spec.nodeName={}", node);
+        let pods = self
+            .api::<Pod>(None)
+            .list(ListOptional {
+                field_selector: Some(&field_selector),
+                ..Default::default()
+            })?
+            .items;
+
+        let mut report = DrainReport::default();
+        let mut to_evict = vec![];
+        let mut blocked = vec![];
+        for pod in pods {
+            match disposition(&pod, options) {
+                Disposition::Evict => to_evict.push(pod),
+                Disposition::EvictWithWarning(warning) => {
+                    report
+                        .warnings
+                        .push(format!("{}: {}", pod_name(&pod), warning));
+                    to_evict.push(pod);
+                }
+                Disposition::Skip => report.skipped.push(pod_name(&pod)),
+                Disposition::Blocked(reason) => {
+                    blocked.push(format!("{}: {}", pod_name(&pod), reason))
+                }
+            }
+        }
+        if !blocked.is_empty() {
+            return Err(KubernetesError::DrainError {
+                node: node.to_string(),
+                blocked,
+            });
+        }
+        if to_evict.is_empty() {
+            return Ok(report);
+        }
+
+        let api_version = self.eviction_api_version()?;
+        for pod in &to_evict {
+            let namespace = pod.metadata.namespace.as_deref().unwrap_or_default();
+            let name = pod.metadata.name.as_deref().unwrap_or_default();
+            while !self.evict_with(
+                &api_version,
+                namespace,
+                name,
+                options.grace_period_seconds,
+                options.dry_run,
+            )? {
+                if remaining() < options.eviction_retry_interval {
+                    return Err(KubernetesError::WaitTimeoutError {
+                        what: format!("eviction of pod {}", pod_name(pod)),
+                        timeout: options.timeout.unwrap_or(FOREVER),
+                    });
+                }
+                thread::sleep(options.eviction_retry_interval);
+            }
+            report.evicted.push(pod_name(pod));
+        }
+
+        if !options.dry_run {
+            for pod in &to_evict {
+                self.api::<Pod>(pod.metadata.namespace.as_deref())
+                    .wait_deleted(
+                        pod.metadata.name.as_deref().unwrap_or_default(),
+                        pod.metadata.uid.as_deref(),
+                        remaining(),
+                    )
+                    .map_err(|err| match err {
+                        KubernetesError::WaitTimeoutError { what, .. } => {
+                            KubernetesError::WaitTimeoutError {
+                                what,
+                                timeout: options.timeout.unwrap_or(FOREVER),
+                            }
+                        }
+                        err => err,
+                    })?;
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod(json: serde_json::Value) -> Pod {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_disposition() {
+        let options = DrainOptions::default();
+        let replica = pod(json!({
+            "metadata": {"name": "web-1", "ownerReferences": [
+                {"apiVersion": "apps/v1", "kind": "ReplicaSet", "name": "web", "uid": "1", "controller": true}
+            ]}
+        }));
+        assert_eq!(disposition(&replica, &options), Disposition::Evict);
+
+        let daemon = pod(json!({
+            "metadata": {"name": "fluentd", "ownerReferences": [
+                {"apiVersion": "apps/v1", "kind": "DaemonSet", "name": "fluentd", "uid": "2", "controller": true}
+            ]}
+        }));
+        assert!(matches!(
+            disposition(&daemon, &options),
+            Disposition::Blocked(_)
+        ));
+        let ignore_daemonsets = DrainOptions {
+            ignore_daemonsets: true,
+            ..Default::default()
+        };
+        assert_eq!(disposition(&daemon, &ignore_daemonsets), Disposition::Skip);
+
+        let mirror = pod(json!({
+            "metadata": {"name": "etcd", "annotations": {"kubernetes.io/config.mirror": "abc"}}
+        }));
+        assert_eq!(disposition(&mirror, &options), Disposition::Skip);
+
+        let bare = pod(json!({
+            "metadata": {"name": "debug"},
+            "spec": {"containers": [], "volumes": [{"name": "scratch", "emptyDir": {}}]}
+        }));
+        assert!(matches!(
+            disposition(&bare, &options),
+            Disposition::Blocked(_)
+        ));
+        let forced = DrainOptions {
+            force: true,
+            delete_emptydir_data: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            disposition(&bare, &forced),
+            Disposition::EvictWithWarning(String::from(
+                "not managed by a controller, won't be recreated, emptyDir data will be lost"
+            ))
+        );
+
+        let completed = pod(json!({"metadata": {"name": "job"}, "status": {"phase": "Succeeded"}}));
+        assert_eq!(disposition(&completed, &options), Disposition::Evict);
+    }
+}
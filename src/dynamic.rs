@@ -0,0 +1,487 @@
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use crate::patch::Patch;
+use http::{header::CONTENT_TYPE, Method, Request};
+use isahc::Body;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ListMeta, ObjectMeta, WatchEvent};
+use k8s_openapi::{CreateOptional, ListOptional, PatchOptional, ReplaceOptional};
+use k8s_openapi::{Resource, WatchOptional};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use std::marker::PhantomData;
+use url::form_urlencoded;
+
+/// GroupVersionResource identifies a kind of resource served by the API server,
+/// e.g. `cert-manager.io/v1` `certificates`. The group is empty for the core API.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GroupVersionResource {
+    pub group: String,
+    pub version: String,
+    /// Plural, lower case name of the resource, as used in URLs.
+    pub resource: String,
+}
+
+impl GroupVersionResource {
+    pub fn new(group: &str, version: &str, resource: &str) -> Self {
+        GroupVersionResource {
+            group: group.to_string(),
+            version: version.to_string(),
+            resource: resource.to_string(),
+        }
+    }
+
+    /// The resource served for a `k8s_openapi` type.
+    pub fn of<K: Resource>() -> Self {
+        Self::new(K::GROUP, K::VERSION, K::URL_PATH_SEGMENT)
+    }
+
+    /// `group/version`, or just `version` for the core API.
+    pub fn api_version(&self) -> String {
+        if self.group.is_empty() {
+            self.version.clone()
+        } else {
+            format!("{}/{}", self.group, self.version)
+        }
+    }
+
+    /// Path of the collection, in `namespace` if any.
+    pub fn url_path(&self, namespace: Option<&str>) -> String {
+        let mut path = if self.group.is_empty() {
+            format!("/api/{}", self.version)
+        } else {
+            format!("/apis/{}/{}", self.group, self.version)
+        };
+        if let Some(namespace) = namespace {
+            path.push_str(&format!("/namespaces/{}", namespace));
+        }
+        path.push('/');
+        path.push_str(&self.resource);
+        path
+    }
+}
+
+/// An object of any kind, kept as JSON, with its metadata decoded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DynamicObject {
+    #[serde(rename = "apiVersion", skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    /// Every other field of the object (`spec`, `status`, `data`...).
+    #[serde(flatten)]
+    pub data: Value,
+}
+
+impl Default for DynamicObject {
+    fn default() -> Self {
+        DynamicObject {
+            api_version: None,
+            kind: None,
+            metadata: Default::default(),
+            data: Value::Object(Default::default()),
+        }
+    }
+}
+
+impl DynamicObject {
+    pub fn new(api_version: &str, kind: &str, name: &str) -> Self {
+        DynamicObject {
+            api_version: Some(api_version.to_string()),
+            kind: Some(kind.to_string()),
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.metadata.name.as_deref()
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.metadata.namespace.as_deref()
+    }
+
+    pub fn uid(&self) -> Option<&str> {
+        self.metadata.uid.as_deref()
+    }
+
+    pub fn resource_version(&self) -> Option<&str> {
+        self.metadata.resource_version.as_deref()
+    }
+
+    pub fn generation(&self) -> Option<i64> {
+        self.metadata.generation
+    }
+
+    pub fn labels(&self) -> Option<&BTreeMap<String, String>> {
+        self.metadata.labels.as_ref()
+    }
+
+    pub fn annotations(&self) -> Option<&BTreeMap<String, String>> {
+        self.metadata.annotations.as_ref()
+    }
+
+    /// Field of the object designated by a JSON pointer, e.g. `/status/phase`.
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        self.data.pointer(pointer)
+    }
+
+    /// Converts the object into a typed one, e.g. a `k8s_openapi` struct.
+    pub fn try_parse<K: DeserializeOwned>(&self) -> Result<K, KubernetesError> {
+        let value =
+            serde_json::to_value(self).map_err(|source| KubernetesError::JsonError { source })?;
+        serde_json::from_value(value).map_err(|source| KubernetesError::JsonError { source })
+    }
+}
+
+/// A list of objects as returned by the `list` verb.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ObjectList<K> {
+    #[serde(default)]
+    pub metadata: ListMeta,
+    #[serde(default = "Vec::new")]
+    pub items: Vec<K>,
+}
+
+/// Verbs on a kind of resource, in a namespace or cluster-wide.
+///
+/// `K` is the type objects are decoded to: `DynamicObject` for arbitrary resources
+/// (see `Kubernetes::dynamic`) or a `k8s_openapi` type (see `Kubernetes::api`).
+#[derive(Debug)]
+pub struct Api<'a, K> {
+    pub(crate) client: &'a Kubernetes,
+    resource: GroupVersionResource,
+    namespace: Option<String>,
+    kind: PhantomData<K>,
+}
+
+impl Kubernetes {
+    /// Untyped access to `resource`, for CRDs and any kind `k8s_openapi` doesn't know.
+    /// `namespace` is `None` for cluster scoped resources, or to act on all namespaces.
+    pub fn dynamic(
+        &self,
+        resource: GroupVersionResource,
+        namespace: Option<&str>,
+    ) -> Api<'_, DynamicObject> {
+        Api::new(self, resource, namespace)
+    }
+
+    /// Typed access to the resource of a `k8s_openapi` type.
+    pub fn api<K: Resource>(&self, namespace: Option<&str>) -> Api<'_, K> {
+        Api::new(self, GroupVersionResource::of::<K>(), namespace)
+    }
+}
+
+impl<'a, K> Api<'a, K> {
+    pub fn new(
+        client: &'a Kubernetes,
+        resource: GroupVersionResource,
+        namespace: Option<&str>,
+    ) -> Self {
+        Api {
+            client,
+            resource,
+            namespace: namespace.map(String::from),
+            kind: PhantomData,
+        }
+    }
+
+    pub fn resource(&self) -> &GroupVersionResource {
+        &self.resource
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    pub(crate) fn collection_path(&self) -> String {
+        self.resource.url_path(self.namespace.as_deref())
+    }
+
+    /// Path of the object `name`, or of its `subresource` (`status`, `scale`...).
+    pub(crate) fn object_path(&self, name: &str, subresource: Option<&str>) -> String {
+        let mut path = format!("{}/{}", self.collection_path(), encode_segment(name));
+        if let Some(subresource) = subresource {
+            path.push('/');
+            path.push_str(subresource);
+        }
+        path
+    }
+}
+
+impl<'a, K> Api<'a, K>
+where
+    K: Serialize + DeserializeOwned,
+{
+    pub fn get(&self, name: &str) -> Result<K, KubernetesError> {
+        let request = build_request(Method::GET, self.object_path(name, None), None, vec![])?;
+        self.client.request_json(request)
+    }
+
+    pub fn list(&self, optional: ListOptional) -> Result<ObjectList<K>, KubernetesError> {
+        let path = with_query(self.collection_path(), list_query(&optional));
+        let request = build_request(Method::GET, path, None, vec![])?;
+        self.client.request_json(request)
+    }
+
+    pub fn create(&self, object: &K, optional: CreateOptional) -> Result<K, KubernetesError> {
+        let query = write_query(optional.dry_run, optional.field_manager, None);
+        let path = with_query(self.collection_path(), query);
+        let request = build_request(
+            Method::POST,
+            path,
+            Some("application/json"),
+            to_json(object)?,
+        )?;
+        self.client.request_json(request)
+    }
+
+    pub fn replace(
+        &self,
+        name: &str,
+        object: &K,
+        optional: ReplaceOptional,
+    ) -> Result<K, KubernetesError> {
+        let query = write_query(optional.dry_run, optional.field_manager, None);
+        let path = with_query(self.object_path(name, None), query);
+        let request = build_request(
+            Method::PUT,
+            path,
+            Some("application/json"),
+            to_json(object)?,
+        )?;
+        self.client.request_json(request)
+    }
+
+    pub fn patch(
+        &self,
+        name: &str,
+        patch: &Patch,
+        optional: PatchOptional,
+    ) -> Result<K, KubernetesError> {
+        let query = write_query(optional.dry_run, optional.field_manager, optional.force);
+        let path = with_query(self.object_path(name, None), query);
+        let request = build_request(
+            Method::PATCH,
+            path,
+            Some(patch.content_type()),
+            patch.to_body()?,
+        )?;
+        self.client.request_json(request)
+    }
+
+    /// Watches changes to the objects, as a blocking iterator over events. The
+    /// iterator ends when the API server closes the watch (see `timeout_seconds`).
+    pub fn watch(&self, optional: WatchOptional) -> Result<WatchStream<K>, KubernetesError> {
+        let path = with_query(self.collection_path(), watch_query(&optional));
+        let request = build_request(Method::GET, path.clone(), None, vec![])?;
+        let response = self.client.send_stream(request)?;
+        Ok(WatchStream {
+            url: format!("{}{}", self.client.base_uri, path),
+            reader: BufReader::new(response.into_body()),
+            kind: PhantomData,
+        })
+    }
+}
+
+/// Events of a watch, read one line at a time from the response.
+pub struct WatchStream<K> {
+    url: String,
+    reader: BufReader<Body>,
+    kind: PhantomData<K>,
+}
+
+impl<K: DeserializeOwned> Iterator for WatchStream<K> {
+    type Item = Result<WatchEvent<K>, KubernetesError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => {
+                    return Some(
+                        serde_json::from_str(&line)
+                            .map_err(|source| KubernetesError::JsonError { source }),
+                    )
+                }
+                Err(err) => {
+                    return Some(Err(KubernetesError::from_stream_error(
+                        Method::GET,
+                        &self.url,
+                        err,
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl<K> std::fmt::Debug for WatchStream<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("WatchStream")
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+pub(crate) fn build_request(
+    method: Method,
+    path: String,
+    content_type: Option<&str>,
+    body: Vec<u8>,
+) -> Result<Request<Vec<u8>>, KubernetesError> {
+    let mut builder = Request::builder().method(method).uri(path);
+    if let Some(content_type) = content_type {
+        builder = builder.header(CONTENT_TYPE, content_type);
+    }
+    builder
+        .body(body)
+        .map_err(|err| KubernetesError::HttpClientBuildError {
+            message: format!("Couldn't build request. Error: {:?}", err),
+        })
+}
+
+pub(crate) fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, KubernetesError> {
+    serde_json::to_vec(value).map_err(|source| KubernetesError::JsonError { source })
+}
+
+pub(crate) fn with_query(path: String, query: String) -> String {
+    if query.is_empty() {
+        path
+    } else {
+        format!("{}?{}", path, query)
+    }
+}
+
+pub(crate) fn encode_segment(segment: &str) -> String {
+    url::form_urlencoded::byte_serialize(segment.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20")
+}
+
+fn write_query(dry_run: Option<&str>, field_manager: Option<&str>, force: Option<bool>) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    if let Some(dry_run) = dry_run {
+        query.append_pair("dryRun", dry_run);
+    }
+    if let Some(field_manager) = field_manager {
+        query.append_pair("fieldManager", field_manager);
+    }
+    if let Some(force) = force {
+        query.append_pair("force", &force.to_string());
+    }
+    query.finish()
+}
+
+pub(crate) fn list_query(optional: &ListOptional) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    if let Some(continue_) = optional.continue_ {
+        query.append_pair("continue", continue_);
+    }
+    if let Some(field_selector) = optional.field_selector {
+        query.append_pair("fieldSelector", field_selector);
+    }
+    if let Some(label_selector) = optional.label_selector {
+        query.append_pair("labelSelector", label_selector);
+    }
+    if let Some(limit) = optional.limit {
+        query.append_pair("limit", &limit.to_string());
+    }
+    if let Some(resource_version) = optional.resource_version {
+        query.append_pair("resourceVersion", resource_version);
+    }
+    if let Some(resource_version_match) = optional.resource_version_match {
+        query.append_pair("resourceVersionMatch", resource_version_match);
+    }
+    if let Some(timeout_seconds) = optional.timeout_seconds {
+        query.append_pair("timeoutSeconds", &timeout_seconds.to_string());
+    }
+    query.finish()
+}
+
+pub(crate) fn watch_query(optional: &WatchOptional) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    query.append_pair("watch", "true");
+    if let Some(allow_watch_bookmarks) = optional.allow_watch_bookmarks {
+        query.append_pair("allowWatchBookmarks", &allow_watch_bookmarks.to_string());
+    }
+    if let Some(field_selector) = optional.field_selector {
+        query.append_pair("fieldSelector", field_selector);
+    }
+    if let Some(label_selector) = optional.label_selector {
+        query.append_pair("labelSelector", label_selector);
+    }
+    if let Some(resource_version) = optional.resource_version {
+        query.append_pair("resourceVersion", resource_version);
+    }
+    if let Some(timeout_seconds) = optional.timeout_seconds {
+        query.append_pair("timeoutSeconds", &timeout_seconds.to_string());
+    }
+    query.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::apps::v1::Deployment;
+    use k8s_openapi::api::core::v1::Node;
+
+    #[test]
+    fn test_url_path() {
+        let gvr = GroupVersionResource::new("cert-manager.io", "v1", "certificates");
+        assert_eq!(gvr.api_version(), "cert-manager.io/v1");
+        assert_eq!(
+            gvr.url_path(Some("default")),
+            "/apis/cert-manager.io/v1/namespaces/default/certificates"
+        );
+        assert_eq!(
+            GroupVersionResource::of::<Node>().url_path(None),
+            "/api/v1/nodes"
+        );
+        assert_eq!(
+            GroupVersionResource::of::<Deployment>().url_path(Some("web")),
+            "/apis/apps/v1/namespaces/web/deployments"
+        );
+    }
+
+    #[test]
+    fn test_dynamic_object_round_trip() {
+        let raw = serde_json::json!({
+            "apiVersion": "argoproj.io/v1alpha1",
+            "kind": "Workflow",
+            "metadata": {"name": "hello", "namespace": "argo", "labels": {"app": "demo"}},
+            "spec": {"entrypoint": "main"},
+            "status": {"phase": "Running"}
+        });
+        let object: DynamicObject = serde_json::from_value(raw.clone()).unwrap();
+        assert_eq!(object.name(), Some("hello"));
+        assert_eq!(object.namespace(), Some("argo"));
+        assert_eq!(object.labels().unwrap()["app"], "demo");
+        assert_eq!(
+            object.pointer("/status/phase").and_then(Value::as_str),
+            Some("Running")
+        );
+        assert_eq!(serde_json::to_value(&object).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_list_query() {
+        let query = list_query(&ListOptional {
+            label_selector: Some("app in (a,b)"),
+            limit: Some(10),
+            ..Default::default()
+        });
+        assert_eq!(query, "labelSelector=app+in+%28a%2Cb%29&limit=10");
+    }
+}
@@ -1,21 +1,235 @@
+use http::{Method, StatusCode};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
 use k8s_openapi::RequestError;
 
 #[derive(Debug)]
 pub enum KubernetesError {
     RequestError,
-    IoError { source: std::io::Error },
+    IoError {
+        source: std::io::Error,
+    },
     ClientBuildError,
-    HttpClientBuildError { message: String },
-    HttpClientRequestError,
-    HttpClientParseResponseError { message: String },
-    ApiRequestError { source: RequestError },
-    Base64DecodeError { source: base64::DecodeError },
+    HttpClientBuildError {
+        message: String,
+    },
+    HttpClientRequestError {
+        source: isahc::Error,
+    },
+    HttpClientParseResponseError {
+        message: String,
+    },
+    ApiRequestError {
+        source: RequestError,
+    },
+    /// The request didn't complete within the configured timeout.
+    TimeoutError {
+        verb: Method,
+        url: String,
+        source: isahc::Error,
+    },
+    /// The API server answered with a non-2xx status code.
+    ///
+    /// `status` holds the decoded `metav1.Status` object when the server sent
+    /// one, which is the case for almost every error coming from the API server.
+    ApiError {
+        verb: Method,
+        url: String,
+        status_code: StatusCode,
+        status: Option<Box<Status>>,
+    },
+    /// A server-side apply touched fields owned by other managers.
+    ApplyConflictError {
+        url: String,
+        conflicts: Vec<FieldConflict>,
+        status: Box<Status>,
+    },
+    /// What was waited for, e.g. a deletion or a rollout, didn't happen in time.
+    WaitTimeoutError {
+        what: String,
+        timeout: std::time::Duration,
+    },
+    /// Pods prevent draining `node` with the options given.
+    DrainError {
+        node: String,
+        blocked: Vec<String>,
+    },
+    /// The rollout of `name` can't complete, e.g. its progress deadline passed.
+    RolloutError {
+        name: String,
+        message: String,
+    },
+    /// A `WaitCondition` couldn't be parsed or evaluated.
+    InvalidWaitConditionError {
+        condition: String,
+        message: String,
+    },
+    /// A document of a manifest stream isn't a valid object. `document` starts at 1.
+    ManifestError {
+        document: usize,
+        message: String,
+    },
+    /// A line of a Prometheus text exposition couldn't be parsed. `line` starts at 1.
+    MetricsParseError {
+        line: usize,
+        message: String,
+    },
+    /// A resource quantity, e.g. `1.5Gi`, couldn't be parsed.
+    QuantityParseError {
+        quantity: String,
+        message: String,
+    },
+    /// Discovery doesn't know `kind` in `api_version`, e.g. a CRD isn't installed.
+    UnknownKindError {
+        api_version: String,
+        kind: String,
+    },
+    /// The resources of `group_version` couldn't be discovered, e.g. its aggregated
+    /// API server is down.
+    GroupDiscoveryError {
+        group_version: String,
+        message: String,
+    },
+    Base64DecodeError {
+        source: base64::DecodeError,
+    },
+    JsonError {
+        source: serde_json::Error,
+    },
     InvalidDataError,
     ConfigLoadError,
-    WrongDatetimeFormat { source: chrono::ParseError },
+    /// The name of the node we run on couldn't be found.
+    NodeNameError,
+    WrongDatetimeFormat {
+        source: chrono::ParseError,
+    },
+    /// Error on a WebSocket connection (exec, attach, port-forward).
+    WebSocketError {
+        source: Box<tungstenite::Error>,
+    },
 }
 
-impl std::error::Error for KubernetesError {}
+/// A field that another manager owns with a different value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldConflict {
+    /// Manager owning the field, e.g. `kubectl-client-side-apply`.
+    pub manager: String,
+    /// Path of the field, e.g. `.spec.replicas`.
+    pub field: String,
+    /// Message of the API server, e.g. `conflict with "kubectl" using apps/v1`.
+    pub message: String,
+}
+
+impl FieldConflict {
+    /// Conflicts listed in the causes of a `Conflict` status.
+    pub(crate) fn from_status(status: &Status) -> Vec<FieldConflict> {
+        let causes = status.details.as_ref().and_then(|d| d.causes.as_ref());
+        causes
+            .into_iter()
+            .flatten()
+            .filter(|c| c.reason.as_deref() == Some("FieldManagerConflict"))
+            .map(|c| {
+                let message = c.message.clone().unwrap_or_default();
+                let manager = message.split('"').nth(1).unwrap_or_default().to_string();
+                FieldConflict {
+                    manager,
+                    field: c.field.clone().unwrap_or_default(),
+                    message,
+                }
+            })
+            .collect()
+    }
+}
+
+impl KubernetesError {
+    /// Error while reading the body of a streamed response (watch, logs). The
+    /// `idle` timeout shows up there, as the response has already started.
+    pub(crate) fn from_stream_error(verb: Method, url: &str, err: std::io::Error) -> Self {
+        let source = isahc::Error::from(err);
+        if source.is_timeout() {
+            KubernetesError::TimeoutError {
+                verb,
+                url: url.to_string(),
+                source,
+            }
+        } else {
+            KubernetesError::HttpClientRequestError { source }
+        }
+    }
+
+    /// HTTP status code returned by the API server, if this error comes from a response.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            KubernetesError::ApiError { status_code, .. } => Some(*status_code),
+            KubernetesError::ApplyConflictError { .. } => Some(StatusCode::CONFLICT),
+            _ => None,
+        }
+    }
+
+    /// Decoded `metav1.Status` returned by the API server, if any.
+    pub fn status(&self) -> Option<&Status> {
+        match self {
+            KubernetesError::ApiError { status, .. } => status.as_deref(),
+            KubernetesError::ApplyConflictError { status, .. } => Some(status),
+            _ => None,
+        }
+    }
+
+    /// `reason` field of the returned `metav1.Status` (e.g. `NotFound`, `AlreadyExists`).
+    pub fn reason(&self) -> Option<&str> {
+        self.status().and_then(|s| s.reason.as_deref())
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            KubernetesError::TimeoutError { .. } | KubernetesError::WaitTimeoutError { .. }
+        )
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status_code() == Some(StatusCode::NOT_FOUND)
+    }
+
+    pub fn is_conflict(&self) -> bool {
+        self.status_code() == Some(StatusCode::CONFLICT)
+    }
+
+    pub fn is_forbidden(&self) -> bool {
+        self.status_code() == Some(StatusCode::FORBIDDEN)
+    }
+
+    pub fn is_unauthorized(&self) -> bool {
+        self.status_code() == Some(StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn is_already_exists(&self) -> bool {
+        self.is_conflict() && self.reason() == Some("AlreadyExists")
+    }
+
+    /// Fields a server-side apply couldn't take from other managers.
+    pub fn field_conflicts(&self) -> &[FieldConflict] {
+        match self {
+            KubernetesError::ApplyConflictError { conflicts, .. } => conflicts,
+            _ => &[],
+        }
+    }
+}
+
+impl std::error::Error for KubernetesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KubernetesError::IoError { source } => Some(source),
+            KubernetesError::HttpClientRequestError { source } => Some(source),
+            KubernetesError::TimeoutError { source, .. } => Some(source),
+            KubernetesError::ApiRequestError { source } => Some(source),
+            KubernetesError::Base64DecodeError { source } => Some(source),
+            KubernetesError::JsonError { source } => Some(source),
+            KubernetesError::WrongDatetimeFormat { source } => Some(source),
+            KubernetesError::WebSocketError { source } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl std::fmt::Display for KubernetesError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -28,23 +242,83 @@ impl std::fmt::Display for KubernetesError {
             KubernetesError::HttpClientBuildError { message } => {
                 write!(f, "Couldn't build http client (isahc): {}", message)
             }
-            KubernetesError::HttpClientRequestError => {
-                write!(f, "Couldn't build http client (isahc)")
+            KubernetesError::HttpClientRequestError { source } => {
+                write!(f, "Couldn't send request (isahc). Source: {}", source)
             }
             KubernetesError::HttpClientParseResponseError { message } => {
                 write!(f, "Couldn't parse response from HTTP server: {}", message)
             }
+            KubernetesError::ApiError {
+                verb,
+                url,
+                status_code,
+                status,
+            } => {
+                write!(f, "{} {} returned {}", verb, url, status_code)?;
+                if let Some(message) = status.as_ref().and_then(|s| s.message.as_ref()) {
+                    write!(f, ": {}", message)?;
+                }
+                Ok(())
+            }
+            KubernetesError::ApplyConflictError { url, conflicts, .. } => {
+                write!(f, "Apply to {} conflicts with other managers:", url)?;
+                for conflict in conflicts {
+                    write!(f, " {} ({})", conflict.field, conflict.manager)?;
+                }
+                Ok(())
+            }
+            KubernetesError::DrainError { node, blocked } => {
+                write!(f, "Cannot drain node {}: {}", node, blocked.join("; "))
+            }
+            KubernetesError::RolloutError { name, message } => {
+                write!(f, "Rollout of {} failed: {}", name, message)
+            }
+            KubernetesError::InvalidWaitConditionError { condition, message } => {
+                write!(f, "Invalid wait condition {:?}: {}", condition, message)
+            }
+            KubernetesError::ManifestError { document, message } => {
+                write!(f, "Invalid manifest document {}: {}", document, message)
+            }
+            KubernetesError::MetricsParseError { line, message } => {
+                write!(f, "Invalid metrics at line {}: {}", line, message)
+            }
+            KubernetesError::QuantityParseError { quantity, message } => {
+                write!(f, "Invalid quantity {:?}: {}", quantity, message)
+            }
+            KubernetesError::UnknownKindError { api_version, kind } => {
+                write!(f, "No resource serves kind {} in {}", kind, api_version)
+            }
+            KubernetesError::GroupDiscoveryError {
+                group_version,
+                message,
+            } => write!(f, "Couldn't discover {}: {}", group_version, message),
+            KubernetesError::WaitTimeoutError { what, timeout } => {
+                write!(f, "Timed out after {:?} waiting for {}", timeout, what)
+            }
+            KubernetesError::TimeoutError { verb, url, source } => {
+                write!(f, "{} {} timed out: {}", verb, url, source)
+            }
             KubernetesError::Base64DecodeError { source } => {
                 write!(f, "Couldn't decode base 64. Source: {}", source)
             }
+            KubernetesError::JsonError { source } => {
+                write!(f, "Couldn't (de)serialize JSON. Source: {}", source)
+            }
             KubernetesError::InvalidDataError => write!(f, "Invalid data provided."),
             KubernetesError::ConfigLoadError => write!(f, "Could not load Kube Config."),
+            KubernetesError::NodeNameError => write!(
+                f,
+                "Could not detect the node name, set the NODE_NAME environment variable."
+            ),
             KubernetesError::ApiRequestError { source } => {
                 write!(f, "API returned error: {}.", source)
             }
             KubernetesError::WrongDatetimeFormat { source } => {
                 write!(f, "Couldn't parse date time input : {}", source)
             }
+            KubernetesError::WebSocketError { source } => {
+                write!(f, "WebSocket error: {}", source)
+            }
         }
     }
 }
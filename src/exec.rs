@@ -0,0 +1,367 @@
+use crate::dynamic::{encode_segment, with_query, GroupVersionResource};
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use crate::ws::{self, ChannelReader, ChannelWriter};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use url::form_urlencoded;
+
+/// Subprotocols of the exec and attach subresources, preferred first. v5 adds a
+/// way to close stdin.
+const PROTOCOLS: [&str; 2] = ["v5.channel.k8s.io", "v4.channel.k8s.io"];
+
+const STDIN: u8 = 0;
+const STDOUT: u8 = 1;
+const STDERR: u8 = 2;
+const ERROR: u8 = 3;
+const RESIZE: u8 = 4;
+const CLOSE: u8 = 255;
+
+/// Streams to connect to a process in a container, matching the `kubectl exec`
+/// and `kubectl attach` flags.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecOptions {
+    /// Container to run in. Required when the pod has several containers.
+    pub container: Option<String>,
+    pub stdin: bool,
+    pub stdout: bool,
+    /// Ignored with `tty`, as the terminal merges stderr into stdout.
+    pub stderr: bool,
+    /// Allocate a terminal, which can then be resized.
+    pub tty: bool,
+}
+
+impl Default for ExecOptions {
+    fn default() -> Self {
+        ExecOptions {
+            container: None,
+            stdin: false,
+            stdout: true,
+            stderr: true,
+            tty: false,
+        }
+    }
+}
+
+impl ExecOptions {
+    fn query(&self, command: &[&str]) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        for arg in command {
+            query.append_pair("command", arg);
+        }
+        if let Some(container) = &self.container {
+            query.append_pair("container", container);
+        }
+        if self.stdin {
+            query.append_pair("stdin", "true");
+        }
+        if self.stdout {
+            query.append_pair("stdout", "true");
+        }
+        if self.stderr && !self.tty {
+            query.append_pair("stderr", "true");
+        }
+        if self.tty {
+            query.append_pair("tty", "true");
+        }
+        query.finish()
+    }
+}
+
+/// How a remote process ended.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExitStatus {
+    /// Exit code of the process, when known. Only a `Success` status on the error
+    /// channel means `0`: when the connection closes without one, the process may
+    /// still be running, or may have been killed.
+    pub code: Option<i32>,
+    /// Status sent by the API server on the error channel, if any. Explains the
+    /// failure when there is no exit code, e.g. the command wasn't found.
+    pub status: Option<Status>,
+}
+
+impl ExitStatus {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    fn decode(error: &[u8]) -> Result<Self, KubernetesError> {
+        if error.is_empty() {
+            return Ok(ExitStatus {
+                code: None,
+                status: None,
+            });
+        }
+        let status: Status = serde_json::from_slice(error)
+            .map_err(|source| KubernetesError::JsonError { source })?;
+        let code = if status.status.as_deref() == Some("Success") {
+            Some(0)
+        } else if status.reason.as_deref() == Some("NonZeroExitCode") {
+            status
+                .details
+                .as_ref()
+                .and_then(|d| d.causes.as_ref())
+                .and_then(|causes| {
+                    causes
+                        .iter()
+                        .find(|c| c.reason.as_deref() == Some("ExitCode"))
+                })
+                .and_then(|c| c.message.as_ref()?.parse().ok())
+        } else {
+            None
+        };
+        Ok(ExitStatus {
+            code,
+            status: Some(status),
+        })
+    }
+}
+
+/// Standard input of a remote process. With `v5.channel.k8s.io`, dropping it
+/// closes the stream, so the process reads end of file.
+pub struct RemoteStdin {
+    writer: ChannelWriter,
+    outgoing: Sender<Vec<u8>>,
+    closable: bool,
+}
+
+impl Write for RemoteStdin {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Drop for RemoteStdin {
+    fn drop(&mut self) {
+        if self.closable {
+            let _ = self.outgoing.send(vec![CLOSE, STDIN]);
+        }
+    }
+}
+
+impl std::fmt::Debug for RemoteStdin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RemoteStdin").finish()
+    }
+}
+
+/// Output stream of a remote process. Reads block until data comes, and return
+/// end of file once the connection is closed.
+pub struct RemoteOutput {
+    reader: ChannelReader,
+}
+
+impl Read for RemoteOutput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl std::fmt::Debug for RemoteOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RemoteOutput").finish()
+    }
+}
+
+/// A process running in a container, connected through the API server.
+///
+/// Streams not asked for in the `ExecOptions` are `None`. Take them out with
+/// `Option::take` to use them from other threads.
+pub struct RemoteProcess {
+    pub stdin: Option<RemoteStdin>,
+    pub stdout: Option<RemoteOutput>,
+    pub stderr: Option<RemoteOutput>,
+    /// Subprotocol negotiated with the API server.
+    pub protocol: Option<String>,
+    tty: bool,
+    outgoing: Sender<Vec<u8>>,
+    error: Receiver<Vec<u8>>,
+    connection: JoinHandle<Result<(), KubernetesError>>,
+}
+
+impl RemoteProcess {
+    /// Sets the size of the terminal. Only possible with `tty`.
+    pub fn resize(&self, width: u16, height: u16) -> Result<(), KubernetesError> {
+        if !self.tty {
+            return Err(KubernetesError::InvalidDataError);
+        }
+        let size = serde_json::json!({ "Width": width, "Height": height });
+        let mut frame = vec![RESIZE];
+        frame.extend(
+            serde_json::to_vec(&size).map_err(|source| KubernetesError::JsonError { source })?,
+        );
+        self.outgoing
+            .send(frame)
+            .map_err(|_| KubernetesError::IoError {
+                source: io::Error::new(io::ErrorKind::BrokenPipe, "connection closed"),
+            })
+    }
+
+    /// Waits for the process to end and the API server to close the connection.
+    ///
+    /// Output not read yet stays available in `stdout` and `stderr` when they were
+    /// taken out before calling this.
+    pub fn wait(self) -> Result<ExitStatus, KubernetesError> {
+        let RemoteProcess {
+            stdin,
+            outgoing,
+            error,
+            connection,
+            ..
+        } = self;
+        drop(stdin);
+        connection
+            .join()
+            .map_err(|_| KubernetesError::InvalidDataError)??;
+        drop(outgoing);
+        let error: Vec<u8> = error.try_iter().flatten().collect();
+        ExitStatus::decode(&error)
+    }
+}
+
+impl std::fmt::Debug for RemoteProcess {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RemoteProcess")
+            .field("protocol", &self.protocol)
+            .field("tty", &self.tty)
+            .finish()
+    }
+}
+
+impl Kubernetes {
+    /// Runs `command` in a container, like `kubectl exec`.
+    pub fn exec(
+        &self,
+        namespace: &str,
+        pod: &str,
+        command: &[&str],
+        options: &ExecOptions,
+    ) -> Result<RemoteProcess, KubernetesError> {
+        self.connect_process(namespace, pod, "exec", options.query(command), options)
+    }
+
+    /// Connects to the main process of a container, like `kubectl attach`.
+    pub fn attach(
+        &self,
+        namespace: &str,
+        pod: &str,
+        options: &ExecOptions,
+    ) -> Result<RemoteProcess, KubernetesError> {
+        self.connect_process(namespace, pod, "attach", options.query(&[]), options)
+    }
+
+    fn connect_process(
+        &self,
+        namespace: &str,
+        pod: &str,
+        subresource: &str,
+        query: String,
+        options: &ExecOptions,
+    ) -> Result<RemoteProcess, KubernetesError> {
+        let path = format!(
+            "{}/{}/{}",
+            GroupVersionResource::new("", "v1", "pods").url_path(Some(namespace)),
+            encode_segment(pod),
+            subresource
+        );
+        let (socket, protocol) = ws::connect(
+            &ws::Endpoint::of(self),
+            &with_query(path, query),
+            &PROTOCOLS,
+        )?;
+
+        let (outgoing, outgoing_receiver) = mpsc::channel();
+        let (stdout, stdout_receiver) = mpsc::channel();
+        let (stderr, stderr_receiver) = mpsc::channel();
+        let (error, error_receiver) = mpsc::channel();
+        let connection = ws::spawn(socket, outgoing_receiver, move |channel, data| {
+            // Receivers may have been dropped by the caller, which is fine.
+            let _ = match channel {
+                STDOUT => stdout.send(data.to_vec()),
+                STDERR => stderr.send(data.to_vec()),
+                ERROR => error.send(data.to_vec()),
+                _ => Ok(()),
+            };
+        })?;
+
+        let stdin = if options.stdin {
+            Some(RemoteStdin {
+                writer: ChannelWriter::new(STDIN, outgoing.clone()),
+                outgoing: outgoing.clone(),
+                closable: protocol.as_deref() == Some(PROTOCOLS[0]),
+            })
+        } else {
+            None
+        };
+        Ok(RemoteProcess {
+            stdin,
+            stdout: if options.stdout {
+                Some(RemoteOutput {
+                    reader: ChannelReader::new(stdout_receiver),
+                })
+            } else {
+                None
+            },
+            stderr: if options.stderr && !options.tty {
+                Some(RemoteOutput {
+                    reader: ChannelReader::new(stderr_receiver),
+                })
+            } else {
+                None
+            },
+            protocol,
+            tty: options.tty,
+            outgoing,
+            error: error_receiver,
+            connection,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query() {
+        let options = ExecOptions {
+            container: Some(String::from("app")),
+            stdin: true,
+            tty: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            options.query(&["sh", "-c", "echo $HOME"]),
+            "command=sh&command=-c&command=echo+%24HOME&container=app&stdin=true&stdout=true&tty=true"
+        );
+    }
+
+    #[test]
+    fn test_decode_exit_status() {
+        let success = ExitStatus::decode(br#"{"metadata":{},"status":"Success"}"#).unwrap();
+        assert!(success.success());
+
+        let failure = ExitStatus::decode(
+            br#"{"metadata":{},"status":"Failure","message":"command terminated with non-zero exit code","reason":"NonZeroExitCode","details":{"causes":[{"reason":"ExitCode","message":"42"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(failure.code, Some(42));
+
+        let not_found = ExitStatus::decode(
+            br#"{"metadata":{},"status":"Failure","message":"executable file not found in $PATH"}"#,
+        )
+        .unwrap();
+        assert_eq!(not_found.code, None);
+        assert!(!not_found.success());
+
+        let closed = ExitStatus::decode(b"").unwrap();
+        assert_eq!(closed.code, None);
+        assert_eq!(closed.status, None);
+    }
+}
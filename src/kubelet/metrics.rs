@@ -0,0 +1,264 @@
+use crate::errors::KubernetesError;
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+
+/// A sample of the Prometheus text format, e.g.
+/// `container_cpu_usage_seconds_total{container="web",namespace="shop",pod="web-1"} 11.41 1633253806862`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl Sample {
+    pub fn label(&self, name: &str) -> Option<&str> {
+        self.labels.get(name).map(String::as_str)
+    }
+}
+
+/// Parses the samples of a Prometheus text exposition, skipping comments
+/// (`# HELP`, `# TYPE`) and blank lines.
+pub fn parse_samples(text: &str) -> Result<Vec<Sample>, KubernetesError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(index, line)| {
+            parse_sample(line.trim()).map_err(|message| KubernetesError::MetricsParseError {
+                line: index + 1,
+                message,
+            })
+        })
+        .collect()
+}
+
+fn parse_sample(line: &str) -> Result<Sample, String> {
+    let name_end = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+        .unwrap_or(line.len());
+    let name = &line[..name_end];
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(String::from("missing metric name"));
+    }
+    let mut rest = &line[name_end..];
+    let mut labels = BTreeMap::new();
+    if let Some(after_brace) = rest.strip_prefix('{') {
+        rest = parse_labels(after_brace, &mut labels)?;
+    }
+    let mut fields = rest.split_whitespace();
+    let value = fields.next().ok_or("missing value")?;
+    // Rust also accepts the `NaN`, `+Inf` and `-Inf` of the format.
+    let value = value
+        .parse()
+        .map_err(|_| format!("invalid value {:?}", value))?;
+    let timestamp = match fields.next() {
+        Some(ms) => {
+            let ms = ms
+                .parse()
+                .map_err(|_| format!("invalid timestamp {:?}", ms))?;
+            Some(
+                Utc.timestamp_millis_opt(ms)
+                    .single()
+                    .ok_or_else(|| format!("timestamp {} out of range", ms))?,
+            )
+        }
+        None => None,
+    };
+    if fields.next().is_some() {
+        return Err(String::from("unexpected content after timestamp"));
+    }
+    Ok(Sample {
+        name: name.to_string(),
+        labels,
+        value,
+        timestamp,
+    })
+}
+
+/// Parses `name="value",...}` and returns what follows the closing brace.
+fn parse_labels<'l>(
+    mut rest: &'l str,
+    labels: &mut BTreeMap<String, String>,
+) -> Result<&'l str, String> {
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix('}') {
+            return Ok(after);
+        }
+        let (name, after_name) = rest.split_once('=').ok_or("unterminated labels")?;
+        let quoted = after_name
+            .trim_start()
+            .strip_prefix('"')
+            .ok_or("label value must be quoted")?;
+        let mut chars = quoted.char_indices();
+        let mut value = String::new();
+        let end = loop {
+            match chars.next().ok_or("unterminated label value")? {
+                (i, '"') => break i,
+                (_, '\\') => match chars.next().ok_or("unterminated label value")?.1 {
+                    'n' => value.push('\n'),
+                    other => value.push(other),
+                },
+                (_, c) => value.push(c),
+            }
+        };
+        labels.insert(name.trim().to_string(), value);
+        let after_value = quoted[end + 1..].trim_start();
+        rest = after_value.strip_prefix(',').unwrap_or(after_value);
+    }
+}
+
+/// A measure with the time it was taken at, when the kubelet reports it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measurement {
+    pub value: f64,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl From<&Sample> for Measurement {
+    fn from(sample: &Sample) -> Self {
+        Measurement {
+            value: sample.value,
+            timestamp: sample.timestamp,
+        }
+    }
+}
+
+/// Usage of a container, from the kubelet `/metrics/resource` endpoint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerUsage {
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+    /// Cumulative CPU time, in core-seconds. It restarts from 0 with the container.
+    pub cpu_usage_seconds: Option<Measurement>,
+    pub memory_working_set_bytes: Option<Measurement>,
+    pub start_time: Option<DateTime<Utc>>,
+}
+
+/// Usage of a node and its containers, from the kubelet `/metrics/resource`
+/// endpoint (Kubernetes 1.20+).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResourceMetrics {
+    pub node_cpu_usage_seconds: Option<Measurement>,
+    pub node_memory_working_set_bytes: Option<Measurement>,
+    /// Sorted by namespace, pod and container.
+    pub containers: Vec<ContainerUsage>,
+}
+
+impl ResourceMetrics {
+    pub fn parse(text: &str) -> Result<Self, KubernetesError> {
+        Ok(Self::from_samples(&parse_samples(text)?))
+    }
+
+    /// Keeps the samples of the node and container metrics, ignoring the others.
+    pub fn from_samples(samples: &[Sample]) -> Self {
+        let mut metrics = ResourceMetrics::default();
+        let mut containers = BTreeMap::new();
+        for sample in samples {
+            match sample.name.as_str() {
+                "node_cpu_usage_seconds_total" => {
+                    metrics.node_cpu_usage_seconds = Some(sample.into())
+                }
+                "node_memory_working_set_bytes" => {
+                    metrics.node_memory_working_set_bytes = Some(sample.into())
+                }
+                "container_cpu_usage_seconds_total"
+                | "container_memory_working_set_bytes"
+                | "container_start_time_seconds" => {
+                    let key = match (
+                        sample.label("namespace"),
+                        sample.label("pod"),
+                        sample.label("container"),
+                    ) {
+                        (Some(namespace), Some(pod), Some(container)) => {
+                            (namespace, pod, container)
+                        }
+                        _ => continue,
+                    };
+                    let usage = containers.entry(key).or_insert_with(|| ContainerUsage {
+                        namespace: key.0.to_string(),
+                        pod: key.1.to_string(),
+                        container: key.2.to_string(),
+                        cpu_usage_seconds: None,
+                        memory_working_set_bytes: None,
+                        start_time: None,
+                    });
+                    match sample.name.as_str() {
+                        "container_cpu_usage_seconds_total" => {
+                            usage.cpu_usage_seconds = Some(sample.into())
+                        }
+                        "container_memory_working_set_bytes" => {
+                            usage.memory_working_set_bytes = Some(sample.into())
+                        }
+                        _ => {
+                            usage.start_time = Utc
+                                .timestamp_millis_opt((sample.value * 1000.0) as i64)
+                                .single()
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        metrics.containers = containers.into_values().collect();
+        metrics
+    }
+
+    pub fn container(
+        &self,
+        namespace: &str,
+        pod: &str,
+        container: &str,
+    ) -> Option<&ContainerUsage> {
+        self.containers
+            .iter()
+            .find(|c| c.namespace == namespace && c.pod == pod && c.container == container)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_samples() {
+        let text = "# HELP scrape_error [ALPHA] 1 if there was an error while getting container metrics, 0 otherwise\n# TYPE scrape_error gauge\nscrape_error 0\n\ncontainer_memory_working_set_bytes{container=\"web\",namespace=\"shop\",pod=\"web-1\"} 1.3037568e+07 1633253806862\nodd{path=\"C:\\\\tmp\",quote=\"say \\\"hi\\\"\",} +Inf\n";
+        let samples = parse_samples(text).unwrap();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].name, "scrape_error");
+        assert_eq!(samples[0].timestamp, None);
+        assert_eq!(samples[1].value, 13037568.0);
+        assert_eq!(samples[1].label("pod"), Some("web-1"));
+        assert_eq!(
+            samples[1].timestamp.unwrap().to_rfc3339(),
+            "2021-10-03T09:36:46.862+00:00"
+        );
+        assert_eq!(samples[2].label("path"), Some("C:\\tmp"));
+        assert_eq!(samples[2].label("quote"), Some("say \"hi\""));
+        assert_eq!(samples[2].value, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (text, line) in [
+            ("a 1\nb{x=\"1\" 2\n", 2),
+            ("{x=\"1\"} 2\n", 1),
+            ("# c\nb two\n", 2),
+            ("b 1 2 3\n", 1),
+        ]
+        .iter()
+        {
+            match parse_samples(text) {
+                Err(KubernetesError::MetricsParseError { line: found, .. }) => {
+                    assert_eq!(found, *line, "{:?}", text)
+                }
+                other => panic!("{:?} parsed as {:?}", text, other),
+            }
+        }
+    }
+}
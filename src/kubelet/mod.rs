@@ -0,0 +1,234 @@
+pub mod metrics;
+pub mod stats;
+
+use crate::dynamic::{build_request, ObjectList};
+use crate::errors::KubernetesError;
+use crate::kubernetes::{Credentials, Kubernetes, Timeouts};
+use crate::retry::RetryPolicy;
+use crate::server::{HealthEndpoint, HealthReport};
+use http::Method;
+use isahc::config::{CaCertificate, ClientCertificate, Configurable, PrivateKey, SslOption};
+use isahc::HttpClient;
+use k8s_openapi::api::core::v1::Pod;
+use metrics::ResourceMetrics;
+use serde::Deserialize;
+use stats::Summary;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Port of the kubelet API, served over HTTPS.
+pub const KUBELET_PORT: u32 = 10250;
+/// Unauthenticated read-only port of the kubelet, served over plain HTTP. It is
+/// disabled by default since Kubernetes 1.10 (`readOnlyPort: 0`).
+pub const KUBELET_READ_ONLY_PORT: u32 = 10255;
+
+/// Client for the API of a kubelet, e.g. the one of the node we run on, which
+/// answers without loading the API server.
+///
+/// With the service account token, the kubelet authorizes requests through the
+/// API server: the account needs `get` on the `nodes/proxy` (`/pods`, `/configz`),
+/// `nodes/stats` (`/stats/summary`) and `nodes/metrics` (`/metrics/resource`)
+/// subresources.
+#[derive(Debug)]
+pub struct Kubelet {
+    client: Kubernetes,
+}
+
+#[derive(Deserialize)]
+struct Configz {
+    kubeletconfig: serde_json::Value,
+}
+
+impl Kubernetes {
+    /// The kubelet at `host` (`localhost` by default, e.g. with `hostNetwork`, or
+    /// `status.hostIP` through the downward API) on port 10250, authenticated with
+    /// the credentials of this client.
+    pub fn kubelet(
+        &self,
+        scheme: Option<String>,
+        host: Option<String>,
+        port: Option<u32>,
+    ) -> Kubelet {
+        let base_uri = kubelet_uri(scheme, host, port.unwrap_or(KUBELET_PORT));
+        Kubelet {
+            client: self.kubelet_client(
+                self.http_client.clone(),
+                base_uri,
+                self.credentials.clone(),
+            ),
+        }
+    }
+
+    /// The read-only port of the kubelet at `host`, 10255 by default. No credentials
+    /// are sent, as the connection isn't encrypted.
+    pub fn kubelet_read_only(
+        &self,
+        host: Option<String>,
+        port: Option<u32>,
+    ) -> Result<Kubelet, KubernetesError> {
+        let http_client =
+            HttpClient::new().map_err(|err| KubernetesError::HttpClientBuildError {
+                message: format!("Failed to initialize http client: {}", err),
+            })?;
+        let base_uri = kubelet_uri(
+            Some(String::from("http")),
+            host,
+            port.unwrap_or(KUBELET_READ_ONLY_PORT),
+        );
+        Ok(Kubelet {
+            client: self.kubelet_client(http_client, base_uri, Credentials::None),
+        })
+    }
+
+    /// The kubelet at `host` on port 10250, authenticated with a client certificate
+    /// instead of the credentials of this client, e.g. the one of the kubelet itself,
+    /// `/var/lib/kubelet/pki/kubelet-client-current.pem`.
+    ///
+    /// `key` defaults to `certificate`, for files holding both. The kubelet serving
+    /// certificate is checked against `ca` when given. Otherwise, as it is often
+    /// self-signed, any certificate is accepted.
+    pub fn kubelet_with_client_certificate<P: AsRef<Path>>(
+        &self,
+        scheme: Option<String>,
+        host: Option<String>,
+        port: Option<u32>,
+        certificate: P,
+        key: Option<P>,
+        ca: Option<P>,
+    ) -> Result<Kubelet, KubernetesError> {
+        let read =
+            |path: &Path| fs::read(path).map_err(|source| KubernetesError::IoError { source });
+        let certificate = read(certificate.as_ref())?;
+        let key = match &key {
+            Some(key) => read(key.as_ref())?,
+            None => certificate.clone(),
+        };
+        let mut builder = HttpClient::builder().ssl_client_certificate(ClientCertificate::pem(
+            certificate.clone(),
+            PrivateKey::pem(key.clone(), None),
+        ));
+        builder = match ca {
+            Some(ca) => builder.ssl_ca_certificate(CaCertificate::file(ca.as_ref())),
+            None => builder.ssl_options(SslOption::DANGER_ACCEPT_INVALID_CERTS),
+        };
+        let http_client = builder
+            .build()
+            .map_err(|err| KubernetesError::HttpClientBuildError {
+                message: format!(
+                    "Failed to initialize http client with client certificate: {}",
+                    err
+                ),
+            })?;
+        let base_uri = kubelet_uri(scheme, host, port.unwrap_or(KUBELET_PORT));
+        Ok(Kubelet {
+            client: self.kubelet_client(
+                http_client,
+                base_uri,
+                Credentials::ClientCertificate { certificate, key },
+            ),
+        })
+    }
+
+    fn kubelet_client(
+        &self,
+        http_client: HttpClient,
+        base_uri: String,
+        credentials: Credentials,
+    ) -> Kubernetes {
+        Kubernetes {
+            kubeconfig: self
+                .kubeconfig
+                .as_ref()
+                .map(Clone::clone)
+                .map_err(|_| KubernetesError::ConfigLoadError),
+            http_client,
+            base_uri,
+            retry_policy: self.retry_policy.clone(),
+            // The limit protects the API server, not the kubelets.
+            rate_limiter: None,
+            timeouts: self.timeouts.clone(),
+            discovery_cache: None,
+            credentials,
+        }
+    }
+}
+
+fn kubelet_uri(scheme: Option<String>, host: Option<String>, port: u32) -> String {
+    let host = host.unwrap_or_else(|| String::from("localhost"));
+    // IPv6 addresses, e.g. a `status.hostIP` on a dual-stack cluster, are bracketed.
+    let host = if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]", host)
+    } else {
+        host
+    };
+    format!(
+        "{}://{}:{}",
+        scheme.unwrap_or_else(|| String::from("https")),
+        host,
+        port
+    )
+}
+
+impl Kubelet {
+    pub fn base_uri(&self) -> &str {
+        &self.client.base_uri
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.client.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.client.timeouts = timeouts;
+        self
+    }
+
+    /// Pods of the node, as known by the kubelet (`/pods`). Static pods are listed
+    /// with their own UID, not the one of their mirror pod.
+    pub fn pods(&self) -> Result<Vec<Pod>, KubernetesError> {
+        let pods: ObjectList<Pod> = self.get_json("/pods")?;
+        Ok(pods.items)
+    }
+
+    /// Resource usage of the node, its pods and their containers (`/stats/summary`).
+    pub fn stats_summary(&self) -> Result<Summary, KubernetesError> {
+        self.get_json("/stats/summary")
+    }
+
+    /// Resource usage metrics of the node and its containers, in the Prometheus text
+    /// format (`/metrics/resource`).
+    pub fn metrics_resource(&self) -> Result<String, KubernetesError> {
+        let request = build_request(Method::GET, String::from("/metrics/resource"), None, vec![])?;
+        let mut response = self.client.send(request)?;
+        let mut body = String::new();
+        response
+            .body_mut()
+            .read_to_string(&mut body)
+            .map_err(|source| KubernetesError::IoError { source })?;
+        Ok(body)
+    }
+
+    /// Usage of the node and its containers, parsed from `/metrics/resource`.
+    pub fn resource_metrics(&self) -> Result<ResourceMetrics, KubernetesError> {
+        ResourceMetrics::parse(&self.metrics_resource()?)
+    }
+
+    /// Configuration the kubelet runs with (`/configz`), a `KubeletConfiguration`
+    /// of `kubelet.config.k8s.io/v1beta1` without its `apiVersion` and `kind`.
+    pub fn configz(&self) -> Result<serde_json::Value, KubernetesError> {
+        let configz: Configz = self.get_json("/configz")?;
+        Ok(configz.kubeletconfig)
+    }
+
+    /// Health of the kubelet (`/healthz`). An unhealthy kubelet is not an error.
+    pub fn healthz(&self, verbose: bool) -> Result<HealthReport, KubernetesError> {
+        self.client.health(HealthEndpoint::Healthz, verbose)
+    }
+
+    fn get_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, KubernetesError> {
+        let request = build_request(Method::GET, path.to_string(), None, vec![])?;
+        self.client.request_json(request)
+    }
+}
@@ -0,0 +1,218 @@
+//! Documents of the kubelet `/stats/summary` endpoint, `stats/v1alpha1` in
+//! `k8s.io/kubelet`. Every measure is optional: the kubelet leaves out what the
+//! container runtime doesn't report.
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use serde::{Deserialize, Serialize};
+
+/// Resource usage of a node and of its pods.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub node: NodeStats,
+    #[serde(default)]
+    pub pods: Vec<PodStats>,
+}
+
+impl Summary {
+    pub fn pod(&self, namespace: &str, name: &str) -> Option<&PodStats> {
+        self.pods
+            .iter()
+            .find(|p| p.pod_ref.namespace == namespace && p.pod_ref.name == name)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStats {
+    pub node_name: String,
+    /// Containers of the node itself, e.g. `kubelet`, `runtime` and `pods`.
+    #[serde(default)]
+    pub system_containers: Vec<ContainerStats>,
+    pub start_time: Option<Time>,
+    pub cpu: Option<CpuStats>,
+    pub memory: Option<MemoryStats>,
+    pub network: Option<NetworkStats>,
+    /// Filesystem of the kubelet root directory.
+    pub fs: Option<FsStats>,
+    pub runtime: Option<RuntimeStats>,
+    pub rlimit: Option<RlimitStats>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeStats {
+    /// Filesystem holding the images of the container runtime.
+    pub image_fs: Option<FsStats>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RlimitStats {
+    pub time: Option<Time>,
+    /// Maximum number of processes of the node.
+    pub maxpid: Option<i64>,
+    /// Number of processes running on the node.
+    pub curproc: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PodStats {
+    pub pod_ref: PodReference,
+    pub start_time: Option<Time>,
+    #[serde(default)]
+    pub containers: Vec<ContainerStats>,
+    pub cpu: Option<CpuStats>,
+    pub memory: Option<MemoryStats>,
+    pub network: Option<NetworkStats>,
+    #[serde(default)]
+    pub volume: Vec<VolumeStats>,
+    /// Local ephemeral storage: root filesystems and logs of the containers, and
+    /// `emptyDir` volumes.
+    #[serde(rename = "ephemeral-storage")]
+    pub ephemeral_storage: Option<FsStats>,
+    #[serde(rename = "process_stats")]
+    pub process_stats: Option<ProcessStats>,
+}
+
+impl PodStats {
+    pub fn container(&self, name: &str) -> Option<&ContainerStats> {
+        self.containers.iter().find(|c| c.name == name)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PodReference {
+    pub name: String,
+    pub namespace: String,
+    pub uid: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessStats {
+    #[serde(rename = "process_count")]
+    pub process_count: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerStats {
+    pub name: String,
+    pub start_time: Option<Time>,
+    pub cpu: Option<CpuStats>,
+    pub memory: Option<MemoryStats>,
+    #[serde(default)]
+    pub accelerators: Vec<AcceleratorStats>,
+    /// Writable layer of the container.
+    pub rootfs: Option<FsStats>,
+    pub logs: Option<FsStats>,
+    #[serde(default)]
+    pub user_defined_metrics: Vec<UserDefinedMetric>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuStats {
+    pub time: Option<Time>,
+    /// Average usage over the last sampling interval, in billionths of a core.
+    pub usage_nano_cores: Option<u64>,
+    /// Cumulative usage since the container started.
+    pub usage_core_nano_seconds: Option<u64>,
+}
+
+impl CpuStats {
+    /// Average usage over the last sampling interval, in cores.
+    pub fn usage_cores(&self) -> Option<f64> {
+        self.usage_nano_cores.map(|n| n as f64 / 1e9)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    pub time: Option<Time>,
+    /// Memory limit (or node capacity) minus the working set.
+    pub available_bytes: Option<u64>,
+    pub usage_bytes: Option<u64>,
+    /// Usage minus inactive file-backed memory: what `kubectl top` shows and what
+    /// evictions are based on.
+    pub working_set_bytes: Option<u64>,
+    pub rss_bytes: Option<u64>,
+    pub page_faults: Option<u64>,
+    pub major_page_faults: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStats {
+    pub time: Option<Time>,
+    /// The default interface, usually `eth0`.
+    #[serde(flatten)]
+    pub default_interface: InterfaceStats,
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceStats>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceStats {
+    #[serde(default)]
+    pub name: String,
+    pub rx_bytes: Option<u64>,
+    pub rx_errors: Option<u64>,
+    pub tx_bytes: Option<u64>,
+    pub tx_errors: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FsStats {
+    pub time: Option<Time>,
+    pub available_bytes: Option<u64>,
+    pub capacity_bytes: Option<u64>,
+    pub used_bytes: Option<u64>,
+    pub inodes_free: Option<u64>,
+    pub inodes: Option<u64>,
+    pub inodes_used: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeStats {
+    /// Name of the volume in the pod spec.
+    pub name: String,
+    #[serde(flatten)]
+    pub fs: FsStats,
+    /// Claim of the volume, for persistent volumes.
+    pub pvc_ref: Option<PvcReference>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PvcReference {
+    pub name: String,
+    pub namespace: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceleratorStats {
+    pub make: String,
+    pub model: String,
+    pub id: String,
+    pub memory_total: u64,
+    pub memory_used: u64,
+    pub duty_cycle: u64,
+}
+
+/// A metric exposed by the application of a container, as collected by cAdvisor.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserDefinedMetric {
+    pub name: String,
+    /// `gauge`, `cumulative` or `delta`.
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub units: String,
+    pub time: Option<Time>,
+    pub value: f64,
+}
@@ -1,23 +1,98 @@
 use crate::config::KubeConfig;
+use crate::discovery::DiscoveryCache;
 use crate::errors::KubernetesError;
+use crate::node::detect_node_name;
+use crate::ratelimit::RateLimiter;
+use crate::retry::{parse_retry_after, RetryPolicy};
 use base64;
 use chrono::DateTime;
-use http::StatusCode;
+use http::{header::RETRY_AFTER, request, StatusCode};
 use isahc::{
     config::CaCertificate, config::ClientCertificate, config::Configurable, config::PrivateKey,
-    config::SslOption, Body, HttpClient, Request,
+    config::SslOption, Body, HttpClient, Request, Response,
 };
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
 use k8s_openapi::{api::core::v1 as api, ListOptional, ResponseBody};
+use serde::de::DeserializeOwned;
+use std::cell::Cell;
 use std::env;
 use std::fs;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use std::{io::Read, io::Write};
 use tempfile::NamedTempFile;
 
+/// Timeouts applied to the requests sent to the API server. `None` means no limit.
+#[derive(Clone, Debug)]
+pub struct Timeouts {
+    /// Time allowed to establish the connection (TCP and TLS handshakes).
+    pub connect: Option<Duration>,
+    /// Time allowed for a whole request, from connection to the end of the response.
+    pub request: Option<Duration>,
+    /// For long-running streams (watch, logs), which don't get the `request` timeout:
+    /// time allowed without receiving any data before giving up.
+    pub idle: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Some(Duration::from_secs(30)),
+            request: None,
+            idle: None,
+        }
+    }
+}
+
+thread_local! {
+    /// Request timeout set by `Kubernetes::with_timeout` for calls made on this thread.
+    static CALL_TIMEOUT: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Restores the previous call timeout when dropped, even if the call panicked.
+struct CallTimeoutGuard(Option<Duration>);
+
+impl Drop for CallTimeoutGuard {
+    fn drop(&mut self) {
+        CALL_TIMEOUT.with(|timeout| timeout.set(self.0));
+    }
+}
+
+/// How the client authenticates to the API server.
+#[derive(Clone)]
+pub(crate) enum Credentials {
+    /// No authentication, e.g. for the read-only port of the kubelet.
+    None,
+    Token(String),
+    ClientCertificate {
+        certificate: Vec<u8>,
+        key: Vec<u8>,
+    },
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Never print secrets.
+        match self {
+            Credentials::None => write!(f, "None"),
+            Credentials::Token(_) => write!(f, "Token"),
+            Credentials::ClientCertificate { .. } => write!(f, "ClientCertificate"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Kubernetes {
     pub kubeconfig: Result<KubeConfig, KubernetesError>,
     pub http_client: HttpClient,
     pub base_uri: String,
+    pub retry_policy: RetryPolicy,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub timeouts: Timeouts,
+    pub discovery_cache: Option<DiscoveryCache>,
+    /// Kept for the connections not made through `http_client` (WebSockets).
+    pub(crate) credentials: Credentials,
 }
 
 impl Kubernetes {
@@ -31,6 +106,7 @@ impl Kubernetes {
         let kubeconfig = KubeConfig::load(kubeconfig_path);
         let token_file = "/var/run/secrets/kubernetes.io/serviceaccount/token";
         let http_client;
+        let credentials;
 
         if std::path::Path::new(token_file).exists() {
             let service_account_token = fs::read_to_string(token_file)
@@ -54,6 +130,7 @@ impl Kubernetes {
                     })
                 }
             };
+            credentials = Credentials::Token(service_account_token.trim().to_string());
         } else if let Ok(conf) = &kubeconfig {
             //TODO add options, guessed from config
             if let Some(cluster) = conf.clusters.first() {
@@ -66,17 +143,16 @@ impl Kubernetes {
                                     .map_err(|err| KubernetesError::IoError { source: err })?;
                                 writeln!(tmpfile, "{}", ca)
                                     .map_err(|err| KubernetesError::IoError { source: err })?;
+                                let certificate = base64::decode(crt).map_err(|err| {
+                                    KubernetesError::Base64DecodeError { source: err }
+                                })?;
+                                let key = base64::decode(key).map_err(|err| {
+                                    KubernetesError::Base64DecodeError { source: err }
+                                })?;
                                 let http_client_builder = HttpClient::builder()
                                     .ssl_client_certificate(ClientCertificate::pem(
-                                        base64::decode(crt).map_err(|err| {
-                                            KubernetesError::Base64DecodeError { source: err }
-                                        })?,
-                                        PrivateKey::pem(
-                                            base64::decode(key).map_err(|err| {
-                                                KubernetesError::Base64DecodeError { source: err }
-                                            })?,
-                                            None,
-                                        ),
+                                        certificate.clone(),
+                                        PrivateKey::pem(key.clone(), None),
                                     ))
                                     .ssl_ca_certificate(CaCertificate::file(
                                         tmpfile.into_temp_path().to_path_buf(),
@@ -86,6 +162,7 @@ impl Kubernetes {
                                     Ok(client) => client,
                                     Err(err) => return Err(KubernetesError::HttpClientBuildError { message: format!("Failed to initialize http client with client certificate: {}", err) })
                                 };
+                                credentials = Credentials::ClientCertificate { certificate, key };
                             } else {
                                 return Err(KubernetesError::HttpClientBuildError {
                                     message: String::from(
@@ -152,64 +229,257 @@ impl Kubernetes {
             kubeconfig,
             http_client,
             base_uri,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+            timeouts: Timeouts::default(),
+            discovery_cache: Some(DiscoveryCache::default()),
+            credentials,
         })
     }
 
-    fn request<T>(
+    /// Sets the policy used to retry requests failing for transient reasons.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limits requests to `qps` per second, with bursts of up to `burst` requests,
+    /// like client-go's `QPS` and `Burst` settings. No limit is applied by default.
+    pub fn with_rate_limit(self, qps: f64, burst: u32) -> Self {
+        self.with_rate_limiter(Arc::new(RateLimiter::new(qps, burst)))
+    }
+
+    /// Uses `rate_limiter` for every request, which allows sharing it between clients.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Runs `call` with `timeout` as the request timeout of every request it sends,
+    /// instead of `self.timeouts.request`.
+    ///
+    /// ```no_run
+    /// # use k8s_sync::kubernetes::Kubernetes;
+    /// # use std::time::Duration;
+    /// # let client = Kubernetes::connect(None, None, None, None, true).unwrap();
+    /// let pods = client.with_timeout(Duration::from_secs(2), |c| {
+    ///     c.list_pods(String::from("kube-system"), Default::default())
+    /// });
+    /// ```
+    pub fn with_timeout<R, F>(&self, timeout: Duration, call: F) -> R
+    where
+        F: FnOnce(&Self) -> R,
+    {
+        let _guard = CallTimeoutGuard(CALL_TIMEOUT.with(|current| current.replace(Some(timeout))));
+        call(self)
+    }
+
+    /// Sends a request built against a path relative to the API server (as produced
+    /// by `k8s_openapi`) and returns the response if its status code is a success.
+    ///
+    /// Non-2xx answers are turned into `KubernetesError::ApiError`, carrying the
+    /// `metav1.Status` object sent back by the API server when it can be decoded.
+    /// Transient failures are retried according to `self.retry_policy`.
+    pub(crate) fn send(
         &self,
         request: Request<Vec<u8>>,
-        response_body: fn(StatusCode) -> ResponseBody<T>,
-    ) -> Result<(Body, ResponseBody<T>), KubernetesError> {
+    ) -> Result<Response<Body>, KubernetesError> {
+        self.send_with_retries(request, false)
+    }
+
+    /// Like `send`, for long-running responses (watch, logs) read as a stream: the
+    /// `request` timeout doesn't apply, the `idle` one does.
+    pub(crate) fn send_stream(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<Response<Body>, KubernetesError> {
+        self.send_with_retries(request, true)
+    }
+
+    /// Sends a request and deserializes the JSON body of the response.
+    pub(crate) fn request_json<T: DeserializeOwned>(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<T, KubernetesError> {
+        let mut response = self.send(request)?;
+        let mut raw = vec![];
+        response
+            .body_mut()
+            .read_to_end(&mut raw)
+            .map_err(|source| KubernetesError::IoError { source })?;
+        serde_json::from_slice(&raw).map_err(|source| KubernetesError::JsonError { source })
+    }
+
+    fn send_with_retries(
+        &self,
+        request: Request<Vec<u8>>,
+        stream: bool,
+    ) -> Result<Response<Body>, KubernetesError> {
         let (parts, body) = request.into_parts();
-        let uri_str = format!("{}{}", self.base_uri, parts.uri);
-        let request = Request::builder().uri(uri_str).body(body).map_err(|err| {
-            KubernetesError::HttpClientBuildError {
-                message: format!("Couldn't build request. Error: {:?}", err),
+        let mut attempt = 1;
+        loop {
+            match self.send_once(&parts, body.clone(), stream) {
+                Ok(response) => return Ok(response),
+                Err((err, retry_after)) => {
+                    if !self.retry_policy.should_retry(&parts.method, &err, attempt)
+                        || !self.retry_policy.accepts_retry_after(retry_after)
+                    {
+                        return Err(err);
+                    }
+                    thread::sleep(self.retry_policy.backoff(attempt, retry_after));
+                    attempt += 1;
+                }
             }
-        })?;
-        let response = self
-            .http_client
-            .send(request)
-            .map_err(|_| KubernetesError::HttpClientRequestError)?;
+        }
+    }
+
+    /// Sends a request without retrying it nor turning non-2xx answers into errors,
+    /// for endpoints whose failures are meaningful answers (e.g. health checks).
+    pub(crate) fn send_unchecked(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<Response<Body>, KubernetesError> {
+        let (parts, body) = request.into_parts();
+        self.execute(&parts, body, false)
+    }
+
+    /// A single attempt of `send`. On failure, also returns the delay requested
+    /// by the server before trying again, if any.
+    fn send_once(
+        &self,
+        parts: &request::Parts,
+        body: Vec<u8>,
+        stream: bool,
+    ) -> Result<Response<Body>, (KubernetesError, Option<Duration>)> {
+        let mut response = self
+            .execute(parts, body, stream)
+            .map_err(|err| (err, None))?;
+        let url = format!("{}{}", self.base_uri, parts.uri);
         let status_code = response.status();
         if !status_code.is_success() {
-            return Err(KubernetesError::HttpClientRequestError);
+            let mut raw = vec![];
+            // The body is only used to enrich the error, a failure to read it is not fatal.
+            let _ = response.body_mut().read_to_end(&mut raw);
+            let status = serde_json::from_slice::<Status>(&raw).ok();
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after)
+                .or_else(|| {
+                    status
+                        .as_ref()
+                        .and_then(|s| s.details.as_ref())
+                        .and_then(|d| d.retry_after_seconds)
+                        .map(|seconds| Duration::from_secs(seconds.max(0) as u64))
+                });
+            let err = KubernetesError::ApiError {
+                verb: parts.method.clone(),
+                url,
+                status_code,
+                status: status.map(Box::new),
+            };
+            return Err((err, retry_after));
+        }
+        Ok(response)
+    }
+
+    /// Sends the request over the wire, applying rate limiting and timeouts.
+    fn execute(
+        &self,
+        parts: &request::Parts,
+        body: Vec<u8>,
+        stream: bool,
+    ) -> Result<Response<Body>, KubernetesError> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire();
+        }
+        let url = format!("{}{}", self.base_uri, parts.uri);
+        let mut builder = Request::builder().method(parts.method.clone()).uri(&url);
+        if let Some(connect) = self.timeouts.connect {
+            builder = builder.connect_timeout(connect);
         }
-        let response_body = response_body(status_code);
+        if stream {
+            if let Some(idle) = self.timeouts.idle {
+                // Less than one byte per second during `idle` means nothing was received.
+                builder = builder.low_speed_timeout(1, idle);
+            }
+        } else if let Some(timeout) = CALL_TIMEOUT.with(Cell::get).or(self.timeouts.request) {
+            builder = builder.timeout(timeout);
+        }
+        for (name, value) in parts.headers.iter() {
+            builder = builder.header(name, value);
+        }
+        let request = builder
+            .body(body)
+            .map_err(|err| KubernetesError::HttpClientBuildError {
+                message: format!("Couldn't build request. Error: {:?}", err),
+            })?;
+        self.http_client.send(request).map_err(|source| {
+            if source.is_timeout() {
+                let verb = parts.method.clone();
+                KubernetesError::TimeoutError { verb, url, source }
+            } else {
+                KubernetesError::HttpClientRequestError { source }
+            }
+        })
+    }
+
+    fn request<T>(
+        &self,
+        request: Request<Vec<u8>>,
+        response_body: fn(StatusCode) -> ResponseBody<T>,
+    ) -> Result<(Body, ResponseBody<T>), KubernetesError> {
+        let response = self.send(request)?;
+        let response_body = response_body(response.status());
         let body = response.into_body();
         Ok((body, response_body))
     }
 
-    pub fn get_events(&self, since: Option<String>) -> Result<Vec<api::Event>, KubernetesError> {
-        let (request, response_body) =
-            match api::Event::list_event_for_all_namespaces(Default::default()) {
-                Ok((request, response_body)) => (request, response_body),
-                Err(err) => return Err(KubernetesError::ApiRequestError { source: err }),
-            };
-        let (mut body, mut response_body) = self.request(request, response_body)?;
+    /// Reads the body until `k8s_openapi` is able to parse a full response out of it.
+    fn read_response<T: k8s_openapi::Response>(
+        mut body: Body,
+        mut response_body: ResponseBody<T>,
+    ) -> Result<T, KubernetesError> {
         let mut buf = Box::new([0u8; 4096]);
-        let events_list_raw = loop {
+        loop {
             let read = body.read(&mut *buf).map_err(|err| {
                 KubernetesError::HttpClientParseResponseError {
                     message: format!("Got error: {}", err),
                 }
             })?;
             response_body.append_slice(&buf[..read]);
-            let response = response_body.parse();
-            match response {
-                Ok(k8s_openapi::ListResponse::Ok(events_list)) => break events_list,
-                Ok(other) => {
-                    return Err(KubernetesError::HttpClientParseResponseError {
-                        message: format!("expected Ok but got {:?}", other),
-                    })
-                }
-                Err(k8s_openapi::ResponseError::NeedMoreData) => continue,
+            match response_body.parse() {
+                Ok(response) => return Ok(response),
+                Err(k8s_openapi::ResponseError::NeedMoreData) if read > 0 => continue,
                 Err(err) => {
                     return Err(KubernetesError::HttpClientParseResponseError {
-                        message: format!("error: {:?}", err),
+                        message: format!("error: {} {:?}", response_body.status_code, err),
                     })
                 }
             }
+        }
+    }
+
+    pub fn get_events(&self, since: Option<String>) -> Result<Vec<api::Event>, KubernetesError> {
+        let (request, response_body) =
+            match api::Event::list_event_for_all_namespaces(Default::default()) {
+                Ok((request, response_body)) => (request, response_body),
+                Err(err) => return Err(KubernetesError::ApiRequestError { source: err }),
+            };
+        let (body, response_body) = self.request(request, response_body)?;
+        let events_list_raw = match Self::read_response(body, response_body)? {
+            k8s_openapi::ListResponse::Ok(events_list) => events_list,
+            other => {
+                return Err(KubernetesError::HttpClientParseResponseError {
+                    message: format!("expected Ok but got {:?}", other),
+                })
+            }
         };
         let events = events_list_raw.items;
         let mut since_datetime = None;
@@ -243,48 +513,41 @@ impl Kubernetes {
             Ok((request, response_body)) => (request, response_body),
             Err(err) => return Err(KubernetesError::ApiRequestError { source: err }),
         };
-        let (parts, body) = request.into_parts();
-        let uri_str = format!("{}{}", self.base_uri, parts.uri);
-        let request = Request::builder().uri(uri_str).body(body).map_err(|err| {
-            KubernetesError::HttpClientBuildError {
-                message: format!("Couldn't build request. Error: {:?}", err),
-            }
-        })?;
-        let response = self
-            .http_client
-            .send(request)
-            .map_err(|_| KubernetesError::HttpClientRequestError)?;
-        let status_code = response.status();
-        if !status_code.is_success() {
-            return Err(KubernetesError::HttpClientRequestError);
-        }
-        let mut response_body = response_body(status_code);
-        let mut buf = Box::new([0u8; 4096]);
-        let mut body = response.into_body();
-        let pods_list_raw = loop {
-            let read = body.read(&mut *buf).map_err(|err| {
-                KubernetesError::HttpClientParseResponseError {
-                    message: format!("Got error : {}", err),
-                }
-            })?;
-            response_body.append_slice(&buf[..read]);
-            let response = response_body.parse();
-            match response {
-                Ok(k8s_openapi::ListResponse::Ok(pod_list)) => break pod_list,
-                Ok(other) => {
-                    return Err(KubernetesError::HttpClientParseResponseError {
-                        message: format!("expected Ok but got {} {:?}", status_code, other),
-                    })
-                }
-                Err(k8s_openapi::ResponseError::NeedMoreData) => continue,
-                Err(err) => {
-                    return Err(KubernetesError::HttpClientParseResponseError {
-                        message: format!("error: {} {:?}", status_code, err),
-                    })
-                }
+        let (body, response_body) = self.request(request, response_body)?;
+        let status_code = response_body.status_code;
+        let pods_list_raw = match Self::read_response(body, response_body)? {
+            k8s_openapi::ListResponse::Ok(pod_list) => pod_list,
+            other => {
+                return Err(KubernetesError::HttpClientParseResponseError {
+                    message: format!("expected Ok but got {} {:?}", status_code, other),
+                })
             }
         };
 
         Ok(pods_list_raw.items)
     }
+
+    /// Pods scheduled on `node_name`, in every namespace, or on the node we run on
+    /// if `None` (see `node::detect_node_name`). A field selector in `optional` is
+    /// combined with the node one.
+    pub fn list_pods_on_node(
+        &self,
+        node_name: Option<String>,
+        optional: ListOptional,
+    ) -> Result<Vec<api::Pod>, KubernetesError> {
+        let node_name = match node_name {
+            Some(node_name) => node_name,
+            None => detect_node_name()?,
+        };
+        let mut field_selector = format!("spec.nodeName={}", node_name);
+        if let Some(other) = optional.field_selector {
+            field_selector.push(',');
+            field_selector.push_str(other);
+        }
+        let optional = ListOptional {
+            field_selector: Some(&field_selector),
+            ..optional
+        };
+        Ok(self.api::<api::Pod>(None).list(optional)?.items)
+    }
 }
@@ -1,8 +1,31 @@
 // declare modules
 //pub mod kubernetes;
+pub mod apply;
+pub mod cgroup;
 pub mod config;
+pub mod containers;
+pub mod delete;
+pub mod discovery;
+pub mod drain;
+pub mod dynamic;
 pub mod errors;
+pub mod exec;
+pub mod kubelet;
 pub mod kubernetes;
+pub mod logs;
+pub mod manifest;
+pub mod metrics;
+pub mod node;
+pub mod patch;
+pub mod portforward;
+pub mod quantity;
+pub mod ratelimit;
+pub mod retry;
+pub mod rollout;
+pub mod scale;
+pub mod server;
+pub mod wait;
+mod ws;
 
 pub use k8s_openapi::api::core::v1::Pod;
 pub use k8s_openapi::ListOptional;
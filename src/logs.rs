@@ -0,0 +1,149 @@
+use crate::dynamic::{build_request, encode_segment, with_query, GroupVersionResource};
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use http::Method;
+use isahc::Body;
+use std::io::{self, Read};
+use url::form_urlencoded;
+
+/// Parameters of a pod log request, matching the `kubectl logs` flags.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogOptions {
+    /// Container to read logs from. Required when the pod has several containers.
+    pub container: Option<String>,
+    /// Keep the stream open and return new lines as they are written.
+    pub follow: bool,
+    /// Logs of the previous, terminated, instance of the container.
+    pub previous: bool,
+    /// Only lines newer than this many seconds. Exclusive with `since_time`.
+    pub since_seconds: Option<i64>,
+    /// Only lines written after this time. Exclusive with `since_seconds`.
+    pub since_time: Option<DateTime<Utc>>,
+    /// Only the last lines, before following.
+    pub tail_lines: Option<i64>,
+    /// Prefix each line with its RFC 3339 timestamp.
+    pub timestamps: bool,
+    /// Stop after this many bytes.
+    pub limit_bytes: Option<i64>,
+}
+
+impl LogOptions {
+    fn query(&self) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        if let Some(container) = &self.container {
+            query.append_pair("container", container);
+        }
+        if self.follow {
+            query.append_pair("follow", "true");
+        }
+        if self.previous {
+            query.append_pair("previous", "true");
+        }
+        if let Some(since_seconds) = self.since_seconds {
+            query.append_pair("sinceSeconds", &since_seconds.to_string());
+        }
+        if let Some(since_time) = self.since_time {
+            query.append_pair(
+                "sinceTime",
+                &since_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            );
+        }
+        if let Some(tail_lines) = self.tail_lines {
+            query.append_pair("tailLines", &tail_lines.to_string());
+        }
+        if self.timestamps {
+            query.append_pair("timestamps", "true");
+        }
+        if let Some(limit_bytes) = self.limit_bytes {
+            query.append_pair("limitBytes", &limit_bytes.to_string());
+        }
+        query.finish()
+    }
+}
+
+/// Logs of a container, read as they come from the API server. When following,
+/// reads block until new lines are written or the container stops.
+pub struct LogStream {
+    body: Body,
+}
+
+impl Read for LogStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+impl std::fmt::Debug for LogStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("LogStream").finish()
+    }
+}
+
+impl Kubernetes {
+    /// Streams the logs of a pod container. Wrap the stream in a `BufReader` to
+    /// read it line by line.
+    pub fn pod_logs(
+        &self,
+        namespace: &str,
+        pod: &str,
+        options: &LogOptions,
+    ) -> Result<LogStream, KubernetesError> {
+        let path = format!(
+            "{}/{}/log",
+            GroupVersionResource::new("", "v1", "pods").url_path(Some(namespace)),
+            encode_segment(pod)
+        );
+        let request = build_request(Method::GET, with_query(path, options.query()), None, vec![])?;
+        let response = if options.follow {
+            self.send_stream(request)?
+        } else {
+            self.send(request)?
+        };
+        Ok(LogStream {
+            body: response.into_body(),
+        })
+    }
+
+    /// Reads the logs of a pod container written so far. `follow` is ignored, as
+    /// the logs of a running container would never end.
+    pub fn pod_logs_string(
+        &self,
+        namespace: &str,
+        pod: &str,
+        options: &LogOptions,
+    ) -> Result<String, KubernetesError> {
+        let options = LogOptions {
+            follow: false,
+            ..options.clone()
+        };
+        let mut logs = String::new();
+        self.pod_logs(namespace, pod, &options)?
+            .read_to_string(&mut logs)
+            .map_err(|source| KubernetesError::IoError { source })?;
+        Ok(logs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_query() {
+        assert_eq!(LogOptions::default().query(), "");
+        let options = LogOptions {
+            container: Some(String::from("app")),
+            follow: true,
+            since_time: Some(Utc.with_ymd_and_hms(2021, 8, 2, 10, 0, 0).unwrap()),
+            tail_lines: Some(100),
+            timestamps: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            options.query(),
+            "container=app&follow=true&sinceTime=2021-08-02T10%3A00%3A00Z&tailLines=100&timestamps=true"
+        );
+    }
+}
@@ -0,0 +1,702 @@
+use crate::apply::ApplyOptions;
+use crate::delete::DeleteOptions;
+use crate::discovery::{split_api_version, ApiResource, Discovery};
+use crate::dynamic::{DynamicObject, GroupVersionResource};
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use crate::wait::WaitCondition;
+use k8s_openapi::api::core::v1::Secret;
+use k8s_openapi::ListOptional;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::Duration;
+
+/// Label of the ApplySet parent, holding the ID of the set.
+pub const APPLYSET_ID_LABEL: &str = "applyset.kubernetes.io/id";
+/// Label of the members of an ApplySet, holding the ID of the set.
+pub const APPLYSET_PART_OF_LABEL: &str = "applyset.kubernetes.io/part-of";
+const APPLYSET_TOOLING_ANNOTATION: &str = "applyset.kubernetes.io/tooling";
+const APPLYSET_GROUP_KINDS_ANNOTATION: &str = "applyset.kubernetes.io/contains-group-kinds";
+const APPLYSET_NAMESPACES_ANNOTATION: &str = "applyset.kubernetes.io/additional-namespaces";
+
+/// Order in which kinds are applied, so that objects come after what they use
+/// (namespaces, service accounts, config...). Other kinds come last.
+const INSTALL_ORDER: [&str; 33] = [
+    "Namespace",
+    "NetworkPolicy",
+    "ResourceQuota",
+    "LimitRange",
+    "PodSecurityPolicy",
+    "PodDisruptionBudget",
+    "ServiceAccount",
+    "Secret",
+    "ConfigMap",
+    "StorageClass",
+    "PersistentVolume",
+    "PersistentVolumeClaim",
+    "CustomResourceDefinition",
+    "ClusterRole",
+    "ClusterRoleBinding",
+    "Role",
+    "RoleBinding",
+    "Service",
+    "DaemonSet",
+    "Pod",
+    "ReplicationController",
+    "ReplicaSet",
+    "Deployment",
+    "HorizontalPodAutoscaler",
+    "StatefulSet",
+    "Job",
+    "CronJob",
+    "IngressClass",
+    "Ingress",
+    "APIService",
+    "MutatingWebhookConfiguration",
+    "ValidatingWebhookConfiguration",
+    "PriorityClass",
+];
+
+/// Parses a stream of YAML or JSON documents, separated by `---`, into objects.
+/// Empty documents are skipped and `List` kinds are replaced by their items.
+pub fn parse_manifests(text: &str) -> Result<Vec<DynamicObject>, KubernetesError> {
+    let mut objects = vec![];
+    for (index, document) in serde_yaml::Deserializer::from_str(text).enumerate() {
+        let error = |message: String| KubernetesError::ManifestError {
+            document: index + 1,
+            message,
+        };
+        let value = Value::deserialize(document).map_err(|err| error(err.to_string()))?;
+        if !value.is_null() {
+            collect_objects(value, &mut objects).map_err(error)?;
+        }
+    }
+    Ok(objects)
+}
+
+fn collect_objects(value: Value, objects: &mut Vec<DynamicObject>) -> Result<(), String> {
+    if !value.is_object() {
+        return Err(String::from("expected an object"));
+    }
+    let kind = value
+        .get("kind")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    if kind.ends_with("List") {
+        if let Some(Value::Array(items)) = value.get("items") {
+            for item in items {
+                collect_objects(item.clone(), objects)?;
+            }
+            return Ok(());
+        }
+    }
+    let object: DynamicObject = serde_json::from_value(value).map_err(|err| err.to_string())?;
+    match (&object.api_version, &object.kind, object.name()) {
+        (Some(_), Some(_), Some(_)) => {
+            objects.push(object);
+            Ok(())
+        }
+        (None, _, _) => Err(String::from("apiVersion is required")),
+        (_, None, _) => Err(String::from("kind is required")),
+        (_, _, None) => Err(format!("metadata.name is required for {}", kind)),
+    }
+}
+
+/// A set of applied objects, tracked by a parent Secret as in `kubectl apply --prune
+/// --applyset`, so that objects removed from the manifests can be deleted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApplySet {
+    /// Name of the parent Secret.
+    pub name: String,
+    pub namespace: String,
+}
+
+impl ApplySet {
+    pub fn new(name: &str, namespace: &str) -> Self {
+        ApplySet {
+            name: name.to_string(),
+            namespace: namespace.to_string(),
+        }
+    }
+
+    /// ID of the set, derived from the parent as specified by KEP-3659.
+    pub fn id(&self) -> String {
+        let parent = format!("{}.{}.Secret.", self.name, self.namespace);
+        let hash = openssl::sha::sha256(parent.as_bytes());
+        format!(
+            "applyset-{}-v1",
+            base64::encode_config(hash, base64::URL_SAFE_NO_PAD)
+        )
+    }
+}
+
+/// Parameters of `Kubernetes::apply_manifests`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestOptions {
+    pub apply: ApplyOptions,
+    /// Namespace of the namespaced objects that don't set one, `default` if `None`.
+    pub namespace: Option<String>,
+    /// How long to wait for applied CRDs to be established.
+    pub crd_timeout: Duration,
+    /// Delete the members of this set that are not in the manifests anymore.
+    pub prune: Option<ApplySet>,
+}
+
+impl ManifestOptions {
+    pub fn new(field_manager: &str) -> Self {
+        ManifestOptions {
+            apply: ApplyOptions::new(field_manager),
+            namespace: None,
+            crd_timeout: Duration::from_secs(60),
+            prune: None,
+        }
+    }
+
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    pub fn prune(mut self, applyset: ApplySet) -> Self {
+        self.prune = Some(applyset);
+        self
+    }
+
+    fn namespace_or_default(&self) -> &str {
+        self.namespace.as_deref().unwrap_or("default")
+    }
+}
+
+/// What applying the manifests did to an object.
+#[derive(Debug)]
+pub enum ApplyOutcome {
+    Created,
+    Configured,
+    Unchanged,
+    /// Deleted as a member of the ApplySet missing from the manifests.
+    Pruned,
+    Failed(KubernetesError),
+}
+
+/// Outcome for one object of the manifests.
+#[derive(Debug)]
+pub struct ApplyResult {
+    pub api_version: String,
+    pub kind: String,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub outcome: ApplyOutcome,
+}
+
+impl ApplyResult {
+    pub fn is_failed(&self) -> bool {
+        matches!(self.outcome, ApplyOutcome::Failed(_))
+    }
+}
+
+/// Same format as kubectl, e.g. `deployment.apps/web configured`.
+impl fmt::Display for ApplyResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (group, _) = split_api_version(&self.api_version);
+        write!(f, "{}", self.kind.to_lowercase())?;
+        if !group.is_empty() {
+            write!(f, ".{}", group)?;
+        }
+        write!(f, "/{} ", self.name)?;
+        match &self.outcome {
+            ApplyOutcome::Created => write!(f, "created"),
+            ApplyOutcome::Configured => write!(f, "configured"),
+            ApplyOutcome::Unchanged => write!(f, "unchanged"),
+            ApplyOutcome::Pruned => write!(f, "pruned"),
+            ApplyOutcome::Failed(err) => write!(f, "failed: {}", err),
+        }
+    }
+}
+
+/// `Kind.group`, or `Kind` for the core group, as in ApplySet annotations.
+fn group_kind(api_version: &str, kind: &str) -> String {
+    match split_api_version(api_version).0 {
+        "" => kind.to_string(),
+        group => format!("{}.{}", kind, group),
+    }
+}
+
+fn is_crd(object: &DynamicObject) -> bool {
+    object.kind.as_deref() == Some("CustomResourceDefinition")
+        && object
+            .api_version
+            .as_deref()
+            .is_some_and(|v| split_api_version(v).0 == "apiextensions.k8s.io")
+}
+
+fn is_namespace(object: &DynamicObject) -> bool {
+    object.kind.as_deref() == Some("Namespace") && object.api_version.as_deref() == Some("v1")
+}
+
+fn install_rank(object: &DynamicObject) -> usize {
+    let kind = object.kind.as_deref().unwrap_or_default();
+    INSTALL_ORDER
+        .iter()
+        .position(|k| *k == kind)
+        .unwrap_or(INSTALL_ORDER.len())
+}
+
+/// Annotation value of an ApplySet parent, as a set.
+fn annotation_set(secret: &Option<Secret>, annotation: &str) -> BTreeSet<String> {
+    secret
+        .as_ref()
+        .and_then(|s| s.metadata.annotations.as_ref())
+        .and_then(|a| a.get(annotation))
+        .map(|v| {
+            v.split(',')
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn join(set: &BTreeSet<String>) -> String {
+    set.iter().cloned().collect::<Vec<_>>().join(",")
+}
+
+impl Kubernetes {
+    /// Server-side applies `objects`, e.g. from `parse_manifests`, like `kubectl apply
+    /// --server-side -f`, and reports what happened to each of them.
+    ///
+    /// Namespaces and CRDs are applied first, then other objects once the CRDs are
+    /// established. An object that fails doesn't stop the others. With `prune`,
+    /// members of the ApplySet that are not in `objects` anymore are deleted.
+    pub fn apply_manifests(
+        &self,
+        objects: &[DynamicObject],
+        options: &ManifestOptions,
+    ) -> Result<Vec<ApplyResult>, KubernetesError> {
+        let mut kinds = Kinds {
+            discovery: self.discovery()?,
+            refreshed: false,
+        };
+        let mut objects: Vec<&DynamicObject> = objects.iter().collect();
+        objects.sort_by_key(|o| install_rank(o));
+        let (first, rest): (Vec<&DynamicObject>, Vec<&DynamicObject>) = objects
+            .into_iter()
+            .partition(|o| is_namespace(o) || is_crd(o));
+
+        let applyset = match &options.prune {
+            Some(applyset) => {
+                Some(self.start_applyset(&kinds.discovery, applyset, &first, &rest, options)?)
+            }
+            None => None,
+        };
+        let part_of = applyset.as_ref().map(|a| a.id.as_str());
+
+        let mut results = vec![];
+        let mut crds = vec![];
+        for object in first {
+            let result = self.apply_object(&mut kinds, object, part_of, options);
+            if is_crd(object) && !result.is_failed() && !options.apply.dry_run {
+                crds.push(results.len());
+            }
+            results.push(result);
+        }
+        if !crds.is_empty() {
+            let api = self.dynamic(
+                GroupVersionResource::new(
+                    "apiextensions.k8s.io",
+                    "v1",
+                    "customresourcedefinitions",
+                ),
+                None,
+            );
+            for index in crds {
+                let result = &mut results[index];
+                if let Err(err) = api.wait_for(
+                    &result.name,
+                    &WaitCondition::condition("Established"),
+                    options.crd_timeout,
+                ) {
+                    result.outcome = ApplyOutcome::Failed(err);
+                }
+            }
+            kinds.discovery = self.refresh_discovery()?;
+            kinds.refreshed = true;
+        }
+        for object in rest {
+            results.push(self.apply_object(&mut kinds, object, part_of, options));
+        }
+
+        // Like kubectl, nothing is pruned when an object couldn't be applied.
+        let failed = results.iter().any(ApplyResult::is_failed);
+        if let (Some(prune), Some(applyset), false) = (&options.prune, applyset, failed) {
+            let mut pruned =
+                self.prune_applyset(&kinds.discovery, prune, &applyset, &results, options)?;
+            results.append(&mut pruned);
+        }
+        Ok(results)
+    }
+
+    /// Applies one object and tells what happened.
+    fn apply_object(
+        &self,
+        kinds: &mut Kinds,
+        object: &DynamicObject,
+        part_of: Option<&str>,
+        options: &ManifestOptions,
+    ) -> ApplyResult {
+        let api_version = object.api_version.clone().unwrap_or_default();
+        let kind = object.kind.clone().unwrap_or_default();
+        let name = object.name().unwrap_or_default().to_string();
+        let mut result = ApplyResult {
+            api_version: api_version.clone(),
+            kind: kind.clone(),
+            namespace: None,
+            name: name.clone(),
+            outcome: ApplyOutcome::Unchanged,
+        };
+        let resource = match self.resolve_kind(kinds, &api_version, &kind) {
+            Ok(resource) => resource,
+            Err(err) => {
+                result.outcome = ApplyOutcome::Failed(err);
+                return result;
+            }
+        };
+        if resource.namespaced {
+            result.namespace = Some(
+                object
+                    .namespace()
+                    .unwrap_or_else(|| options.namespace_or_default())
+                    .to_string(),
+            );
+        }
+
+        let api = self.dynamic(resource.gvr(), result.namespace.as_deref());
+        let mut object = object.clone();
+        object.metadata.namespace = result.namespace.clone();
+        if let Some(id) = part_of {
+            object
+                .metadata
+                .labels
+                .get_or_insert_with(Default::default)
+                .insert(APPLYSET_PART_OF_LABEL.to_string(), id.to_string());
+        }
+        let outcome = api
+            .get(&name)
+            .map(|before| before.metadata.resource_version)
+            .or_else(|err| {
+                if err.is_not_found() {
+                    Ok(None)
+                } else {
+                    Err(err)
+                }
+            })
+            .and_then(|before| Ok((before, api.apply(&name, &object, &options.apply)?)));
+        result.outcome = match outcome {
+            Ok((None, _)) => ApplyOutcome::Created,
+            Ok((before, after)) if before == after.metadata.resource_version => {
+                ApplyOutcome::Unchanged
+            }
+            Ok(_) => ApplyOutcome::Configured,
+            Err(err) => ApplyOutcome::Failed(err),
+        };
+        result
+    }
+
+    /// Resource serving `kind`, refreshing discovery once if it's unknown: it may
+    /// have been added since discovery was cached, or its group may have failed.
+    fn resolve_kind(
+        &self,
+        kinds: &mut Kinds,
+        api_version: &str,
+        kind: &str,
+    ) -> Result<ApiResource, KubernetesError> {
+        if let Some(resource) = kinds.discovery.resolve(api_version, kind) {
+            return Ok(resource.clone());
+        }
+        if !kinds.refreshed {
+            kinds.discovery = self.refresh_discovery()?;
+            kinds.refreshed = true;
+        }
+        if let Some(resource) = kinds.discovery.resolve(api_version, kind) {
+            return Ok(resource.clone());
+        }
+        Err(match kinds.discovery.failed(api_version) {
+            Some(failed) => KubernetesError::GroupDiscoveryError {
+                group_version: failed.group_version.clone(),
+                message: failed.message.clone(),
+            },
+            None => KubernetesError::UnknownKindError {
+                api_version: api_version.to_string(),
+                kind: kind.to_string(),
+            },
+        })
+    }
+
+    /// Records on the parent the kinds and namespaces of both the previous and the
+    /// new members, so that they can be pruned even if applying is interrupted.
+    fn start_applyset(
+        &self,
+        discovery: &Discovery,
+        applyset: &ApplySet,
+        first: &[&DynamicObject],
+        rest: &[&DynamicObject],
+        options: &ManifestOptions,
+    ) -> Result<AppliedSet, KubernetesError> {
+        let parent = match self
+            .api::<Secret>(Some(&applyset.namespace))
+            .get(&applyset.name)
+        {
+            Ok(parent) => Some(parent),
+            Err(err) if err.is_not_found() => None,
+            Err(err) => return Err(err),
+        };
+        let mut set = AppliedSet {
+            id: applyset.id(),
+            group_kinds: annotation_set(&parent, APPLYSET_GROUP_KINDS_ANNOTATION),
+            namespaces: annotation_set(&parent, APPLYSET_NAMESPACES_ANNOTATION),
+        };
+        for object in first.iter().chain(rest) {
+            let api_version = object.api_version.as_deref().unwrap_or_default();
+            let kind = object.kind.as_deref().unwrap_or_default();
+            set.group_kinds.insert(group_kind(api_version, kind));
+            // Kinds not known yet, e.g. defined by a CRD of the manifests, may be
+            // namespaced.
+            let namespaced = discovery
+                .resolve(api_version, kind)
+                .is_none_or(|r| r.namespaced);
+            if namespaced {
+                let namespace = object
+                    .namespace()
+                    .unwrap_or_else(|| options.namespace_or_default());
+                set.namespaces.insert(namespace.to_string());
+            }
+        }
+        set.namespaces.remove(&applyset.namespace);
+        self.update_applyset(applyset, &set, options)?;
+        Ok(set)
+    }
+
+    fn update_applyset(
+        &self,
+        applyset: &ApplySet,
+        set: &AppliedSet,
+        options: &ManifestOptions,
+    ) -> Result<(), KubernetesError> {
+        let mut annotations = BTreeMap::new();
+        annotations.insert(
+            APPLYSET_TOOLING_ANNOTATION,
+            concat!("k8s-sync/v", env!("CARGO_PKG_VERSION")).to_string(),
+        );
+        annotations.insert(APPLYSET_GROUP_KINDS_ANNOTATION, join(&set.group_kinds));
+        if !set.namespaces.is_empty() {
+            annotations.insert(APPLYSET_NAMESPACES_ANNOTATION, join(&set.namespaces));
+        }
+        let parent: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": {
+                "name": applyset.name,
+                "namespace": applyset.namespace,
+                "labels": {APPLYSET_ID_LABEL: set.id},
+                "annotations": annotations,
+            }
+        }))
+        .map_err(|source| KubernetesError::JsonError { source })?;
+        self.dynamic(
+            GroupVersionResource::of::<Secret>(),
+            Some(&applyset.namespace),
+        )
+        .apply(&applyset.name, &parent, &options.apply)?;
+        Ok(())
+    }
+
+    /// Deletes the members of the set missing from `results`, then records the
+    /// kinds and namespaces of the remaining members on the parent.
+    fn prune_applyset(
+        &self,
+        discovery: &Discovery,
+        applyset: &ApplySet,
+        set: &AppliedSet,
+        results: &[ApplyResult],
+        options: &ManifestOptions,
+    ) -> Result<Vec<ApplyResult>, KubernetesError> {
+        let selector = format!("{}={}", APPLYSET_PART_OF_LABEL, set.id);
+        let applied: BTreeSet<(String, Option<String>, String)> = results
+            .iter()
+            .map(|r| {
+                (
+                    group_kind(&r.api_version, &r.kind),
+                    r.namespace.clone(),
+                    r.name.clone(),
+                )
+            })
+            .collect();
+
+        let mut pruned = vec![];
+        for group_kind_name in &set.group_kinds {
+            let (kind, group) = group_kind_name
+                .split_once('.')
+                .unwrap_or((group_kind_name, ""));
+            let resource = match discovery.resolve_preferred(group, kind) {
+                Some(resource) => resource,
+                None => continue,
+            };
+            let namespaces: Vec<Option<&str>> = if resource.namespaced {
+                std::iter::once(applyset.namespace.as_str())
+                    .chain(set.namespaces.iter().map(String::as_str))
+                    .map(Some)
+                    .collect()
+            } else {
+                vec![None]
+            };
+            for namespace in namespaces {
+                let api = self.dynamic(resource.gvr(), namespace);
+                let members = api.list(ListOptional {
+                    label_selector: Some(&selector),
+                    ..Default::default()
+                })?;
+                for member in members.items {
+                    let name = member.name().unwrap_or_default().to_string();
+                    let key = (
+                        group_kind_name.clone(),
+                        namespace.map(String::from),
+                        name.clone(),
+                    );
+                    if applied.contains(&key) {
+                        continue;
+                    }
+                    let deleted = api.delete(
+                        &name,
+                        &DeleteOptions {
+                            dry_run: options.apply.dry_run,
+                            ..Default::default()
+                        },
+                    );
+                    pruned.push(ApplyResult {
+                        api_version: resource.api_version(),
+                        kind: resource.kind.clone(),
+                        namespace: namespace.map(String::from),
+                        name,
+                        outcome: match deleted {
+                            Ok(_) => ApplyOutcome::Pruned,
+                            Err(err) => ApplyOutcome::Failed(err),
+                        },
+                    });
+                }
+            }
+        }
+
+        let remaining = AppliedSet {
+            id: set.id.clone(),
+            group_kinds: applied.iter().map(|(gk, _, _)| gk.clone()).collect(),
+            namespaces: applied
+                .iter()
+                .filter_map(|(_, namespace, _)| namespace.clone())
+                .filter(|namespace| *namespace != applyset.namespace)
+                .collect(),
+        };
+        self.update_applyset(applyset, &remaining, options)?;
+        Ok(pruned)
+    }
+}
+
+/// Discovery as used while applying, refreshed at most once.
+struct Kinds {
+    discovery: Discovery,
+    refreshed: bool,
+}
+
+/// Kinds and namespaces of the members of an ApplySet, as recorded on its parent.
+struct AppliedSet {
+    id: String,
+    group_kinds: BTreeSet<String>,
+    namespaces: BTreeSet<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifests() {
+        let objects = parse_manifests(
+            r#"
+# The namespace first.
+apiVersion: v1
+kind: Namespace
+metadata:
+  name: shop
+---
+---
+{"apiVersion": "v1", "kind": "ConfigMap", "metadata": {"name": "settings"}, "data": {"a": "1"}}
+---
+apiVersion: v1
+kind: List
+items:
+- apiVersion: v1
+  kind: Service
+  metadata:
+    name: web
+    namespace: shop
+"#,
+        )
+        .unwrap();
+        let names: Vec<_> = objects
+            .iter()
+            .map(|o| (o.kind.as_deref().unwrap(), o.name().unwrap()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Namespace", "shop"),
+                ("ConfigMap", "settings"),
+                ("Service", "web")
+            ]
+        );
+        assert_eq!(objects[1].pointer("/data/a"), Some(&json!("1")));
+        assert_eq!(objects[2].namespace(), Some("shop"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse_manifests("kind: Pod\nmetadata:\n  name: a\n---\n- 1\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid manifest document 1: apiVersion is required"
+        );
+        let err = parse_manifests("apiVersion: v1\nkind: Pod\n---\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid manifest document 1: metadata.name is required for Pod"
+        );
+        let err = parse_manifests("{}\n---\n- 1\n").unwrap_err();
+        assert!(err.to_string().starts_with("Invalid manifest document 1"));
+        let err = parse_manifests("---\n- 1\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid manifest document 1: expected an object"
+        );
+    }
+
+    #[test]
+    fn test_applyset_id() {
+        assert_eq!(
+            ApplySet::new("shop", "shop").id(),
+            "applyset-GwAbKEnoQdgaoi0MSLuXqidpqgFxJVNssD4MzmoY9us-v1"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let result = ApplyResult {
+            api_version: String::from("apps/v1"),
+            kind: String::from("Deployment"),
+            namespace: Some(String::from("shop")),
+            name: String::from("web"),
+            outcome: ApplyOutcome::Configured,
+        };
+        assert_eq!(result.to_string(), "deployment.apps/web configured");
+    }
+}
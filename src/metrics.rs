@@ -0,0 +1,272 @@
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use crate::quantity::Quantity;
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity as ApiQuantity;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time};
+use k8s_openapi::{
+    ClusterResourceScope, ListOptional, ListableResource, NamespaceResourceScope, Resource,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// Resource usage of a node, from `metrics.k8s.io/v1beta1` (metrics-server).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeMetrics {
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    /// End of the interval the usage was measured on.
+    pub timestamp: Option<Time>,
+    /// Length of the interval, e.g. `10.5s`.
+    pub window: Option<String>,
+    /// `cpu` and `memory` usage.
+    #[serde(default)]
+    pub usage: BTreeMap<String, ApiQuantity>,
+}
+
+/// Resource usage of the containers of a pod, from `metrics.k8s.io/v1beta1`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PodMetrics {
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub timestamp: Option<Time>,
+    pub window: Option<String>,
+    #[serde(default)]
+    pub containers: Vec<ContainerMetrics>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContainerMetrics {
+    pub name: String,
+    #[serde(default)]
+    pub usage: BTreeMap<String, ApiQuantity>,
+}
+
+impl Resource for NodeMetrics {
+    const API_VERSION: &'static str = "metrics.k8s.io/v1beta1";
+    const GROUP: &'static str = "metrics.k8s.io";
+    const KIND: &'static str = "NodeMetrics";
+    const VERSION: &'static str = "v1beta1";
+    const URL_PATH_SEGMENT: &'static str = "nodes";
+    type Scope = ClusterResourceScope;
+}
+
+impl ListableResource for NodeMetrics {
+    const LIST_KIND: &'static str = "NodeMetricsList";
+}
+
+impl Resource for PodMetrics {
+    const API_VERSION: &'static str = "metrics.k8s.io/v1beta1";
+    const GROUP: &'static str = "metrics.k8s.io";
+    const KIND: &'static str = "PodMetrics";
+    const VERSION: &'static str = "v1beta1";
+    const URL_PATH_SEGMENT: &'static str = "pods";
+    type Scope = NamespaceResourceScope;
+}
+
+impl ListableResource for PodMetrics {
+    const LIST_KIND: &'static str = "PodMetricsList";
+}
+
+/// Usage of `resource`. `None` if not reported or invalid.
+fn quantity(usage: &BTreeMap<String, ApiQuantity>, resource: &str) -> Option<Quantity> {
+    Quantity::try_from(usage.get(resource)?).ok()
+}
+
+/// CPU usage in millicores, rounded up like `kubectl top`. `None` if not reported.
+fn cpu_millicores(usage: &BTreeMap<String, ApiQuantity>) -> Option<i64> {
+    quantity(usage, "cpu").map(|cpu| cpu.as_millicores())
+}
+
+/// Memory usage (working set) in bytes. `None` if not reported.
+fn memory_bytes(usage: &BTreeMap<String, ApiQuantity>) -> Option<i64> {
+    quantity(usage, "memory").map(|memory| memory.as_bytes())
+}
+
+impl NodeMetrics {
+    pub fn name(&self) -> Option<&str> {
+        self.metadata.name.as_deref()
+    }
+
+    pub fn cpu_millicores(&self) -> Option<i64> {
+        cpu_millicores(&self.usage)
+    }
+
+    pub fn memory_bytes(&self) -> Option<i64> {
+        memory_bytes(&self.usage)
+    }
+}
+
+impl ContainerMetrics {
+    pub fn cpu_millicores(&self) -> Option<i64> {
+        cpu_millicores(&self.usage)
+    }
+
+    pub fn memory_bytes(&self) -> Option<i64> {
+        memory_bytes(&self.usage)
+    }
+}
+
+impl PodMetrics {
+    pub fn name(&self) -> Option<&str> {
+        self.metadata.name.as_deref()
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.metadata.namespace.as_deref()
+    }
+
+    pub fn container(&self, name: &str) -> Option<&ContainerMetrics> {
+        self.containers.iter().find(|c| c.name == name)
+    }
+
+    /// Sum of the usage of `resource` by the containers, `None` if one of them
+    /// doesn't report it.
+    fn total(&self, resource: &str) -> Option<Quantity> {
+        self.containers
+            .iter()
+            .map(|container| quantity(&container.usage, resource))
+            .sum()
+    }
+
+    /// Sum of the CPU usage of the containers, as shown by `kubectl top pods`: it is
+    /// rounded once summed, not container by container.
+    pub fn cpu_millicores(&self) -> Option<i64> {
+        self.total("cpu").map(|cpu| cpu.as_millicores())
+    }
+
+    /// Sum of the memory usage of the containers.
+    pub fn memory_bytes(&self) -> Option<i64> {
+        self.total("memory").map(|memory| memory.as_bytes())
+    }
+}
+
+/// What `kubectl top --sort-by` sorts on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    Cpu,
+    Memory,
+}
+
+/// Sorts nodes by decreasing usage, like `kubectl top nodes --sort-by`. Nodes
+/// without the usage come last.
+pub fn sort_node_metrics(nodes: &mut [NodeMetrics], sort_by: SortBy) {
+    nodes.sort_by(|a, b| match sort_by {
+        SortBy::Cpu => by_decreasing_usage(a.cpu_millicores(), b.cpu_millicores()),
+        SortBy::Memory => by_decreasing_usage(a.memory_bytes(), b.memory_bytes()),
+    });
+}
+
+/// Sorts pods by decreasing usage, like `kubectl top pods --sort-by`. Pods without
+/// the usage come last.
+pub fn sort_pod_metrics(pods: &mut [PodMetrics], sort_by: SortBy) {
+    pods.sort_by(|a, b| match sort_by {
+        SortBy::Cpu => by_decreasing_usage(a.cpu_millicores(), b.cpu_millicores()),
+        SortBy::Memory => by_decreasing_usage(a.memory_bytes(), b.memory_bytes()),
+    });
+}
+
+fn by_decreasing_usage(a: Option<i64>, b: Option<i64>) -> Ordering {
+    // `None` is smaller than any value, so it comes last.
+    b.cmp(&a)
+}
+
+impl Kubernetes {
+    /// Usage of the nodes, like `kubectl top nodes`. Requires metrics-server.
+    pub fn list_node_metrics(
+        &self,
+        optional: ListOptional,
+    ) -> Result<Vec<NodeMetrics>, KubernetesError> {
+        Ok(self.api::<NodeMetrics>(None).list(optional)?.items)
+    }
+
+    /// Usage of the pods of `namespace`, or of every namespace if `None`, like
+    /// `kubectl top pods`. Requires metrics-server.
+    pub fn list_pod_metrics(
+        &self,
+        namespace: Option<&str>,
+        optional: ListOptional,
+    ) -> Result<Vec<PodMetrics>, KubernetesError> {
+        Ok(self.api::<PodMetrics>(namespace).list(optional)?.items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(cpu: &str, memory: &str) -> BTreeMap<String, ApiQuantity> {
+        let mut usage = BTreeMap::new();
+        usage.insert(String::from("cpu"), ApiQuantity(cpu.to_string()));
+        usage.insert(String::from("memory"), ApiQuantity(memory.to_string()));
+        usage
+    }
+
+    #[test]
+    fn test_usage_numbers() {
+        assert_eq!(cpu_millicores(&usage("250m", "0")), Some(250));
+        assert_eq!(cpu_millicores(&usage("2", "0")), Some(2000));
+        // metrics-server reports nanocores, `kubectl top` rounds up.
+        assert_eq!(cpu_millicores(&usage("1203442n", "0")), Some(2));
+        assert_eq!(memory_bytes(&usage("0", "24340Ki")), Some(24924160));
+        assert_eq!(memory_bytes(&usage("0", "1.5Gi")), Some(1610612736));
+        assert_eq!(memory_bytes(&usage("0", "2e3")), Some(2000));
+        assert_eq!(memory_bytes(&usage("0", "12Q")), None);
+        assert_eq!(memory_bytes(&BTreeMap::new()), None);
+    }
+
+    #[test]
+    fn test_sort_pods() {
+        let pod = |name: &str, cpus: &[&str]| PodMetrics {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            containers: cpus
+                .iter()
+                .map(|cpu| ContainerMetrics {
+                    name: name.to_string(),
+                    usage: usage(cpu, "1Mi"),
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let mut pods = vec![
+            pod("idle", &["1m"]),
+            pod("sidecars", &["100m", "150m"]),
+            pod("broken", &["oops"]),
+            pod("busy", &["1"]),
+        ];
+        sort_pod_metrics(&mut pods, SortBy::Cpu);
+        let names: Vec<_> = pods.iter().filter_map(PodMetrics::name).collect();
+        assert_eq!(names, vec!["busy", "sidecars", "idle", "broken"]);
+        assert_eq!(pods[1].cpu_millicores(), Some(250));
+        assert_eq!(pods[1].memory_bytes(), Some(2097152));
+    }
+
+    #[test]
+    fn test_pod_totals() {
+        let pod = |usages: Vec<BTreeMap<String, ApiQuantity>>| PodMetrics {
+            containers: usages
+                .into_iter()
+                .map(|usage| ContainerMetrics {
+                    name: String::from("app"),
+                    usage,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        // Rounding each container would give 2m + 2m.
+        let busy = pod(vec![usage("1203442n", "1Ki"), usage("1203442n", "1Ki")]);
+        assert_eq!(busy.cpu_millicores(), Some(3));
+        assert_eq!(busy.memory_bytes(), Some(2048));
+
+        let broken = pod(vec![usage("1m", "1Ki"), usage("oops", "1Ki")]);
+        assert_eq!(broken.cpu_millicores(), None);
+        assert_eq!(broken.memory_bytes(), Some(2048));
+        let missing = pod(vec![usage("1m", "1Ki"), BTreeMap::new()]);
+        assert_eq!(missing.cpu_millicores(), None);
+        assert_eq!(missing.memory_bytes(), None);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::config::KubeConfig;
+use crate::errors::KubernetesError;
+use openssl::nid::Nid;
+use openssl::x509::X509;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Prefix of the user name of kubelets, followed by the node name.
+const NODE_USER_PREFIX: &str = "system:node:";
+
+/// Where the name of the node we run on is looked for, in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeNameSources {
+    /// Environment variable set through the downward API, e.g. with
+    /// `valueFrom.fieldRef.fieldPath: spec.nodeName`.
+    pub env_var: String,
+    /// Kubeconfig files of the kubelet, whose user is `system:node:<name>`.
+    pub kubelet_kubeconfigs: Vec<PathBuf>,
+    /// Files holding the host name, which is the node name unless the kubelet
+    /// runs with `--hostname-override`.
+    pub hostname_files: Vec<PathBuf>,
+}
+
+impl Default for NodeNameSources {
+    fn default() -> Self {
+        NodeNameSources {
+            env_var: String::from("NODE_NAME"),
+            kubelet_kubeconfigs: vec![
+                PathBuf::from("/etc/kubernetes/kubelet.conf"),
+                PathBuf::from("/var/lib/kubelet/kubeconfig"),
+                PathBuf::from("/etc/kubernetes/kubelet-kubeconfig"),
+            ],
+            hostname_files: vec![
+                PathBuf::from("/proc/sys/kernel/hostname"),
+                PathBuf::from("/etc/hostname"),
+            ],
+        }
+    }
+}
+
+impl NodeNameSources {
+    /// First node name found in the sources.
+    pub fn detect(&self) -> Result<String, KubernetesError> {
+        if let Some(name) = env::var(&self.env_var)
+            .ok()
+            .filter(|n| !n.trim().is_empty())
+        {
+            return Ok(name.trim().to_string());
+        }
+        for path in &self.kubelet_kubeconfigs {
+            if let Some(name) = path.to_str().and_then(node_name_from_kubeconfig) {
+                return Ok(name);
+            }
+        }
+        for path in &self.hostname_files {
+            if let Ok(hostname) = fs::read_to_string(path) {
+                // The kubelet lower cases the host name to get the node name.
+                let hostname = hostname.trim().to_lowercase();
+                if !hostname.is_empty() {
+                    return Ok(hostname);
+                }
+            }
+        }
+        Err(KubernetesError::NodeNameError)
+    }
+}
+
+/// Name of the node we run on, from `NODE_NAME`, the kubelet kubeconfig or the
+/// host name.
+pub fn detect_node_name() -> Result<String, KubernetesError> {
+    NodeNameSources::default().detect()
+}
+
+/// Node name of a kubelet kubeconfig: the user of its current context is either
+/// named `system:node:<name>`, or has a client certificate with this common name.
+pub fn node_name_from_kubeconfig(path: &str) -> Option<String> {
+    let kubeconfig = KubeConfig::load(Some(path.to_string())).ok()?;
+    let context = kubeconfig
+        .contexts
+        .iter()
+        .find(|c| c.name == kubeconfig.current_context)?;
+    if let Some(name) = context.context.user.strip_prefix(NODE_USER_PREFIX) {
+        return Some(name.to_string());
+    }
+    let user = kubeconfig
+        .auth_infos
+        .iter()
+        .find(|u| u.name == context.context.user)?;
+    let certificate = user.auth_info.load_client_certificate().ok()?;
+    let certificate = X509::from_pem(&certificate).ok()?;
+    let common_name = certificate
+        .subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .next()?
+        .data()
+        .to_string()
+        .ok()?;
+    common_name.strip_prefix(NODE_USER_PREFIX).map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::X509NameBuilder;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn kubeconfig(user: &str, certificate: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "apiVersion: v1
+kind: Config
+clusters:
+- name: default-cluster
+  cluster:
+    server: https://10.0.0.1:6443
+contexts:
+- name: default-context
+  context:
+    cluster: default-cluster
+    user: {}
+current-context: default-context
+users:
+- name: {}
+  user:
+    client-certificate: {}
+",
+            user, user, certificate
+        )
+        .unwrap();
+        file
+    }
+
+    fn certificate(common_name: &str) -> NamedTempFile {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+            .unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&builder.build().to_pem().unwrap()).unwrap();
+        file
+    }
+
+    fn sources(kubeconfigs: Vec<PathBuf>, hostname_files: Vec<PathBuf>) -> NodeNameSources {
+        NodeNameSources {
+            env_var: String::from("K8S_SYNC_TEST_UNSET_NODE_NAME"),
+            kubelet_kubeconfigs: kubeconfigs,
+            hostname_files,
+        }
+    }
+
+    #[test]
+    fn test_node_name_from_user() {
+        let file = kubeconfig("system:node:worker-1", "/nonexistent.pem");
+        assert_eq!(
+            node_name_from_kubeconfig(file.path().to_str().unwrap()),
+            Some(String::from("worker-1"))
+        );
+    }
+
+    #[test]
+    fn test_node_name_from_certificate() {
+        let node = certificate("system:node:worker-2");
+        let file = kubeconfig("default-auth", node.path().to_str().unwrap());
+        assert_eq!(
+            node_name_from_kubeconfig(file.path().to_str().unwrap()),
+            Some(String::from("worker-2"))
+        );
+        let other = certificate("admin");
+        let file = kubeconfig("default-auth", other.path().to_str().unwrap());
+        assert_eq!(
+            node_name_from_kubeconfig(file.path().to_str().unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_detect_order() {
+        let kubelet = kubeconfig("system:node:worker-3", "/nonexistent.pem");
+        let mut hostname = NamedTempFile::new().unwrap();
+        writeln!(hostname, "Worker-4").unwrap();
+
+        let found = sources(
+            vec![PathBuf::from("/nonexistent"), kubelet.path().to_path_buf()],
+            vec![hostname.path().to_path_buf()],
+        )
+        .detect()
+        .unwrap();
+        assert_eq!(found, "worker-3");
+
+        let found = sources(vec![], vec![hostname.path().to_path_buf()])
+            .detect()
+            .unwrap();
+        assert_eq!(found, "worker-4");
+
+        assert!(sources(vec![], vec![]).detect().is_err());
+
+        let mut with_env = sources(vec![kubelet.path().to_path_buf()], vec![]);
+        with_env.env_var = String::from("K8S_SYNC_TEST_NODE_NAME");
+        env::set_var("K8S_SYNC_TEST_NODE_NAME", "worker-5");
+        assert_eq!(with_env.detect().unwrap(), "worker-5");
+    }
+}
@@ -0,0 +1,288 @@
+use crate::errors::KubernetesError;
+use k8s_openapi::api::core::v1::{PodSpec, Toleration};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A patch to send to the API server, in one of the formats it understands.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Patch {
+    /// RFC 6902 JSON Patch: a list of operations. See `JsonPatch` to build one.
+    Json(Value),
+    /// RFC 7386 JSON Merge Patch.
+    Merge(Value),
+    /// Kubernetes strategic merge patch, only supported by built-in types.
+    Strategic(Value),
+    /// Server-side apply: the fields the manager wants to own, with their values.
+    /// JSON being valid YAML, it is sent as is.
+    Apply(Value),
+}
+
+impl Patch {
+    /// Content type telling the API server how to interpret the patch.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Patch::Json(_) => "application/json-patch+json",
+            Patch::Merge(_) => "application/merge-patch+json",
+            Patch::Strategic(_) => "application/strategic-merge-patch+json",
+            Patch::Apply(_) => "application/apply-patch+yaml",
+        }
+    }
+
+    pub(crate) fn to_body(&self) -> Result<Vec<u8>, KubernetesError> {
+        let value = match self {
+            Patch::Json(value)
+            | Patch::Merge(value)
+            | Patch::Strategic(value)
+            | Patch::Apply(value) => value,
+        };
+        serde_json::to_vec(value).map_err(|source| KubernetesError::JsonError { source })
+    }
+
+    /// Merge patch setting labels, leaving the other ones untouched.
+    pub fn set_labels(labels: &[(&str, &str)]) -> Patch {
+        metadata_patch("labels", labels.iter().map(|(k, v)| (*k, Value::from(*v))))
+    }
+
+    /// Merge patch removing labels. Missing ones are ignored.
+    pub fn remove_labels(keys: &[&str]) -> Patch {
+        metadata_patch("labels", keys.iter().map(|k| (*k, Value::Null)))
+    }
+
+    /// Merge patch setting annotations, leaving the other ones untouched.
+    pub fn set_annotations(annotations: &[(&str, &str)]) -> Patch {
+        metadata_patch(
+            "annotations",
+            annotations.iter().map(|(k, v)| (*k, Value::from(*v))),
+        )
+    }
+
+    /// Merge patch removing annotations. Missing ones are ignored.
+    pub fn remove_annotations(keys: &[&str]) -> Patch {
+        metadata_patch("annotations", keys.iter().map(|k| (*k, Value::Null)))
+    }
+
+    /// JSON patch adding `toleration` to `pod_spec`, found at `pod_spec_path` in the
+    /// object: `/spec` for a pod, `/spec/template/spec` for a deployment.
+    ///
+    /// Tolerations are replaced as a whole by merge patches, so the patch appends to
+    /// the current list, after testing it didn't change since `pod_spec` was read.
+    /// Without tolerations, it tests the list is still unset: the API server treats
+    /// a missing field as `null` there. Does nothing if an identical toleration is already there.
+    pub fn add_toleration(
+        pod_spec_path: &str,
+        pod_spec: &PodSpec,
+        toleration: &Toleration,
+    ) -> Result<Patch, KubernetesError> {
+        let path = format!("{}/tolerations", pod_spec_path);
+        let patch = match &pod_spec.tolerations {
+            None => JsonPatch::new()
+                .test(&path, Value::Null)
+                .add(&path, Value::Array(vec![to_value(toleration)?])),
+            Some(current) if current.contains(toleration) => {
+                JsonPatch::new().test(&path, to_value(current)?)
+            }
+            Some(current) => JsonPatch::new()
+                .test(&path, to_value(current)?)
+                .add(&format!("{}/-", path), to_value(toleration)?),
+        };
+        Ok(patch.into())
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, KubernetesError> {
+    serde_json::to_value(value).map_err(|source| KubernetesError::JsonError { source })
+}
+
+fn metadata_patch<'a, I>(field: &str, entries: I) -> Patch
+where
+    I: Iterator<Item = (&'a str, Value)>,
+{
+    let entries: Map<String, Value> = entries.map(|(k, v)| (k.to_string(), v)).collect();
+    Patch::Merge(serde_json::json!({ "metadata": { field: entries } }))
+}
+
+/// One operation of an RFC 6902 JSON Patch. Paths are JSON pointers, see
+/// `escape_pointer` for keys containing `/` or `~`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOperation {
+    Add {
+        path: String,
+        value: Value,
+    },
+    Remove {
+        path: String,
+    },
+    Replace {
+        path: String,
+        value: Value,
+    },
+    Move {
+        from: String,
+        path: String,
+    },
+    Copy {
+        from: String,
+        path: String,
+    },
+    /// Fails the whole patch when the value at `path` differs.
+    Test {
+        path: String,
+        value: Value,
+    },
+}
+
+/// Builder of RFC 6902 JSON Patches. Operations are applied in order, and the
+/// patch fails as a whole if any of them does.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JsonPatch {
+    pub operations: Vec<JsonPatchOperation>,
+}
+
+impl JsonPatch {
+    pub fn new() -> Self {
+        JsonPatch::default()
+    }
+
+    pub fn add(mut self, path: &str, value: Value) -> Self {
+        self.operations.push(JsonPatchOperation::Add {
+            path: path.to_string(),
+            value,
+        });
+        self
+    }
+
+    pub fn remove(mut self, path: &str) -> Self {
+        self.operations.push(JsonPatchOperation::Remove {
+            path: path.to_string(),
+        });
+        self
+    }
+
+    pub fn replace(mut self, path: &str, value: Value) -> Self {
+        self.operations.push(JsonPatchOperation::Replace {
+            path: path.to_string(),
+            value,
+        });
+        self
+    }
+
+    pub fn move_from(mut self, from: &str, path: &str) -> Self {
+        self.operations.push(JsonPatchOperation::Move {
+            from: from.to_string(),
+            path: path.to_string(),
+        });
+        self
+    }
+
+    pub fn copy_from(mut self, from: &str, path: &str) -> Self {
+        self.operations.push(JsonPatchOperation::Copy {
+            from: from.to_string(),
+            path: path.to_string(),
+        });
+        self
+    }
+
+    pub fn test(mut self, path: &str, value: Value) -> Self {
+        self.operations.push(JsonPatchOperation::Test {
+            path: path.to_string(),
+            value,
+        });
+        self
+    }
+}
+
+impl From<JsonPatch> for Patch {
+    fn from(patch: JsonPatch) -> Self {
+        Patch::Json(
+            serde_json::to_value(patch.operations).expect("JSON patch operations serialize"),
+        )
+    }
+}
+
+/// Escapes a key to be used as a JSON pointer segment, e.g. the label
+/// `app.kubernetes.io/name` in `/metadata/labels/app.kubernetes.io~1name`.
+pub fn escape_pointer(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_patch_operations() {
+        let patch: Patch = JsonPatch::new()
+            .test("/metadata/resourceVersion", json!("42"))
+            .replace("/spec/replicas", json!(3))
+            .remove(&format!(
+                "/metadata/labels/{}",
+                escape_pointer("app.kubernetes.io/name")
+            ))
+            .move_from("/data/old", "/data/new")
+            .into();
+        assert_eq!(
+            patch,
+            Patch::Json(json!([
+                {"op": "test", "path": "/metadata/resourceVersion", "value": "42"},
+                {"op": "replace", "path": "/spec/replicas", "value": 3},
+                {"op": "remove", "path": "/metadata/labels/app.kubernetes.io~1name"},
+                {"op": "move", "from": "/data/old", "path": "/data/new"},
+            ]))
+        );
+        assert_eq!(patch.content_type(), "application/json-patch+json");
+    }
+
+    #[test]
+    fn test_metadata_patches() {
+        assert_eq!(
+            Patch::set_labels(&[("app", "web"), ("tier", "front")]),
+            Patch::Merge(json!({"metadata": {"labels": {"app": "web", "tier": "front"}}}))
+        );
+        assert_eq!(
+            Patch::remove_annotations(&["deployment.kubernetes.io/revision"]),
+            Patch::Merge(
+                json!({"metadata": {"annotations": {"deployment.kubernetes.io/revision": null}}})
+            )
+        );
+    }
+
+    #[test]
+    fn test_add_toleration() {
+        let toleration = Toleration {
+            key: Some(String::from("dedicated")),
+            operator: Some(String::from("Exists")),
+            effect: Some(String::from("NoSchedule")),
+            ..Default::default()
+        };
+        let mut spec = PodSpec::default();
+        assert_eq!(
+            Patch::add_toleration("/spec", &spec, &toleration).unwrap(),
+            Patch::Json(json!([
+                {"op": "test", "path": "/spec/tolerations", "value": null},
+                {
+                    "op": "add",
+                    "path": "/spec/tolerations",
+                    "value": [{"key": "dedicated", "operator": "Exists", "effect": "NoSchedule"}]
+                }
+            ]))
+        );
+
+        spec.tolerations = Some(vec![Toleration {
+            key: Some(String::from("gpu")),
+            ..Default::default()
+        }]);
+        assert_eq!(
+            Patch::add_toleration("/spec/template/spec", &spec, &toleration).unwrap(),
+            Patch::Json(json!([
+                {"op": "test", "path": "/spec/template/spec/tolerations", "value": [{"key": "gpu"}]},
+                {
+                    "op": "add",
+                    "path": "/spec/template/spec/tolerations/-",
+                    "value": {"key": "dedicated", "operator": "Exists", "effect": "NoSchedule"}
+                }
+            ]))
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use crate::dynamic::{encode_segment, with_query, GroupVersionResource};
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use crate::ws::{self, Endpoint};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const PROTOCOL: &str = "portforward.k8s.io";
+
+// Each forwarded port gets a pair of channels: data, then error. Only one port is
+// forwarded per connection, so these are always the first pair.
+const DATA: u8 = 0;
+const ERROR: u8 = 1;
+
+/// Wait after a failed accept, doubled on each consecutive failure.
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(5);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// State shared between the handle and the background threads.
+#[derive(Default)]
+struct Shared {
+    stopped: AtomicBool,
+    next_id: AtomicU64,
+    /// Local connections being relayed, to cut them on shutdown.
+    connections: Mutex<HashMap<u64, TcpStream>>,
+    errors: Mutex<Vec<String>>,
+}
+
+impl Shared {
+    fn report(&self, error: String) {
+        self.errors
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(error);
+    }
+}
+
+/// Ports of a pod forwarded to local TCP listeners, like `kubectl port-forward`.
+///
+/// Every local connection opens its own WebSocket to the API server. Dropping the
+/// handle closes the listeners and every connection still open.
+pub struct PortForward {
+    ports: Vec<(u16, u16)>,
+    addresses: Vec<SocketAddr>,
+    shared: Arc<Shared>,
+    listeners: Vec<JoinHandle<()>>,
+}
+
+impl PortForward {
+    /// Pairs of (local port, pod port), local ports being the ones actually bound.
+    pub fn ports(&self) -> &[(u16, u16)] {
+        &self.ports
+    }
+
+    /// Local port forwarded to `remote_port` of the pod.
+    pub fn local_port(&self, remote_port: u16) -> Option<u16> {
+        self.ports
+            .iter()
+            .find(|(_, remote)| *remote == remote_port)
+            .map(|(local, _)| *local)
+    }
+
+    /// Errors met so far: messages sent by the API server on error channels, e.g.
+    /// nothing listening on the pod port, and connections that couldn't be opened.
+    pub fn errors(&self) -> Vec<String> {
+        self.shared
+            .errors
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Stops forwarding. Same as dropping the handle.
+    pub fn stop(self) {}
+}
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        // Wake up the listeners blocked in accept, they check the flag first.
+        for address in &self.addresses {
+            let _ = TcpStream::connect(address);
+        }
+        for listener in self.listeners.drain(..) {
+            let _ = listener.join();
+        }
+        let connections = self
+            .shared
+            .connections
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        for stream in connections.values() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl std::fmt::Debug for PortForward {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PortForward")
+            .field("ports", &self.ports)
+            .finish()
+    }
+}
+
+impl Kubernetes {
+    /// Forwards ports of a pod to `127.0.0.1`. `ports` are pairs of (local port,
+    /// pod port); a local port of 0 picks a free one, see `PortForward::ports`.
+    pub fn port_forward(
+        &self,
+        namespace: &str,
+        pod: &str,
+        ports: &[(u16, u16)],
+    ) -> Result<PortForward, KubernetesError> {
+        let path = format!(
+            "{}/{}/portforward",
+            GroupVersionResource::new("", "v1", "pods").url_path(Some(namespace)),
+            encode_segment(pod)
+        );
+        let endpoint = Endpoint::of(self);
+        let shared = Arc::new(Shared::default());
+        let mut forward = PortForward {
+            ports: vec![],
+            addresses: vec![],
+            shared: shared.clone(),
+            listeners: vec![],
+        };
+        for (local, remote) in ports {
+            let listener = TcpListener::bind(("127.0.0.1", *local))
+                .map_err(|source| KubernetesError::IoError { source })?;
+            let address = listener
+                .local_addr()
+                .map_err(|source| KubernetesError::IoError { source })?;
+            forward.ports.push((address.port(), *remote));
+            forward.addresses.push(address);
+
+            let path = with_query(path.clone(), format!("ports={}", remote));
+            let endpoint = endpoint.clone();
+            let shared = shared.clone();
+            forward.listeners.push(thread::spawn(move || {
+                let mut backoff = MIN_ACCEPT_BACKOFF;
+                for stream in listener.incoming() {
+                    if shared.stopped.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            // e.g. out of file descriptors: retrying at once would spin.
+                            shared.report(format!("accept on port {}: {}", address.port(), err));
+                            thread::sleep(backoff);
+                            backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                            continue;
+                        }
+                    };
+                    backoff = MIN_ACCEPT_BACKOFF;
+                    // Connecting to the API server takes a round trip, don't make the
+                    // next local connections wait for it.
+                    let (endpoint, path, shared) = (endpoint.clone(), path.clone(), shared.clone());
+                    thread::spawn(move || {
+                        if let Err(err) = relay(&endpoint, &path, stream, &shared) {
+                            shared.report(err.to_string());
+                        }
+                    });
+                }
+            }));
+        }
+        Ok(forward)
+    }
+}
+
+/// Relays a local connection through a new WebSocket, in background threads.
+fn relay(
+    endpoint: &Endpoint,
+    path: &str,
+    stream: TcpStream,
+    shared: &Arc<Shared>,
+) -> Result<(), KubernetesError> {
+    let io_error = |source| KubernetesError::IoError { source };
+    let (socket, _) = ws::connect(endpoint, path, &[PROTOCOL])?;
+    let id = shared.next_id.fetch_add(1, Ordering::SeqCst);
+    let mut connections = shared.connections.lock().unwrap_or_else(|e| e.into_inner());
+    // The handle was dropped while connecting: it won't cut this connection.
+    if shared.stopped.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    connections.insert(id, stream.try_clone().map_err(io_error)?);
+    drop(connections);
+
+    let mut local_writer = stream.try_clone().map_err(io_error)?;
+    let from_pod = shared.clone();
+    // The first frame of each channel only holds the port number.
+    let mut prefixed = [true, true];
+    let (outgoing, outgoing_receiver) = mpsc::channel();
+    let connection = ws::spawn(socket, outgoing_receiver, move |channel, data| {
+        if channel > ERROR {
+            return;
+        }
+        let data = if prefixed[channel as usize] {
+            prefixed[channel as usize] = false;
+            data.get(2..).unwrap_or_default()
+        } else {
+            data
+        };
+        if data.is_empty() {
+            return;
+        }
+        if channel == DATA {
+            let _ = local_writer.write_all(data);
+        } else {
+            from_pod.report(String::from_utf8_lossy(data).into_owned());
+        }
+    })?;
+
+    let mut local_reader = stream.try_clone().map_err(io_error)?;
+    thread::spawn(move || {
+        let mut buf = [0u8; 16 * 1024];
+        loop {
+            match local_reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => {
+                    let mut frame = Vec::with_capacity(read + 1);
+                    frame.push(DATA);
+                    frame.extend_from_slice(&buf[..read]);
+                    if outgoing.send(frame).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        // Dropping the sender closes the WebSocket, if the API server didn't already.
+    });
+
+    let shared = shared.clone();
+    thread::spawn(move || {
+        if let Ok(Err(err)) = connection.join() {
+            shared.report(err.to_string());
+        }
+        // Unblocks the thread reading the local connection.
+        let _ = stream.shutdown(Shutdown::Both);
+        shared
+            .connections
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+    });
+    Ok(())
+}
@@ -0,0 +1,498 @@
+use crate::errors::KubernetesError;
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity as ApiQuantity;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+const NANOS: i128 = 1_000_000_000;
+
+const DECIMAL_SUFFIXES: [(&str, i32); 10] = [
+    ("n", -9),
+    ("u", -6),
+    ("m", -3),
+    ("", 0),
+    ("k", 3),
+    ("M", 6),
+    ("G", 9),
+    ("T", 12),
+    ("P", 15),
+    ("E", 18),
+];
+
+const BINARY_SUFFIXES: [&str; 6] = ["Ki", "Mi", "Gi", "Ti", "Pi", "Ei"];
+
+/// How a quantity is written, from the suffix it was parsed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// `n`, `u`, `m`, `k`, `M`, `G`... or no suffix.
+    DecimalSI,
+    /// `Ki`, `Mi`, `Gi`...
+    BinarySI,
+    /// `e3`, `E-6`...
+    DecimalExponent,
+}
+
+/// A resource quantity, e.g. `100m` of CPU or `1.5Gi` of memory.
+///
+/// Values are exact down to the nano unit, the precision the API server keeps:
+/// finer values are rounded up (away from zero), like the API server does.
+/// Quantities compare by value, whatever their format: `1k` equals `1000`.
+///
+/// ```
+/// # use k8s_sync::quantity::Quantity;
+/// let requests: Quantity = ["100m", "250m", "1"]
+///     .iter()
+///     .map(|q| q.parse::<Quantity>().unwrap())
+///     .sum();
+/// assert_eq!(requests.to_string(), "1350m");
+/// assert_eq!(requests.as_millicores(), 1350);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Quantity {
+    nanos: i128,
+    format: Format,
+}
+
+impl Default for Quantity {
+    fn default() -> Self {
+        Quantity {
+            nanos: 0,
+            format: Format::DecimalSI,
+        }
+    }
+}
+
+impl Quantity {
+    pub fn from_millicores(millicores: i64) -> Self {
+        Quantity {
+            nanos: millicores as i128 * 1_000_000,
+            format: Format::DecimalSI,
+        }
+    }
+
+    pub fn from_bytes(bytes: i64) -> Self {
+        Quantity {
+            nanos: bytes as i128 * NANOS,
+            format: Format::BinarySI,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The same value, written with `format` when possible (see `Display`).
+    pub fn with_format(self, format: Format) -> Self {
+        Quantity { format, ..self }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.nanos == 0
+    }
+
+    /// Value in nano units, e.g. nanocores.
+    pub fn as_nanos(&self) -> i128 {
+        self.nanos
+    }
+
+    /// Value in thousandths, rounded away from zero like `MilliValue` in Go, e.g.
+    /// millicores for a CPU quantity.
+    pub fn as_millicores(&self) -> i64 {
+        saturate(div_away_from_zero(self.nanos, 1_000_000))
+    }
+
+    /// Value rounded away from zero to an integer like `Value` in Go, e.g. bytes for
+    /// a memory quantity.
+    pub fn as_bytes(&self) -> i64 {
+        saturate(div_away_from_zero(self.nanos, NANOS))
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.nanos as f64 / NANOS as f64
+    }
+
+    /// Format of the result of `self` and `other` combined: the one of `self`, or of
+    /// `other` when `self` is zero, so that sums of memory quantities stay binary.
+    fn combined_format(&self, other: &Quantity) -> Format {
+        if self.is_zero() {
+            other.format
+        } else {
+            self.format
+        }
+    }
+
+    fn parse(quantity: &str) -> Result<Self, String> {
+        let (negative, unsigned) = match quantity.as_bytes().first() {
+            Some(b'-') => (true, &quantity[1..]),
+            Some(b'+') => (false, &quantity[1..]),
+            _ => (false, quantity),
+        };
+        let number_end = unsigned
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(unsigned.len());
+        let (number, suffix) = unsigned.split_at(number_end);
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(String::from("missing number"));
+        }
+        if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(String::from("invalid number"));
+        }
+        // Trimmed so that zeros don't overflow the mantissa.
+        let fraction = fraction.trim_end_matches('0');
+        let digits = format!("{}{}", integer, fraction);
+        let digits = digits.trim_start_matches('0');
+        let mantissa: i128 = if digits.is_empty() {
+            0
+        } else {
+            digits.parse().map_err(|_| String::from("out of range"))?
+        };
+
+        // The value is `mantissa * 1024^binary * 10^exponent`.
+        let mut exponent = -(fraction.len() as i32);
+        let mut binary = 0;
+        let format = if let Some(position) = BINARY_SUFFIXES.iter().position(|s| *s == suffix) {
+            binary = position as u32 + 1;
+            Format::BinarySI
+        } else if let Some((_, decimal)) = DECIMAL_SUFFIXES.iter().find(|(s, _)| *s == suffix) {
+            exponent += decimal;
+            Format::DecimalSI
+        } else {
+            let explicit = suffix
+                .strip_prefix(|c| c == 'e' || c == 'E')
+                .and_then(|e| e.parse::<i32>().ok())
+                .ok_or_else(|| format!("unknown suffix {:?}", suffix))?;
+            exponent = exponent.saturating_add(explicit);
+            Format::DecimalExponent
+        };
+
+        let mut nanos = mantissa
+            .checked_mul(1 << (10 * binary))
+            .ok_or_else(|| String::from("out of range"))?;
+        exponent = exponent.saturating_add(9);
+        if nanos != 0 {
+            nanos = if exponent >= 0 {
+                10i128
+                    .checked_pow(exponent as u32)
+                    .and_then(|scale| nanos.checked_mul(scale))
+                    .ok_or_else(|| String::from("out of range"))?
+            } else {
+                match 10i128.checked_pow(exponent.unsigned_abs()) {
+                    Some(scale) => div_away_from_zero(nanos, scale),
+                    // Less than a nano unit, rounded up.
+                    None => 1,
+                }
+            };
+        }
+        Ok(Quantity {
+            nanos: if negative { -nanos } else { nanos },
+            format,
+        })
+    }
+}
+
+fn div_away_from_zero(value: i128, divisor: i128) -> i128 {
+    let quotient = value / divisor;
+    if value % divisor != 0 {
+        quotient + value.signum()
+    } else {
+        quotient
+    }
+}
+
+fn saturate(value: i128) -> i64 {
+    value.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+impl FromStr for Quantity {
+    type Err = KubernetesError;
+
+    /// Parses a quantity, e.g. `100m`, `1.5Gi`, `2e3` or `500Mi`.
+    fn from_str(quantity: &str) -> Result<Self, Self::Err> {
+        Quantity::parse(quantity).map_err(|message| KubernetesError::QuantityParseError {
+            quantity: quantity.to_string(),
+            message,
+        })
+    }
+}
+
+impl TryFrom<&ApiQuantity> for Quantity {
+    type Error = KubernetesError;
+
+    fn try_from(quantity: &ApiQuantity) -> Result<Self, Self::Error> {
+        quantity.0.parse()
+    }
+}
+
+impl From<Quantity> for ApiQuantity {
+    fn from(quantity: Quantity) -> Self {
+        ApiQuantity(quantity.to_string())
+    }
+}
+
+/// Canonical form, as written by the API server: the largest suffix of the format
+/// leaving an integer, e.g. `1.5Gi` is `1536Mi` and `0.5` is `500m`. Binary values
+/// below 1024 or with a fractional part are written with a decimal suffix.
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.nanos == 0 {
+            return f.write_str("0");
+        }
+        let binary = self.format == Format::BinarySI
+            && self.nanos.unsigned_abs() >= 1024 * NANOS as u128
+            && self.nanos % NANOS == 0;
+        if binary {
+            let mut value = self.nanos / NANOS;
+            let mut suffix = "";
+            for next in BINARY_SUFFIXES.iter() {
+                if value % 1024 != 0 {
+                    break;
+                }
+                value /= 1024;
+                suffix = next;
+            }
+            return write!(f, "{}{}", value, suffix);
+        }
+        let mut mantissa = self.nanos;
+        let mut exponent = -9;
+        while exponent < 18 && mantissa % 1000 == 0 {
+            mantissa /= 1000;
+            exponent += 3;
+        }
+        if self.format == Format::DecimalExponent {
+            if exponent == 0 {
+                write!(f, "{}", mantissa)
+            } else {
+                write!(f, "{}e{}", mantissa, exponent)
+            }
+        } else {
+            let (suffix, _) = DECIMAL_SUFFIXES
+                .iter()
+                .find(|(_, e)| *e == exponent)
+                .expect("exponent is a multiple of 3 between -9 and 18");
+            write!(f, "{}{}", mantissa, suffix)
+        }
+    }
+}
+
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Self) -> bool {
+        self.nanos == other.nanos
+    }
+}
+
+impl Eq for Quantity {}
+
+impl Hash for Quantity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.nanos.hash(state);
+    }
+}
+
+impl PartialOrd for Quantity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Quantity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.nanos.cmp(&other.nanos)
+    }
+}
+
+/// The result keeps the format of the left operand, or of the right one when the
+/// left one is zero. Like the other operators, it saturates instead of overflowing.
+impl Add for Quantity {
+    type Output = Quantity;
+
+    fn add(self, other: Quantity) -> Quantity {
+        Quantity {
+            nanos: self.nanos.saturating_add(other.nanos),
+            format: self.combined_format(&other),
+        }
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, other: Quantity) -> Quantity {
+        Quantity {
+            nanos: self.nanos.saturating_sub(other.nanos),
+            format: self.combined_format(&other),
+        }
+    }
+}
+
+impl Neg for Quantity {
+    type Output = Quantity;
+
+    fn neg(self) -> Quantity {
+        Quantity {
+            nanos: self.nanos.saturating_neg(),
+            ..self
+        }
+    }
+}
+
+impl AddAssign for Quantity {
+    fn add_assign(&mut self, other: Quantity) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Quantity {
+    fn sub_assign(&mut self, other: Quantity) {
+        *self = *self - other;
+    }
+}
+
+impl Sum for Quantity {
+    fn sum<I: Iterator<Item = Quantity>>(iter: I) -> Self {
+        iter.fold(Quantity::default(), Add::add)
+    }
+}
+
+impl<'q> Sum<&'q Quantity> for Quantity {
+    fn sum<I: Iterator<Item = &'q Quantity>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let quantity = String::deserialize(deserializer)?;
+        quantity.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q(quantity: &str) -> Quantity {
+        quantity.parse().unwrap()
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        for (parsed, canonical) in [
+            ("0", "0"),
+            ("0Mi", "0"),
+            ("100m", "100m"),
+            ("0.1", "100m"),
+            ("1.5", "1500m"),
+            ("2000m", "2"),
+            ("1000", "1k"),
+            ("1500", "1500"),
+            ("1.5G", "1500M"),
+            ("+1n", "1n"),
+            ("0.1n", "1n"),
+            ("-100m", "-100m"),
+            ("-0.1n", "-1n"),
+            ("1Ki", "1Ki"),
+            ("1024Ki", "1Mi"),
+            ("1.5Gi", "1536Mi"),
+            ("0.5Gi", "512Mi"),
+            ("1500Ki", "1500Ki"),
+            ("0.1Ki", "102400m"),
+            ("512", "512"),
+            ("12e6", "12e6"),
+            ("1E3", "1e3"),
+            ("1.5e3", "1500"),
+            ("5e-3", "5e-3"),
+            ("1e-20", "1e-9"),
+            (".5", "500m"),
+            ("5.", "5"),
+            ("000123.4500", "123450m"),
+            ("1E", "1E"),
+        ]
+        .iter()
+        {
+            assert_eq!(q(parsed).to_string(), *canonical, "{}", parsed);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        for invalid in [
+            "", "m", "abc", "1.2.3", "1Qi", "1 Gi", "--1", "e3", "1e", "1e999",
+        ]
+        .iter()
+        {
+            match invalid.parse::<Quantity>() {
+                Err(KubernetesError::QuantityParseError { quantity, .. }) => {
+                    assert_eq!(quantity, *invalid)
+                }
+                other => panic!("{:?} parsed as {:?}", invalid, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(q("250m").as_millicores(), 250);
+        assert_eq!(q("2").as_millicores(), 2000);
+        assert_eq!(q("1203442n").as_millicores(), 2);
+        assert_eq!(q("1.5Gi").as_bytes(), 1610612736);
+        assert_eq!(q("100m").as_bytes(), 1);
+        assert_eq!(q("-1.5").as_bytes(), -2);
+        assert_eq!(q("-1203442n").as_millicores(), -2);
+        assert_eq!(q("1Ei").as_bytes(), 1 << 60);
+        assert_eq!(q("100E").as_bytes(), i64::MAX);
+        assert_eq!(q("1.5").as_f64(), 1.5);
+        assert_eq!(Quantity::from_millicores(1500).to_string(), "1500m");
+        assert_eq!(Quantity::from_bytes(2 << 30).to_string(), "2Gi");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let cpu: Quantity = ["100m", "250m", "1"].iter().map(|s| q(s)).sum();
+        assert_eq!(cpu.to_string(), "1350m");
+        let memory: Quantity = [q("512Mi"), q("0.5Gi")].iter().sum();
+        assert_eq!(memory.to_string(), "1Gi");
+        assert_eq!((q("1Gi") - q("1.5Gi")).to_string(), "-512Mi");
+        assert_eq!((q("0") - q("1Mi")).to_string(), "-1Mi");
+        let mut left = q("1");
+        left -= q("1");
+        assert!(left.is_zero());
+        left += q("300m");
+        assert_eq!(left, q("0.3"));
+
+        assert!(q("1Gi") > q("1G"));
+        assert_eq!(q("1k"), q("1000"));
+
+        // Saturates instead of overflowing.
+        let huge = q("1e29") + q("1e29");
+        assert_eq!(huge.as_nanos(), i128::MAX);
+        assert_eq!(huge.as_bytes(), i64::MAX);
+        assert_eq!((-huge - q("1e29")).as_nanos(), i128::MIN);
+        assert_eq!((-(-huge - huge)).as_nanos(), i128::MAX);
+        assert_eq!(q("1e3").max(q("999")).to_string(), "1e3");
+        assert_eq!(
+            q("1Mi").with_format(Format::DecimalSI).to_string(),
+            "1048576"
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        let quantity: Quantity = serde_json::from_str("\"1.5Gi\"").unwrap();
+        assert_eq!(serde_json::to_string(&quantity).unwrap(), "\"1536Mi\"");
+        assert!(serde_json::from_str::<Quantity>("\"1.5Qi\"").is_err());
+        let api = ApiQuantity(String::from("100m"));
+        assert_eq!(ApiQuantity::from(Quantity::try_from(&api).unwrap()), api);
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Token bucket limiting the rate of requests sent to the API server, with the
+/// same semantics as client-go's `QPS` and `Burst` settings: up to `burst` requests
+/// can be sent at once, then the bucket refills at `qps` tokens per second.
+///
+/// A single limiter is shared by every thread using the client it is attached to,
+/// and can be attached to several clients through an `Arc`.
+pub struct RateLimiter {
+    qps: f64,
+    burst: u32,
+    bucket: Mutex<Bucket>,
+    observer: Option<Box<dyn Fn(Duration) + Send + Sync>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+    throttled: u64,
+    total_wait: Duration,
+}
+
+impl RateLimiter {
+    /// client-go's defaults: 5 requests per second, bursts of 10.
+    pub const DEFAULT_QPS: f64 = 5.0;
+    pub const DEFAULT_BURST: u32 = 10;
+
+    pub fn new(qps: f64, burst: u32) -> Self {
+        let burst = burst.max(1);
+        RateLimiter {
+            qps,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                last_refill: Instant::now(),
+                throttled: 0,
+                total_wait: Duration::from_secs(0),
+            }),
+            observer: None,
+        }
+    }
+
+    /// Calls `observer` with the time each request waited for a token, including
+    /// requests that did not wait at all.
+    pub fn with_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    pub fn qps(&self) -> f64 {
+        self.qps
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Blocks until a token is available and returns how long the caller waited.
+    pub fn acquire(&self) -> Duration {
+        let wait = self.reserve();
+        if wait > Duration::from_secs(0) {
+            thread::sleep(wait);
+        }
+        if let Some(observer) = &self.observer {
+            observer(wait);
+        }
+        wait
+    }
+
+    /// Takes a token, possibly borrowing it from the future, and returns the time
+    /// to wait until that token is actually available.
+    fn reserve(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        if self.qps <= 0.0 {
+            return Duration::from_secs(0);
+        }
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.qps).min(self.burst as f64);
+        bucket.last_refill = now;
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            return Duration::from_secs(0);
+        }
+        let wait = Duration::from_secs_f64(-bucket.tokens / self.qps);
+        bucket.throttled += 1;
+        bucket.total_wait += wait;
+        wait
+    }
+
+    /// Number of requests that had to wait for a token.
+    pub fn throttled_requests(&self) -> u64 {
+        self.bucket
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .throttled
+    }
+
+    /// Cumulated time requests spent waiting for a token.
+    pub fn total_wait(&self) -> Duration {
+        self.bucket
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .total_wait
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter::new(Self::DEFAULT_QPS, Self::DEFAULT_BURST)
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("qps", &self.qps)
+            .field("burst", &self.burst)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_burst_then_throttle() {
+        let limiter = RateLimiter::new(20.0, 3);
+        for _ in 0..3 {
+            assert_eq!(limiter.acquire(), Duration::from_secs(0));
+        }
+        let wait = limiter.acquire();
+        assert!(wait > Duration::from_millis(30) && wait <= Duration::from_millis(50));
+        assert_eq!(limiter.throttled_requests(), 1);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let observed = Arc::new(AtomicUsize::new(0));
+        let counter = observed.clone();
+        let limiter = Arc::new(RateLimiter::new(50.0, 2).with_observer(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        let start = Instant::now();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                thread::spawn(move || {
+                    limiter.acquire();
+                    limiter.acquire();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        // 8 requests, 2 served by the burst, 6 at 50 per second.
+        assert!(start.elapsed() >= Duration::from_millis(110));
+        assert_eq!(observed.load(Ordering::SeqCst), 8);
+        assert_eq!(limiter.throttled_requests(), 6);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::errors::KubernetesError;
+use http::{Method, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// RetryPolicy describes how `Kubernetes` retries requests failing for transient reasons:
+/// network errors, `429 Too Many Requests` (API Priority and Fairness) and the 5xx
+/// answers the API server sends during upgrades or etcd leader elections.
+///
+/// Waits grow exponentially from `initial_backoff` up to `max_backoff`, with a random
+/// jitter. A `Retry-After` header (or `retryAfterSeconds` in the returned Status)
+/// is a minimum: the request is never sent again sooner than the server asked. When
+/// it asks for more than `max_retry_after`, the request fails instead.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Fraction of the backoff randomly added or removed, between `0.0` and `1.0`.
+    pub jitter: f64,
+    /// Also retry verbs that are not idempotent (`POST`, `PATCH`).
+    pub retry_non_idempotent: bool,
+    /// Also retry requests that timed out. Each attempt can take the whole request
+    /// timeout, so the call may last `max_attempts` times longer than it.
+    pub retry_timeouts: bool,
+    /// Longest `Retry-After` waited for. Beyond it, the error is returned at once.
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+            retry_non_idempotent: false,
+            retry_timeouts: false,
+            max_retry_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a request sent with `verb` that failed with `error` on attempt
+    /// number `attempt` (starting at 1) should be sent again. See `accepts_retry_after`
+    /// for the delay requested by the server.
+    pub fn should_retry(&self, verb: &Method, error: &KubernetesError, attempt: u32) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        if !self.retry_non_idempotent && !is_idempotent(verb) {
+            return false;
+        }
+        match error {
+            KubernetesError::HttpClientRequestError { source } => source.is_network(),
+            KubernetesError::TimeoutError { .. } => self.retry_timeouts,
+            KubernetesError::ApiError { status_code, .. } => is_retryable_status(*status_code),
+            _ => false,
+        }
+    }
+
+    /// Whether waiting `retry_after`, as requested by the server, is acceptable.
+    pub fn accepts_retry_after(&self, retry_after: Option<Duration>) -> bool {
+        retry_after.is_none_or(|retry_after| retry_after <= self.max_retry_after)
+    }
+
+    /// Time to wait before attempt number `attempt + 1`. `retry_after` is the delay
+    /// requested by the server, if any: the wait is never shorter.
+    pub fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        self.computed_backoff(attempt)
+            .max(retry_after.unwrap_or_default())
+    }
+
+    fn computed_backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1) as i32;
+        let base = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let base = base.min(self.max_backoff.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 + jitter * (2.0 * random_unit() - 1.0);
+        Duration::from_secs_f64((base * factor).max(0.0))
+    }
+}
+
+fn is_idempotent(verb: &Method) -> bool {
+    matches!(
+        *verb,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+    )
+}
+
+fn is_retryable_status(status_code: StatusCode) -> bool {
+    matches!(
+        status_code,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Parses a `Retry-After` header value. Only the delay-seconds form is used by the API server.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// A random number in `[0, 1)`, good enough to spread retries of concurrent clients.
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(status_code: StatusCode) -> KubernetesError {
+        KubernetesError::ApiError {
+            verb: Method::GET,
+            url: String::from("http://localhost/api"),
+            status_code,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::default();
+        let unavailable = api_error(StatusCode::SERVICE_UNAVAILABLE);
+        assert!(policy.should_retry(&Method::GET, &unavailable, 1));
+        assert!(!policy.should_retry(&Method::GET, &unavailable, 4));
+        assert!(!policy.should_retry(&Method::POST, &unavailable, 1));
+        assert!(!policy.should_retry(&Method::GET, &api_error(StatusCode::NOT_FOUND), 1));
+
+        let policy = RetryPolicy {
+            retry_non_idempotent: true,
+            ..Default::default()
+        };
+        assert!(policy.should_retry(&Method::POST, &unavailable, 1));
+        assert!(!RetryPolicy::none().should_retry(&Method::GET, &unavailable, 1));
+    }
+
+    #[test]
+    fn test_should_retry_timeouts() {
+        let timeout = KubernetesError::TimeoutError {
+            verb: Method::GET,
+            url: String::from("http://localhost/api"),
+            source: isahc::Error::from(std::io::Error::from(std::io::ErrorKind::TimedOut)),
+        };
+        assert!(!RetryPolicy::default().should_retry(&Method::GET, &timeout, 1));
+        let policy = RetryPolicy {
+            retry_timeouts: true,
+            ..Default::default()
+        };
+        assert!(policy.should_retry(&Method::GET, &timeout, 1));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1, None), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3, None), Duration::from_millis(800));
+        assert_eq!(policy.backoff(20, None), Duration::from_secs(10));
+        assert_eq!(
+            policy.backoff(1, Some(Duration::from_secs(3))),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            policy.backoff(1, Some(Duration::from_secs(60))),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            policy.backoff(3, Some(Duration::from_millis(100))),
+            Duration::from_millis(800)
+        );
+        assert!(policy.accepts_retry_after(None));
+        assert!(policy.accepts_retry_after(Some(Duration::from_secs(60))));
+        assert!(!policy.accepts_retry_after(Some(Duration::from_secs(61))));
+
+        let policy = RetryPolicy::default();
+        for _ in 0..100 {
+            let wait = policy.backoff(2, None);
+            assert!(wait >= Duration::from_millis(320) && wait <= Duration::from_millis(480));
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after(" 5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
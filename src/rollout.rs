@@ -0,0 +1,520 @@
+use crate::dynamic::Api;
+use crate::errors::KubernetesError;
+use crate::patch::{JsonPatch, Patch};
+use crate::wait::wait_until;
+use chrono::{SecondsFormat, Utc};
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet};
+use k8s_openapi::api::core::v1::PodTemplateSpec;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use k8s_openapi::ListOptional;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Pod template annotation changed by `kubectl rollout restart`.
+pub const RESTARTED_AT_ANNOTATION: &str = "kubectl.kubernetes.io/restartedAt";
+
+/// Revision of a Deployment, set on its ReplicaSets.
+pub const REVISION_ANNOTATION: &str = "deployment.kubernetes.io/revision";
+
+/// Reason of the `Progressing` condition of a Deployment stuck for longer than
+/// its `progressDeadlineSeconds`.
+const PROGRESS_DEADLINE_EXCEEDED: &str = "ProgressDeadlineExceeded";
+
+/// ReplicaSet annotations that are not copied to the Deployment on undo.
+const UNDO_SKIPPED_ANNOTATIONS: [&str; 6] = [
+    "kubectl.kubernetes.io/last-applied-configuration",
+    REVISION_ANNOTATION,
+    "deployment.kubernetes.io/revision-history",
+    "deployment.kubernetes.io/desired-replicas",
+    "deployment.kubernetes.io/max-replicas",
+    "deprecated.deployment.rollback.to",
+];
+
+/// Where a rollout stands, as reported by `kubectl rollout status`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RolloutProgress {
+    pub desired: i32,
+    pub updated: i32,
+    pub ready: i32,
+    pub available: i32,
+    /// Whether the controller has seen the last change to the spec. Counts are
+    /// stale until then.
+    pub observed: bool,
+    pub done: bool,
+    /// Same message as kubectl, e.g. `2 of 3 updated replicas are available`.
+    pub message: String,
+}
+
+/// Workloads whose rollout can be followed.
+pub trait Rollout {
+    /// Computes the progress of the rollout from the object. Fails when it can't
+    /// complete: progress deadline exceeded, or no rolling update strategy.
+    fn rollout_progress(&self) -> Result<RolloutProgress, KubernetesError>;
+}
+
+fn observed(generation: Option<i64>, observed_generation: Option<i64>) -> bool {
+    generation.unwrap_or_default() <= observed_generation.unwrap_or_default()
+}
+
+fn rollout_error(name: &Option<String>, message: String) -> KubernetesError {
+    KubernetesError::RolloutError {
+        name: name.clone().unwrap_or_default(),
+        message,
+    }
+}
+
+impl Rollout for Deployment {
+    fn rollout_progress(&self) -> Result<RolloutProgress, KubernetesError> {
+        let name = &self.metadata.name;
+        let status = self.status.clone().unwrap_or_default();
+        let mut progress = RolloutProgress {
+            desired: self.spec.as_ref().and_then(|s| s.replicas).unwrap_or(1),
+            updated: status.updated_replicas.unwrap_or_default(),
+            ready: status.ready_replicas.unwrap_or_default(),
+            available: status.available_replicas.unwrap_or_default(),
+            observed: observed(self.metadata.generation, status.observed_generation),
+            ..Default::default()
+        };
+        let replicas = status.replicas.unwrap_or_default();
+        if !progress.observed {
+            progress.message = String::from("Waiting for deployment spec update to be observed...");
+            return Ok(progress);
+        }
+        let progressing = status
+            .conditions
+            .iter()
+            .flatten()
+            .find(|c| c.type_ == "Progressing");
+        if progressing.and_then(|c| c.reason.as_deref()) == Some(PROGRESS_DEADLINE_EXCEEDED) {
+            return Err(rollout_error(
+                name,
+                String::from("exceeded its progress deadline"),
+            ));
+        }
+        let waiting = |detail: String| {
+            format!(
+                "Waiting for deployment {:?} rollout to finish: {}",
+                name.as_deref().unwrap_or_default(),
+                detail
+            )
+        };
+        progress.message = if progress.updated < progress.desired {
+            waiting(format!(
+                "{} out of {} new replicas have been updated...",
+                progress.updated, progress.desired
+            ))
+        } else if replicas > progress.updated {
+            waiting(format!(
+                "{} old replicas are pending termination...",
+                replicas - progress.updated
+            ))
+        } else if progress.ready < progress.updated {
+            waiting(format!(
+                "{} of {} updated replicas are ready...",
+                progress.ready, progress.updated
+            ))
+        } else if progress.available < progress.updated {
+            waiting(format!(
+                "{} of {} updated replicas are available...",
+                progress.available, progress.updated
+            ))
+        } else {
+            progress.done = true;
+            format!(
+                "deployment {:?} successfully rolled out",
+                name.as_deref().unwrap_or_default()
+            )
+        };
+        Ok(progress)
+    }
+}
+
+impl Rollout for StatefulSet {
+    fn rollout_progress(&self) -> Result<RolloutProgress, KubernetesError> {
+        let name = &self.metadata.name;
+        let spec = self.spec.clone().unwrap_or_default();
+        let strategy = spec.update_strategy.unwrap_or_default();
+        if strategy.type_.as_deref().unwrap_or("RollingUpdate") != "RollingUpdate" {
+            return Err(rollout_error(
+                name,
+                String::from("rollout status is only available for the RollingUpdate strategy"),
+            ));
+        }
+        let status = self.status.clone().unwrap_or_default();
+        let mut progress = RolloutProgress {
+            desired: spec.replicas.unwrap_or(1),
+            updated: status.updated_replicas.unwrap_or_default(),
+            ready: status.ready_replicas.unwrap_or_default(),
+            available: status.ready_replicas.unwrap_or_default(),
+            observed: observed(self.metadata.generation, status.observed_generation),
+            ..Default::default()
+        };
+        let partition = strategy
+            .rolling_update
+            .and_then(|r| r.partition)
+            .unwrap_or_default();
+        let update_revision = status.update_revision.unwrap_or_default();
+        progress.message = if !progress.observed {
+            String::from("Waiting for statefulset spec update to be observed...")
+        } else if progress.ready < progress.desired {
+            format!(
+                "Waiting for {} pods to be ready...",
+                progress.desired - progress.ready
+            )
+        } else if partition > 0 {
+            let expected = progress.desired - partition;
+            if progress.updated < expected {
+                format!(
+                    "Waiting for partitioned roll out to finish: {} out of {} new pods have been updated...",
+                    progress.updated, expected
+                )
+            } else {
+                progress.done = true;
+                format!(
+                    "partitioned roll out complete: {} new pods have been updated...",
+                    progress.updated
+                )
+            }
+        } else if Some(&update_revision) != status.current_revision.as_ref() {
+            format!(
+                "waiting for statefulset rolling update to complete {} pods at revision {}...",
+                progress.updated, update_revision
+            )
+        } else {
+            progress.done = true;
+            format!(
+                "statefulset rolling update complete {} pods at revision {}...",
+                status.current_replicas.unwrap_or_default(),
+                update_revision
+            )
+        };
+        Ok(progress)
+    }
+}
+
+impl Rollout for DaemonSet {
+    fn rollout_progress(&self) -> Result<RolloutProgress, KubernetesError> {
+        let name = &self.metadata.name;
+        let strategy = self
+            .spec
+            .as_ref()
+            .and_then(|s| s.update_strategy.as_ref())
+            .and_then(|s| s.type_.as_deref())
+            .unwrap_or("RollingUpdate");
+        if strategy != "RollingUpdate" {
+            return Err(rollout_error(
+                name,
+                String::from("rollout status is only available for the RollingUpdate strategy"),
+            ));
+        }
+        let status = self.status.clone().unwrap_or_default();
+        let mut progress = RolloutProgress {
+            desired: status.desired_number_scheduled,
+            updated: status.updated_number_scheduled.unwrap_or_default(),
+            ready: status.number_ready,
+            available: status.number_available.unwrap_or_default(),
+            observed: observed(self.metadata.generation, status.observed_generation),
+            ..Default::default()
+        };
+        let name = name.as_deref().unwrap_or_default();
+        progress.message = if !progress.observed {
+            String::from("Waiting for daemon set spec update to be observed...")
+        } else if progress.updated < progress.desired {
+            format!(
+                "Waiting for daemon set {:?} rollout to finish: {} out of {} new pods have been updated...",
+                name, progress.updated, progress.desired
+            )
+        } else if progress.available < progress.desired {
+            format!(
+                "Waiting for daemon set {:?} rollout to finish: {} of {} updated pods are available...",
+                name, progress.available, progress.desired
+            )
+        } else {
+            progress.done = true;
+            format!("daemon set {:?} successfully rolled out", name)
+        };
+        Ok(progress)
+    }
+}
+
+/// A revision of a Deployment, kept as a ReplicaSet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Revision {
+    pub revision: i64,
+    pub replica_set: String,
+    /// `kubernetes.io/change-cause` annotation, if set.
+    pub change_cause: Option<String>,
+    pub template: PodTemplateSpec,
+}
+
+/// Formats a label selector for the `labelSelector` query parameter.
+pub(crate) fn selector_query(selector: &LabelSelector) -> String {
+    let mut terms: Vec<String> = selector
+        .match_labels
+        .iter()
+        .flatten()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect();
+    for expression in selector.match_expressions.iter().flatten() {
+        let values = expression.values.clone().unwrap_or_default().join(",");
+        terms.push(match expression.operator.as_str() {
+            "In" => format!("{} in ({})", expression.key, values),
+            "NotIn" => format!("{} notin ({})", expression.key, values),
+            "DoesNotExist" => format!("!{}", expression.key),
+            _ => expression.key.clone(),
+        });
+    }
+    terms.join(",")
+}
+
+impl<'a, K> Api<'a, K>
+where
+    K: Serialize + DeserializeOwned,
+{
+    /// Restarts the pods of a Deployment, StatefulSet or DaemonSet, like
+    /// `kubectl rollout restart`: the pod template changes, so the controller
+    /// rolls out new pods following its update strategy.
+    pub fn rollout_restart(&self, name: &str) -> Result<K, KubernetesError> {
+        let restarted_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let patch = Patch::Merge(json!({
+            "spec": { "template": { "metadata": { "annotations": {
+                RESTARTED_AT_ANNOTATION: restarted_at
+            } } } }
+        }));
+        self.patch(name, &patch, Default::default())
+    }
+}
+
+impl<'a, K> Api<'a, K>
+where
+    K: Rollout + Serialize + DeserializeOwned,
+{
+    /// Blocks until the rollout of `name` completes, like `kubectl rollout status`,
+    /// and returns the object as last read. `progress` is called each time the
+    /// progress message changes.
+    pub fn rollout_status<F>(
+        &self,
+        name: &str,
+        timeout: Duration,
+        mut progress: F,
+    ) -> Result<K, KubernetesError>
+    where
+        F: FnMut(&RolloutProgress),
+    {
+        let mut last_message = None;
+        let mut object = None;
+        wait_until(&format!("rollout of {}", name), timeout, || {
+            let current = self.get(name)?;
+            let current_progress = current.rollout_progress()?;
+            if last_message.as_ref() != Some(&current_progress.message) {
+                progress(&current_progress);
+                last_message = Some(current_progress.message.clone());
+            }
+            object = Some(current);
+            Ok(current_progress.done)
+        })?;
+        object.ok_or(KubernetesError::InvalidDataError)
+    }
+}
+
+impl<'a> Api<'a, Deployment> {
+    /// Revisions of a Deployment, oldest first, like `kubectl rollout history`.
+    pub fn rollout_history(&self, name: &str) -> Result<Vec<Revision>, KubernetesError> {
+        let deployment = self.get(name)?;
+        self.revisions(&deployment)
+    }
+
+    fn revisions(&self, deployment: &Deployment) -> Result<Vec<Revision>, KubernetesError> {
+        let selector = deployment
+            .spec
+            .as_ref()
+            .map(|s| selector_query(&s.selector))
+            .unwrap_or_default();
+        let replica_sets = self
+            .client
+            .api::<ReplicaSet>(self.namespace())
+            .list(ListOptional {
+                label_selector: Some(&selector),
+                ..Default::default()
+            })?
+            .items;
+        let mut revisions: Vec<Revision> = replica_sets
+            .into_iter()
+            .filter(|rs| {
+                rs.metadata.owner_references.iter().flatten().any(|o| {
+                    o.controller == Some(true) && Some(&o.uid) == deployment.metadata.uid.as_ref()
+                })
+            })
+            .filter_map(|rs| {
+                let annotations = rs.metadata.annotations.unwrap_or_default();
+                Some(Revision {
+                    revision: annotations.get(REVISION_ANNOTATION)?.parse().ok()?,
+                    replica_set: rs.metadata.name.unwrap_or_default(),
+                    change_cause: annotations.get("kubernetes.io/change-cause").cloned(),
+                    template: rs.spec?.template.unwrap_or_default(),
+                })
+            })
+            .collect();
+        revisions.sort_by_key(|r| r.revision);
+        Ok(revisions)
+    }
+
+    /// Rolls a Deployment back to `to_revision`, or to the previous revision, like
+    /// `kubectl rollout undo`. Fails if the Deployment changed meanwhile.
+    pub fn rollout_undo(
+        &self,
+        name: &str,
+        to_revision: Option<i64>,
+    ) -> Result<Deployment, KubernetesError> {
+        let deployment = self.get(name)?;
+        if deployment.spec.as_ref().and_then(|s| s.paused) == Some(true) {
+            return Err(rollout_error(
+                &deployment.metadata.name,
+                String::from("can't undo a paused deployment, resume it first"),
+            ));
+        }
+        let revisions = self.revisions(&deployment)?;
+        let target = match to_revision {
+            Some(revision) => revisions.iter().find(|r| r.revision == revision),
+            // The last revision is the current one.
+            None => revisions.iter().rev().nth(1),
+        }
+        .ok_or_else(|| {
+            rollout_error(
+                &deployment.metadata.name,
+                match to_revision {
+                    Some(revision) => format!("revision {} not found", revision),
+                    None => String::from("no previous revision to roll back to"),
+                },
+            )
+        })?;
+
+        let mut template = target.template.clone();
+        if let Some(labels) = template.metadata.as_mut().and_then(|m| m.labels.as_mut()) {
+            labels.remove("pod-template-hash");
+        }
+        // Annotations of the target revision replace the ones the Deployment got
+        // from the current one.
+        let mut annotations: BTreeMap<String, String> = deployment
+            .metadata
+            .annotations
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(k, _)| UNDO_SKIPPED_ANNOTATIONS.contains(&k.as_str()))
+            .collect();
+        let replica_set = self
+            .client
+            .api::<ReplicaSet>(self.namespace())
+            .get(&target.replica_set)?;
+        annotations.extend(
+            replica_set
+                .metadata
+                .annotations
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(k, _)| !UNDO_SKIPPED_ANNOTATIONS.contains(&k.as_str())),
+        );
+
+        let to_value =
+            |v| serde_json::to_value(v).map_err(|source| KubernetesError::JsonError { source });
+        let patch = JsonPatch::new()
+            .test(
+                "/metadata/resourceVersion",
+                Value::from(deployment.metadata.resource_version.clone()),
+            )
+            .replace("/spec/template", to_value(&template)?)
+            .add("/metadata/annotations", json!(annotations));
+        self.patch(name, &patch.into(), Default::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployment(status: Value) -> Deployment {
+        serde_json::from_value(json!({
+            "metadata": {"name": "web", "generation": 4},
+            "spec": {"replicas": 3, "selector": {}, "template": {}},
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_deployment_progress() {
+        let stale = deployment(json!({"observedGeneration": 3}));
+        assert!(!stale.rollout_progress().unwrap().observed);
+
+        let updating = deployment(json!({
+            "observedGeneration": 4, "replicas": 4, "updatedReplicas": 2, "readyReplicas": 4, "availableReplicas": 4
+        }));
+        let progress = updating.rollout_progress().unwrap();
+        assert!(!progress.done);
+        assert_eq!(
+            progress.message,
+            "Waiting for deployment \"web\" rollout to finish: 2 out of 3 new replicas have been updated..."
+        );
+
+        let terminating = deployment(json!({
+            "observedGeneration": 4, "replicas": 4, "updatedReplicas": 3, "readyReplicas": 4, "availableReplicas": 4
+        }));
+        assert!(terminating
+            .rollout_progress()
+            .unwrap()
+            .message
+            .ends_with("1 old replicas are pending termination..."));
+
+        let done = deployment(json!({
+            "observedGeneration": 4, "replicas": 3, "updatedReplicas": 3, "readyReplicas": 3, "availableReplicas": 3
+        }));
+        assert!(done.rollout_progress().unwrap().done);
+
+        let stuck = deployment(json!({
+            "observedGeneration": 4, "replicas": 3, "updatedReplicas": 1,
+            "conditions": [{"type": "Progressing", "status": "False", "reason": "ProgressDeadlineExceeded"}]
+        }));
+        assert!(stuck
+            .rollout_progress()
+            .unwrap_err()
+            .to_string()
+            .contains("exceeded its progress deadline"));
+    }
+
+    #[test]
+    fn test_statefulset_progress() {
+        let statefulset: StatefulSet = serde_json::from_value(json!({
+            "metadata": {"name": "db", "generation": 2},
+            "spec": {"replicas": 3, "selector": {}, "serviceName": "db", "template": {},
+                     "updateStrategy": {"type": "RollingUpdate", "rollingUpdate": {"partition": 1}}},
+            "status": {"observedGeneration": 2, "replicas": 3, "readyReplicas": 3, "updatedReplicas": 1}
+        }))
+        .unwrap();
+        let progress = statefulset.rollout_progress().unwrap();
+        assert!(!progress.done);
+        assert_eq!(
+            progress.message,
+            "Waiting for partitioned roll out to finish: 1 out of 2 new pods have been updated..."
+        );
+    }
+
+    #[test]
+    fn test_selector_query() {
+        let selector: LabelSelector = serde_json::from_value(json!({
+            "matchLabels": {"app": "web"},
+            "matchExpressions": [
+                {"key": "tier", "operator": "In", "values": ["front", "edge"]},
+                {"key": "canary", "operator": "DoesNotExist"}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            selector_query(&selector),
+            "app=web,tier in (front,edge),!canary"
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use crate::dynamic::{build_request, to_json, Api};
+use crate::errors::KubernetesError;
+use crate::patch::Patch;
+use http::Method;
+use k8s_openapi::api::autoscaling::v1::Scale;
+use serde_json::json;
+
+impl<'a, K> Api<'a, K> {
+    /// Reads the `scale` subresource of `name`: desired and current replicas, and
+    /// the label selector of its pods. Served by Deployments, StatefulSets,
+    /// ReplicaSets and custom resources declaring it.
+    pub fn get_scale(&self, name: &str) -> Result<Scale, KubernetesError> {
+        let request = build_request(
+            Method::GET,
+            self.object_path(name, Some("scale")),
+            None,
+            vec![],
+        )?;
+        self.client.request_json(request)
+    }
+
+    /// Replaces the `scale` subresource of `name`. Set `metadata.resourceVersion`
+    /// to fail if it changed since it was read.
+    pub fn replace_scale(&self, name: &str, scale: &Scale) -> Result<Scale, KubernetesError> {
+        let request = build_request(
+            Method::PUT,
+            self.object_path(name, Some("scale")),
+            Some("application/json"),
+            to_json(scale)?,
+        )?;
+        self.client.request_json(request)
+    }
+
+    /// Sets the number of replicas of `name`, like `kubectl scale`.
+    pub fn scale(&self, name: &str, replicas: i32) -> Result<Scale, KubernetesError> {
+        let patch = Patch::Merge(json!({ "spec": { "replicas": replicas } }));
+        let request = build_request(
+            Method::PATCH,
+            self.object_path(name, Some("scale")),
+            Some(patch.content_type()),
+            patch.to_body()?,
+        )?;
+        self.client.request_json(request)
+    }
+}
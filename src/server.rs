@@ -0,0 +1,223 @@
+use crate::errors::KubernetesError;
+use crate::kubernetes::Kubernetes;
+use http::{Request, StatusCode};
+use k8s_openapi::apimachinery::pkg::version::Info;
+use std::fmt;
+use std::io::Read;
+
+/// Version of the API server, as returned by `/version`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerVersion {
+    pub info: Info,
+}
+
+impl ServerVersion {
+    /// Major version, e.g. `1`.
+    pub fn major(&self) -> Option<u32> {
+        leading_number(&self.info.major)
+    }
+
+    /// Minor version, e.g. `21`. Providers sometimes report it as `21+`.
+    pub fn minor(&self) -> Option<u32> {
+        leading_number(&self.info.minor)
+            .or_else(|| leading_number(self.info.git_version.split('.').nth(1)?))
+    }
+
+    /// Whether the server is at least `major.minor`, e.g. `at_least(1, 19)` to know
+    /// if `events.k8s.io/v1` is served.
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        match (self.major(), self.minor()) {
+            (Some(server_major), Some(server_minor)) => {
+                (server_major, server_minor) >= (major, minor)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.info.git_version)
+    }
+}
+
+fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value
+        .trim_start_matches('v')
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+/// Health endpoints of the API server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HealthEndpoint {
+    /// `/healthz`, deprecated in favor of the two others.
+    Healthz,
+    /// `/livez`: whether the API server should be restarted.
+    Livez,
+    /// `/readyz`: whether the API server is ready to serve traffic.
+    Readyz,
+}
+
+impl HealthEndpoint {
+    pub fn path(&self) -> &'static str {
+        match self {
+            HealthEndpoint::Healthz => "/healthz",
+            HealthEndpoint::Livez => "/livez",
+            HealthEndpoint::Readyz => "/readyz",
+        }
+    }
+}
+
+/// One line of a verbose health report, e.g. `[+]etcd ok` or `[-]etcd failed: reason withheld`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub name: String,
+    pub passed: bool,
+    /// What the API server said about a failed check.
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthReport {
+    pub endpoint: HealthEndpoint,
+    pub status_code: StatusCode,
+    pub healthy: bool,
+    /// Individual checks, only filled for verbose reports.
+    pub checks: Vec<HealthCheck>,
+    /// Raw body returned by the API server.
+    pub body: String,
+}
+
+impl HealthReport {
+    pub fn failed_checks(&self) -> impl Iterator<Item = &HealthCheck> {
+        self.checks.iter().filter(|c| !c.passed)
+    }
+
+    fn parse(endpoint: HealthEndpoint, status_code: StatusCode, body: String) -> Self {
+        let checks = body
+            .lines()
+            .filter_map(|line| {
+                let (passed, rest) = if let Some(rest) = line.strip_prefix("[+]") {
+                    (true, rest)
+                } else if let Some(rest) = line.strip_prefix("[-]") {
+                    (false, rest)
+                } else {
+                    return None;
+                };
+                let (name, outcome) = rest.split_once(' ').unwrap_or((rest, ""));
+                let reason = outcome
+                    .strip_prefix("failed")
+                    .map(|r| r.trim_start_matches(':').trim().to_string())
+                    .filter(|r| !r.is_empty());
+                Some(HealthCheck {
+                    name: name.to_string(),
+                    passed,
+                    reason,
+                })
+            })
+            .collect();
+        HealthReport {
+            endpoint,
+            status_code,
+            healthy: status_code.is_success(),
+            checks,
+            body,
+        }
+    }
+}
+
+impl Kubernetes {
+    pub fn server_version(&self) -> Result<ServerVersion, KubernetesError> {
+        let request = Request::get("/version").body(vec![]).map_err(|err| {
+            KubernetesError::HttpClientBuildError {
+                message: format!("Couldn't build request. Error: {:?}", err),
+            }
+        })?;
+        let info = self.request_json(request)?;
+        Ok(ServerVersion { info })
+    }
+
+    /// Queries a health endpoint. An unhealthy server is not an error: it is
+    /// reported with `healthy` set to false. `verbose` asks for per-check details.
+    pub fn health(
+        &self,
+        endpoint: HealthEndpoint,
+        verbose: bool,
+    ) -> Result<HealthReport, KubernetesError> {
+        let path = if verbose {
+            format!("{}?verbose", endpoint.path())
+        } else {
+            endpoint.path().to_string()
+        };
+        let request = Request::get(path).body(vec![]).map_err(|err| {
+            KubernetesError::HttpClientBuildError {
+                message: format!("Couldn't build request. Error: {:?}", err),
+            }
+        })?;
+        let mut response = self.send_unchecked(request)?;
+        let status_code = response.status();
+        let mut body = String::new();
+        response
+            .body_mut()
+            .read_to_string(&mut body)
+            .map_err(|source| KubernetesError::IoError { source })?;
+        Ok(HealthReport::parse(endpoint, status_code, body))
+    }
+
+    pub fn healthz(&self, verbose: bool) -> Result<HealthReport, KubernetesError> {
+        self.health(HealthEndpoint::Healthz, verbose)
+    }
+
+    pub fn livez(&self, verbose: bool) -> Result<HealthReport, KubernetesError> {
+        self.health(HealthEndpoint::Livez, verbose)
+    }
+
+    pub fn readyz(&self, verbose: bool) -> Result<HealthReport, KubernetesError> {
+        self.health(HealthEndpoint::Readyz, verbose)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: &str, minor: &str, git_version: &str) -> ServerVersion {
+        ServerVersion {
+            info: Info {
+                major: major.to_string(),
+                minor: minor.to_string(),
+                git_version: git_version.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_version_numbers() {
+        let gke = version("1", "21+", "v1.21.5-gke.1302");
+        assert_eq!(gke.major(), Some(1));
+        assert_eq!(gke.minor(), Some(21));
+        assert!(gke.at_least(1, 19));
+        assert!(!gke.at_least(1, 22));
+        assert_eq!(version("", "", "v1.18.2").minor(), Some(18));
+    }
+
+    #[test]
+    fn test_parse_verbose_report() {
+        let body = "[+]ping ok\n[+]log ok\n[-]etcd failed: reason withheld\n[+]poststarthook/start-kube-aggregator-informers ok\nreadyz check failed\n";
+        let report = HealthReport::parse(
+            HealthEndpoint::Readyz,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            body.to_string(),
+        );
+        assert!(!report.healthy);
+        assert_eq!(report.checks.len(), 4);
+        let failed: Vec<_> = report.failed_checks().collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].name, "etcd");
+        assert_eq!(failed[0].reason.as_deref(), Some("reason withheld"));
+    }
+}
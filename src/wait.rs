@@ -0,0 +1,587 @@
+use crate::dynamic::Api;
+use crate::errors::KubernetesError;
+use http::StatusCode;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::WatchEvent;
+use k8s_openapi::{ListOptional, WatchOptional};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What to wait for, like the `--for` flag of `kubectl wait`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WaitCondition {
+    /// An entry of `status.conditions` has this type and status, compared without
+    /// case like kubectl does, e.g. `Ready` and `True`.
+    Condition { type_: String, status: String },
+    /// The object doesn't exist anymore, or was recreated with another UID.
+    Delete,
+    /// The JSONPath expression, e.g. `{.status.phase}`, yields `value`, or
+    /// anything when `value` is `None`.
+    JsonPath {
+        expression: String,
+        value: Option<String>,
+    },
+}
+
+impl WaitCondition {
+    /// The condition `type_` is `True`.
+    pub fn condition(type_: &str) -> Self {
+        WaitCondition::Condition {
+            type_: type_.to_string(),
+            status: String::from("True"),
+        }
+    }
+
+    /// The field at `expression` equals `value`.
+    pub fn jsonpath(expression: &str, value: &str) -> Self {
+        WaitCondition::JsonPath {
+            expression: expression.to_string(),
+            value: Some(value.to_string()),
+        }
+    }
+
+    /// Whether the condition holds for `object`. Never true for `Delete`.
+    pub fn is_met(&self, object: &Value) -> Result<bool, KubernetesError> {
+        match self {
+            WaitCondition::Condition { type_, status } => {
+                let generation = object
+                    .pointer("/metadata/generation")
+                    .and_then(Value::as_i64);
+                let conditions = object
+                    .pointer("/status/conditions")
+                    .and_then(Value::as_array);
+                Ok(conditions.into_iter().flatten().any(|condition| {
+                    let field = |name| condition.get(name).and_then(Value::as_str);
+                    let observed = condition.get("observedGeneration").and_then(Value::as_i64);
+                    field("type").is_some_and(|t| t.eq_ignore_ascii_case(type_))
+                        && field("status").is_some_and(|s| s.eq_ignore_ascii_case(status))
+                        // A condition about an older generation is stale.
+                        && (observed.is_none() || observed >= generation)
+                }))
+            }
+            WaitCondition::Delete => Ok(false),
+            WaitCondition::JsonPath { expression, value } => {
+                let steps = parse_jsonpath(expression).map_err(|message| invalid(self, message))?;
+                let results = evaluate(&steps, object);
+                match (value, results.as_slice()) {
+                    (None, results) => Ok(!results.is_empty()),
+                    (Some(_), []) => Ok(false),
+                    (Some(expected), [result]) => Ok(&to_text(result) == expected),
+                    (Some(_), _) => Err(invalid(
+                        self,
+                        String::from("the expression matches more than one value"),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+fn invalid(condition: &WaitCondition, message: String) -> KubernetesError {
+    KubernetesError::InvalidWaitConditionError {
+        condition: condition.to_string(),
+        message,
+    }
+}
+
+impl fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WaitCondition::Condition { type_, status } if status == "True" => {
+                write!(f, "condition={}", type_)
+            }
+            WaitCondition::Condition { type_, status } => {
+                write!(f, "condition={}={}", type_, status)
+            }
+            WaitCondition::Delete => write!(f, "delete"),
+            WaitCondition::JsonPath {
+                expression,
+                value: None,
+            } => write!(f, "jsonpath={}", expression),
+            WaitCondition::JsonPath {
+                expression,
+                value: Some(value),
+            } => write!(f, "jsonpath={}={}", expression, value),
+        }
+    }
+}
+
+/// Parses the `--for` syntax of `kubectl wait`: `delete`, `condition=Ready`,
+/// `condition=Ready=False` or `jsonpath={.status.phase}=Running`.
+impl FromStr for WaitCondition {
+    type Err = KubernetesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |message: &str| KubernetesError::InvalidWaitConditionError {
+            condition: s.to_string(),
+            message: message.to_string(),
+        };
+        if s.eq_ignore_ascii_case("delete") {
+            return Ok(WaitCondition::Delete);
+        }
+        if let Some(condition) = s.strip_prefix("condition=") {
+            let (type_, status) = condition.split_once('=').unwrap_or((condition, "True"));
+            if type_.is_empty() {
+                return Err(error("missing condition type"));
+            }
+            return Ok(WaitCondition::Condition {
+                type_: type_.to_string(),
+                status: status.to_string(),
+            });
+        }
+        if let Some(jsonpath) = s.strip_prefix("jsonpath=") {
+            // The value follows the `=` after the closing brace, if any.
+            let split = match jsonpath.rfind('}') {
+                Some(end) => jsonpath[end..].find('=').map(|i| end + i),
+                None => jsonpath.find('='),
+            };
+            let (expression, value) = match split {
+                Some(i) => (&jsonpath[..i], Some(jsonpath[i + 1..].to_string())),
+                None => (jsonpath, None),
+            };
+            parse_jsonpath(expression).map_err(|message| error(&message))?;
+            return Ok(WaitCondition::JsonPath {
+                expression: expression.to_string(),
+                value,
+            });
+        }
+        Err(error("expected delete, condition=... or jsonpath=..."))
+    }
+}
+
+impl<'a, K> Api<'a, K>
+where
+    K: Serialize + DeserializeOwned,
+{
+    /// Blocks until `condition` holds for the object `name`, for at most `timeout`,
+    /// like `kubectl wait`. Returns the object that satisfied it, or `None` when
+    /// waiting for its deletion.
+    ///
+    /// Changes are watched; if the resource can't be watched, it is polled. A
+    /// `timeout` too large to be a deadline, e.g. `Duration::MAX`, waits forever.
+    pub fn wait_for(
+        &self,
+        name: &str,
+        condition: &WaitCondition,
+        timeout: Duration,
+    ) -> Result<Option<K>, KubernetesError> {
+        let deadline = Instant::now().checked_add(timeout);
+        let field_selector = format!("metadata.name={}", name);
+        // Set by the first object seen, a recreated object counts as deleted.
+        let mut uid = None;
+        loop {
+            let list = self.list(ListOptional {
+                field_selector: Some(&field_selector),
+                ..Default::default()
+            })?;
+            let current = list.items.into_iter().next();
+            if let Some(result) = check(condition, current, &mut uid)? {
+                return Ok(result);
+            }
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if remaining == Some(Duration::ZERO) {
+                return Err(self.wait_timeout(name, condition, timeout));
+            }
+            let watch = self.watch(WatchOptional {
+                field_selector: Some(&field_selector),
+                resource_version: list.metadata.resource_version.as_deref(),
+                timeout_seconds: remaining.map(|r| r.as_millis().div_ceil(1000) as i64),
+                ..Default::default()
+            });
+            let events = match watch {
+                Ok(events) => events,
+                Err(err) if cannot_watch(&err) => {
+                    return self.poll_for(name, condition, remaining.unwrap_or(timeout), uid);
+                }
+                Err(err) => return Err(err),
+            };
+            for event in events {
+                let object = match event {
+                    Ok(WatchEvent::Added(object)) | Ok(WatchEvent::Modified(object)) => {
+                        Some(object)
+                    }
+                    Ok(WatchEvent::Deleted(_)) => None,
+                    Ok(WatchEvent::Bookmark { .. }) => continue,
+                    // Expired resource version or dropped connection: list again.
+                    Ok(WatchEvent::ErrorStatus(_)) | Ok(WatchEvent::ErrorOther(_)) => break,
+                    Err(err) if err.is_timeout() => break,
+                    Err(err) => return Err(err),
+                };
+                if let Some(result) = check(condition, object, &mut uid)? {
+                    return Ok(result);
+                }
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(self.wait_timeout(name, condition, timeout));
+            }
+        }
+    }
+
+    /// Same as `wait_for`, by getting the object repeatedly.
+    fn poll_for(
+        &self,
+        name: &str,
+        condition: &WaitCondition,
+        timeout: Duration,
+        mut uid: Option<String>,
+    ) -> Result<Option<K>, KubernetesError> {
+        let mut result = None;
+        wait_until(&self.waited(name, condition), timeout, || {
+            let current = match self.get(name) {
+                Ok(object) => Some(object),
+                Err(err) if err.is_not_found() => None,
+                Err(err) => return Err(err),
+            };
+            result = check(condition, current, &mut uid)?;
+            Ok(result.is_some())
+        })?;
+        result.ok_or(KubernetesError::InvalidDataError)
+    }
+
+    fn waited(&self, name: &str, condition: &WaitCondition) -> String {
+        format!("{} on {} {}", condition, self.resource().resource, name)
+    }
+
+    fn wait_timeout(
+        &self,
+        name: &str,
+        condition: &WaitCondition,
+        timeout: Duration,
+    ) -> KubernetesError {
+        KubernetesError::WaitTimeoutError {
+            what: self.waited(name, condition),
+            timeout,
+        }
+    }
+}
+
+/// Interval between two checks while waiting, growing up to the maximum.
+const WAIT_INITIAL_INTERVAL: Duration = Duration::from_millis(100);
+const WAIT_MAX_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Calls `done` until it returns true, with a growing interval, for at most `timeout`.
+/// There is no deadline if `timeout` doesn't fit in an `Instant`.
+pub(crate) fn wait_until<F>(
+    what: &str,
+    timeout: Duration,
+    mut done: F,
+) -> Result<(), KubernetesError>
+where
+    F: FnMut() -> Result<bool, KubernetesError>,
+{
+    let deadline = Instant::now().checked_add(timeout);
+    let mut interval = WAIT_INITIAL_INTERVAL;
+    loop {
+        if done()? {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if deadline.is_some_and(|d| now >= d) {
+            return Err(KubernetesError::WaitTimeoutError {
+                what: what.to_string(),
+                timeout,
+            });
+        }
+        thread::sleep(deadline.map_or(interval, |d| interval.min(d - now)));
+        interval = (interval * 2).min(WAIT_MAX_INTERVAL);
+    }
+}
+
+/// The outcome of the wait if `condition` is satisfied by `object`, `None` meaning
+/// the object doesn't exist.
+#[allow(clippy::option_option)]
+fn check<K: Serialize>(
+    condition: &WaitCondition,
+    object: Option<K>,
+    uid: &mut Option<String>,
+) -> Result<Option<Option<K>>, KubernetesError> {
+    let object = match object {
+        Some(object) => object,
+        None if *condition == WaitCondition::Delete => return Ok(Some(None)),
+        None => return Ok(None),
+    };
+    let value =
+        serde_json::to_value(&object).map_err(|source| KubernetesError::JsonError { source })?;
+    let current_uid = value.pointer("/metadata/uid").and_then(Value::as_str);
+    if *condition == WaitCondition::Delete {
+        if uid.is_some() && current_uid != uid.as_deref() {
+            return Ok(Some(None));
+        }
+    } else if condition.is_met(&value)? {
+        return Ok(Some(Some(object)));
+    }
+    if uid.is_none() {
+        *uid = current_uid.map(String::from);
+    }
+    Ok(None)
+}
+
+/// Whether a watch failed because the resource doesn't support it.
+fn cannot_watch(err: &KubernetesError) -> bool {
+    matches!(
+        err.status_code(),
+        Some(StatusCode::METHOD_NOT_ALLOWED) | Some(StatusCode::FORBIDDEN)
+    )
+}
+
+/// A step of a JSONPath expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Field(String),
+    Index(i64),
+    Wildcard,
+    /// `[?(@.path == value)]`, or `[?(@.path)]` when `value` is `None`.
+    Filter {
+        path: Vec<Step>,
+        equal: bool,
+        value: Option<Value>,
+    },
+}
+
+/// Parses the subset of JSONPath kubectl users rely on in `wait`: fields, indices,
+/// wildcards and filters on a field, e.g. `{.status.conditions[?(@.type=="Ready")].status}`.
+fn parse_jsonpath(expression: &str) -> Result<Vec<Step>, String> {
+    let trimmed = expression.trim();
+    let inner = trimmed
+        .strip_prefix('{')
+        .and_then(|e| e.strip_suffix('}'))
+        .unwrap_or(trimmed);
+    parse_steps(inner.strip_prefix('$').unwrap_or(inner))
+}
+
+fn parse_steps(mut rest: &str) -> Result<Vec<Step>, String> {
+    let mut steps = vec![];
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            if after.starts_with('.') {
+                return Err(String::from("recursive descent is not supported"));
+            }
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let name = &after[..end];
+            steps.push(match name {
+                "" => return Err(format!("missing field name in {:?}", rest)),
+                "*" => Step::Wildcard,
+                name => Step::Field(name.to_string()),
+            });
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix("[?(") {
+            let end = after
+                .find(")]")
+                .ok_or_else(|| format!("unterminated filter in {:?}", rest))?;
+            steps.push(parse_filter(&after[..end])?);
+            rest = &after[end + 2..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after
+                .find(']')
+                .ok_or_else(|| format!("unterminated brackets in {:?}", rest))?;
+            let content = after[..end].trim();
+            steps.push(if content == "*" {
+                Step::Wildcard
+            } else if let Some(name) = unquote(content) {
+                Step::Field(name.to_string())
+            } else {
+                Step::Index(
+                    content
+                        .parse()
+                        .map_err(|_| format!("invalid index {:?}", content))?,
+                )
+            });
+            rest = &after[end + 1..];
+        } else {
+            return Err(format!("unexpected {:?}", rest));
+        }
+    }
+    Ok(steps)
+}
+
+fn parse_filter(filter: &str) -> Result<Step, String> {
+    let (path, equal, value) = match filter.find("==").or_else(|| filter.find("!=")) {
+        Some(i) => (
+            &filter[..i],
+            &filter[i..i + 2] == "==",
+            Some(filter[i + 2..].trim()),
+        ),
+        None => (filter, true, None),
+    };
+    let path = path
+        .trim()
+        .strip_prefix('@')
+        .ok_or_else(|| format!("filter {:?} must start with @", filter))?;
+    let value = match value {
+        Some(literal) => Some(match unquote(literal) {
+            Some(text) => Value::from(text),
+            None => serde_json::from_str(literal)
+                .map_err(|_| format!("invalid literal {:?}", literal))?,
+        }),
+        None => None,
+    };
+    Ok(Step::Filter {
+        path: parse_steps(path)?,
+        equal,
+        value,
+    })
+}
+
+fn unquote(text: &str) -> Option<&str> {
+    ['\'', '"'].iter().find_map(|quote| {
+        text.strip_prefix(*quote)
+            .and_then(|t| t.strip_suffix(*quote))
+    })
+}
+
+fn evaluate<'v>(steps: &[Step], root: &'v Value) -> Vec<&'v Value> {
+    let mut current = vec![root];
+    for step in steps {
+        current = current
+            .into_iter()
+            .flat_map(|value| -> Vec<&'v Value> {
+                match (step, value) {
+                    (Step::Field(name), Value::Object(map)) => map.get(name).into_iter().collect(),
+                    (Step::Index(index), Value::Array(items)) => {
+                        let index = if *index < 0 {
+                            items.len() as i64 + index
+                        } else {
+                            *index
+                        };
+                        usize::try_from(index)
+                            .ok()
+                            .and_then(|i| items.get(i))
+                            .into_iter()
+                            .collect()
+                    }
+                    (Step::Wildcard, Value::Array(items)) => items.iter().collect(),
+                    (Step::Wildcard, Value::Object(map)) => map.values().collect(),
+                    (Step::Filter { path, equal, value }, Value::Array(items)) => items
+                        .iter()
+                        .filter(|item| {
+                            let found = evaluate(path, item);
+                            match value {
+                                None => !found.is_empty(),
+                                Some(expected) => found.contains(&expected) == *equal,
+                            }
+                        })
+                        .collect(),
+                    _ => vec![],
+                }
+            })
+            .collect();
+    }
+    current
+}
+
+/// A value as printed by kubectl's JSONPath output: strings without quotes.
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pod() -> Value {
+        json!({
+            "metadata": {"name": "web", "generation": 2},
+            "status": {
+                "phase": "Running",
+                "conditions": [
+                    {"type": "Initialized", "status": "True"},
+                    {"type": "Ready", "status": "False"},
+                    {"type": "Synced", "status": "True", "observedGeneration": 1}
+                ],
+                "containerStatuses": [{"name": "web", "restartCount": 3}]
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "condition=Ready".parse::<WaitCondition>().unwrap(),
+            WaitCondition::condition("Ready")
+        );
+        assert_eq!(
+            "condition=Ready=False".parse::<WaitCondition>().unwrap(),
+            WaitCondition::Condition {
+                type_: String::from("Ready"),
+                status: String::from("False")
+            }
+        );
+        assert_eq!(
+            "delete".parse::<WaitCondition>().unwrap(),
+            WaitCondition::Delete
+        );
+        assert_eq!(
+            "jsonpath={.status.conditions[?(@.type==\"Ready\")].status}=True"
+                .parse::<WaitCondition>()
+                .unwrap(),
+            WaitCondition::jsonpath("{.status.conditions[?(@.type==\"Ready\")].status}", "True")
+        );
+        assert_eq!(
+            "jsonpath={.status.loadBalancer.ingress}"
+                .parse::<WaitCondition>()
+                .unwrap(),
+            WaitCondition::JsonPath {
+                expression: String::from("{.status.loadBalancer.ingress}"),
+                value: None
+            }
+        );
+        assert!("ready".parse::<WaitCondition>().is_err());
+        assert!("jsonpath={..name}=x".parse::<WaitCondition>().is_err());
+    }
+
+    #[test]
+    fn test_conditions() {
+        let pod = pod();
+        assert!(WaitCondition::condition("initialized")
+            .is_met(&pod)
+            .unwrap());
+        assert!(!WaitCondition::condition("Ready").is_met(&pod).unwrap());
+        assert!("condition=Ready=false"
+            .parse::<WaitCondition>()
+            .unwrap()
+            .is_met(&pod)
+            .unwrap());
+        // Observed for generation 1, the object is at generation 2.
+        assert!(!WaitCondition::condition("Synced").is_met(&pod).unwrap());
+    }
+
+    #[test]
+    fn test_jsonpath() {
+        let pod = pod();
+        let met = |expression: &str, value: &str| {
+            WaitCondition::jsonpath(expression, value)
+                .is_met(&pod)
+                .unwrap()
+        };
+        assert!(met("{.status.phase}", "Running"));
+        assert!(met("{$.status.containerStatuses[0].restartCount}", "3"));
+        assert!(met("{.status.containerStatuses[-1]['name']}", "web"));
+        assert!(met(
+            "{.status.conditions[?(@.type=='Ready')].status}",
+            "False"
+        ));
+        assert!(!met("{.status.phase}", "Pending"));
+        assert!(!met("{.spec.nodeName}", "node-1"));
+        assert!(
+            WaitCondition::jsonpath("{.status.conditions[*].type}", "Ready")
+                .is_met(&pod)
+                .is_err()
+        );
+        assert_eq!(
+            evaluate(
+                &parse_jsonpath("{.status.conditions[?(@.status!=\"True\")].type}").unwrap(),
+                &pod
+            ),
+            vec!["Ready"]
+        );
+    }
+}
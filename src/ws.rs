@@ -0,0 +1,334 @@
+//! WebSocket connections to the API server, used by the subresources streaming
+//! several channels over a single connection (exec, attach, portforward).
+//!
+//! Each binary message starts with the number of the channel it belongs to.
+use crate::errors::KubernetesError;
+use crate::kubernetes::{Credentials, Kubernetes};
+use crate::ratelimit::RateLimiter;
+use http::{Method, StatusCode};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use openssl::pkey::PKey;
+use openssl::ssl::{SslConnector, SslMethod, SslStream, SslVerifyMode};
+use openssl::x509::X509;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::HandshakeError;
+use tungstenite::{Message, WebSocket};
+
+/// How long the background thread blocks on reads before checking for data to send.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The TCP connection under a WebSocket, encrypted or not depending on the scheme
+/// of the API server URI.
+pub(crate) enum Stream {
+    Plain(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+impl Stream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Stream::Plain(stream) => stream,
+            Stream::Tls(stream) => stream.get_ref(),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+fn build_error(message: String) -> KubernetesError {
+    KubernetesError::HttpClientBuildError { message }
+}
+
+fn websocket_error(source: tungstenite::Error) -> KubernetesError {
+    KubernetesError::WebSocketError {
+        source: Box::new(source),
+    }
+}
+
+/// What is needed to open WebSockets to the API server, detached from the client
+/// so that background threads can open new ones.
+#[derive(Clone, Debug)]
+pub(crate) struct Endpoint {
+    base_uri: String,
+    credentials: Credentials,
+    connect_timeout: Option<Duration>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Endpoint {
+    pub(crate) fn of(client: &Kubernetes) -> Self {
+        Endpoint {
+            base_uri: client.base_uri.clone(),
+            credentials: client.credentials.clone(),
+            connect_timeout: client.timeouts.connect,
+            rate_limiter: client.rate_limiter.clone(),
+        }
+    }
+}
+
+/// Opens a WebSocket to `path` (query included), offering `protocols` in order of
+/// preference. Returns the socket and the protocol the server picked.
+///
+/// TLS settings mirror the ones of the HTTP client built by `Kubernetes::connect`.
+pub(crate) fn connect(
+    endpoint: &Endpoint,
+    path: &str,
+    protocols: &[&str],
+) -> Result<(WebSocket<Stream>, Option<String>), KubernetesError> {
+    if let Some(rate_limiter) = &endpoint.rate_limiter {
+        rate_limiter.acquire();
+    }
+    let base = url::Url::parse(&endpoint.base_uri)
+        .map_err(|err| build_error(format!("Invalid API server URI: {}", err)))?;
+    let host = base
+        .host_str()
+        .ok_or_else(|| build_error(String::from("No host in API server URI.")))?
+        .to_string();
+    let port = base.port_or_known_default().unwrap_or(443);
+    let tls = base.scheme() == "https";
+    let url = format!(
+        "{}://{}:{}{}",
+        if tls { "wss" } else { "ws" },
+        host,
+        port,
+        path
+    );
+
+    let address = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|source| KubernetesError::IoError { source })?
+        .next()
+        .ok_or_else(|| build_error(format!("Couldn't resolve {}", host)))?;
+    let tcp = match endpoint.connect_timeout {
+        Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+        None => TcpStream::connect(address),
+    }
+    .map_err(|source| KubernetesError::IoError { source })?;
+    let stream = if tls {
+        Stream::Tls(Box::new(tls_connect(endpoint, &host, tcp)?))
+    } else {
+        Stream::Plain(tcp)
+    };
+
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .map_err(websocket_error)?;
+    let headers = request.headers_mut();
+    let protocols = protocols.join(", ");
+    headers.insert(
+        "Sec-WebSocket-Protocol",
+        protocols
+            .parse()
+            .map_err(|_| build_error(String::from("Invalid WebSocket protocol.")))?,
+    );
+    if let Credentials::Token(token) = &endpoint.credentials {
+        headers.insert(
+            "Authorization",
+            format!("Bearer {}", token)
+                .parse()
+                .map_err(|_| build_error(String::from("Invalid bearer token.")))?,
+        );
+    }
+
+    match tungstenite::client::client(request, stream) {
+        Ok((socket, response)) => {
+            let protocol = response
+                .headers()
+                .get("Sec-WebSocket-Protocol")
+                .and_then(|value| value.to_str().ok())
+                .map(String::from);
+            Ok((socket, protocol))
+        }
+        Err(HandshakeError::Failure(tungstenite::Error::Http(response))) => {
+            // The API server refused the upgrade, e.g. 403 or 404: report it as any API error.
+            let status = response
+                .body()
+                .as_ref()
+                .and_then(|body| serde_json::from_slice::<Status>(body).ok());
+            Err(KubernetesError::ApiError {
+                verb: Method::GET,
+                url,
+                status_code: StatusCode::from_u16(response.status().as_u16())
+                    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+                status: status.map(Box::new),
+            })
+        }
+        Err(HandshakeError::Failure(source)) => Err(websocket_error(source)),
+        Err(HandshakeError::Interrupted(_)) => Err(build_error(String::from(
+            "WebSocket handshake interrupted.",
+        ))),
+    }
+}
+
+fn tls_connect(
+    endpoint: &Endpoint,
+    host: &str,
+    tcp: TcpStream,
+) -> Result<SslStream<TcpStream>, KubernetesError> {
+    let ssl_error = |err: openssl::error::ErrorStack| build_error(format!("TLS error: {}", err));
+    let mut builder = SslConnector::builder(SslMethod::tls()).map_err(ssl_error)?;
+    builder.set_verify(SslVerifyMode::NONE);
+    if let Credentials::ClientCertificate { certificate, key } = &endpoint.credentials {
+        let certificate = X509::from_pem(certificate).map_err(ssl_error)?;
+        let key = PKey::private_key_from_pem(key).map_err(ssl_error)?;
+        builder.set_certificate(&certificate).map_err(ssl_error)?;
+        builder.set_private_key(&key).map_err(ssl_error)?;
+    }
+    builder
+        .build()
+        .configure()
+        .map_err(ssl_error)?
+        .verify_hostname(false)
+        .connect(host, tcp)
+        .map_err(|err| build_error(format!("TLS handshake failed: {}", err)))
+}
+
+/// Runs `socket` in a background thread until either side closes it.
+///
+/// Each message received is passed to `on_frame` with its channel number. Frames
+/// sent through `outgoing`, channel number included, are written as they come;
+/// dropping every sender of `outgoing` closes the socket.
+pub(crate) fn spawn<F>(
+    mut socket: WebSocket<Stream>,
+    outgoing: Receiver<Vec<u8>>,
+    mut on_frame: F,
+) -> Result<JoinHandle<Result<(), KubernetesError>>, KubernetesError>
+where
+    F: FnMut(u8, &[u8]) + Send + 'static,
+{
+    socket
+        .get_ref()
+        .tcp()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(|source| KubernetesError::IoError { source })?;
+    Ok(thread::spawn(move || {
+        let mut closing = false;
+        loop {
+            while !closing {
+                match outgoing.try_recv() {
+                    Ok(frame) => socket
+                        .send(Message::Binary(frame))
+                        .map_err(websocket_error)?,
+                    Err(TryRecvError::Disconnected) => {
+                        closing = true;
+                        match socket.close(None) {
+                            Ok(()) | Err(tungstenite::Error::ConnectionClosed) => {}
+                            Err(err) => return Err(websocket_error(err)),
+                        }
+                    }
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+            match socket.read() {
+                Ok(Message::Binary(data)) => {
+                    if let Some((channel, payload)) = data.split_first() {
+                        on_frame(*channel, payload);
+                    }
+                }
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(err))
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut => {}
+                Err(tungstenite::Error::ConnectionClosed)
+                | Err(tungstenite::Error::AlreadyClosed) => return Ok(()),
+                // Some servers drop the connection right after their close frame.
+                Err(_) if closing => return Ok(()),
+                Err(err) => return Err(websocket_error(err)),
+            }
+        }
+    }))
+}
+
+/// Reads what the background thread receives on one channel.
+pub(crate) struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl ChannelReader {
+    pub(crate) fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        ChannelReader {
+            receiver,
+            buffer: vec![],
+            position: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.buffer.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.buffer = chunk;
+                    self.position = 0;
+                }
+                // The connection is closed: end of stream.
+                Err(_) => return Ok(0),
+            }
+        }
+        let read = buf.len().min(self.buffer.len() - self.position);
+        buf[..read].copy_from_slice(&self.buffer[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// Writes to one channel through the background thread.
+pub(crate) struct ChannelWriter {
+    channel: u8,
+    outgoing: Sender<Vec<u8>>,
+}
+
+impl ChannelWriter {
+    pub(crate) fn new(channel: u8, outgoing: Sender<Vec<u8>>) -> Self {
+        ChannelWriter { channel, outgoing }
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut frame = Vec::with_capacity(buf.len() + 1);
+        frame.push(self.channel);
+        frame.extend_from_slice(buf);
+        self.outgoing
+            .send(frame)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "connection closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
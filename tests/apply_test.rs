@@ -0,0 +1,74 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_sync::apply::ApplyOptions;
+use k8s_sync::errors::KubernetesError;
+use k8s_sync::retry::RetryPolicy;
+
+const APPLIED: &str = r#"{"apiVersion":"v1","kind":"ConfigMap","metadata":{"name":"settings","namespace":"default","resourceVersion":"7"},"data":{"mode":"fast","owner":"someone"}}"#;
+
+fn settings() -> ConfigMap {
+    ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(String::from("settings")),
+            ..Default::default()
+        },
+        data: Some([(String::from("mode"), String::from("fast"))].into()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn apply_sends_apply_patch() {
+    let server = StandIn::start(vec![Reply::new(200, APPLIED)]);
+    let client = server.client();
+    let applied = client
+        .api::<ConfigMap>(Some("default"))
+        .apply(
+            "settings",
+            &settings(),
+            &ApplyOptions::new("deployer").force().dry_run(),
+        )
+        .unwrap();
+    assert_eq!(applied.data.unwrap()["owner"], "someone");
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method, "PATCH");
+    assert_eq!(
+        request.path,
+        "/api/v1/namespaces/default/configmaps/settings?dryRun=All&fieldManager=deployer&force=true"
+    );
+    assert_eq!(
+        request.header("Content-Type"),
+        Some("application/apply-patch+yaml")
+    );
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["apiVersion"], "v1");
+    assert_eq!(body["kind"], "ConfigMap");
+    assert_eq!(body["data"]["mode"], "fast");
+}
+
+#[test]
+fn apply_conflicts_are_structured() {
+    let server = StandIn::start(vec![Reply::new(
+        409,
+        r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"Apply failed with 1 conflict: conflict with \"kubectl-edit\": .data.mode","reason":"Conflict","details":{"causes":[{"reason":"FieldManagerConflict","message":"conflict with \"kubectl-edit\"","field":".data.mode"}]},"code":409}"#,
+    )]);
+    let client = server.client().with_retry_policy(RetryPolicy::none());
+    let err = client
+        .api::<ConfigMap>(Some("default"))
+        .apply("settings", &settings(), &ApplyOptions::new("deployer"))
+        .unwrap_err();
+    assert!(matches!(err, KubernetesError::ApplyConflictError { .. }));
+    assert!(err.is_conflict());
+    let conflicts = err.field_conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].manager, "kubectl-edit");
+    assert_eq!(conflicts[0].field, ".data.mode");
+    assert!(err.to_string().contains(".data.mode (kubectl-edit)"));
+    assert!(server.requests()[0]
+        .path
+        .ends_with("fieldManager=deployer&force=false"));
+}
@@ -0,0 +1,73 @@
+use k8s_openapi::api::core::v1::Pod;
+use k8s_sync::cgroup::{ProcRoot, QosClass};
+use k8s_sync::containers::ContainerIndex;
+
+/// Fixture tree: 4242 runs in a burstable pod (cgroup v2, systemd driver), 4343 in
+/// a best effort one (cgroup v1, cgroupfs driver), 4444 in the sandbox of the
+/// first pod and 4545 outside of pods.
+const PROC: &str = "tests/fixtures/proc";
+
+fn pods() -> Vec<Pod> {
+    serde_json::from_str(
+        r#"[
+        {"metadata":{"name":"web-1","namespace":"shop","uid":"0f3a9c2e-5b1d-4e8f-9a7c-2d6b8e1f4a3c"},
+         "status":{"qosClass":"Burstable","containerStatuses":[{"name":"web","image":"web","imageID":"","ready":true,"restartCount":0,
+           "containerID":"containerd://7b4c1e9a2f5d8c3b6a0e1f4d7c2b5a8e9f3c6d1b4a7e0f2c5b8d1a4e7c0f3b6a"}]}},
+        {"metadata":{"name":"batch-1","namespace":"jobs","uid":"9d2e4f6a-8b0c-4d1e-a3f5-6b7c8d9e0f1a"},
+         "status":{"qosClass":"BestEffort","containerStatuses":[{"name":"batch","image":"batch","imageID":"","ready":true,"restartCount":0,
+           "containerID":"docker://2e5f8a1b4c7d0e3f6a9b2c5d8e1f4a7b0c3d6e9f2a5b8c1d4e7f0a3b6c9d2e5f"}]}}
+    ]"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn processes_to_containers() {
+    let proc_root = ProcRoot::new(PROC);
+    let pods = pods();
+    let index = ContainerIndex::from_pods(&pods);
+
+    let web = proc_root.container_of(4242, &index).unwrap().unwrap();
+    assert_eq!(
+        (web.pod_name.as_str(), web.container_name.as_str()),
+        ("web-1", "web")
+    );
+    let batch = proc_root.container_of(4343, &index).unwrap().unwrap();
+    assert_eq!(
+        (batch.namespace.as_str(), batch.pod_name.as_str()),
+        ("jobs", "batch-1")
+    );
+
+    let cgroup = proc_root.pod_cgroup(4343).unwrap().unwrap();
+    assert_eq!(cgroup.qos_class, QosClass::BestEffort);
+    assert_eq!(
+        cgroup
+            .find_pod(&pods)
+            .unwrap()
+            .status
+            .as_ref()
+            .unwrap()
+            .qos_class
+            .as_deref(),
+        Some(cgroup.qos_class.as_str())
+    );
+}
+
+#[test]
+fn sandbox_and_host_processes() {
+    let proc_root = ProcRoot::new(PROC);
+    let pods = pods();
+    let index = ContainerIndex::from_pods(&pods);
+
+    // The pause container isn't in the pod status, but the pod is found.
+    let sandbox = proc_root.pod_cgroup(4444).unwrap().unwrap();
+    assert_eq!(sandbox.qos_class, QosClass::Burstable);
+    assert!(sandbox.find_container(&index).is_none());
+    assert_eq!(
+        sandbox.find_pod(&pods).unwrap().metadata.name.as_deref(),
+        Some("web-1")
+    );
+
+    assert_eq!(proc_root.pod_cgroup(4545).unwrap(), None);
+    assert!(proc_root.pod_cgroup(1).is_err());
+}
@@ -0,0 +1,182 @@
+//! Local stand-in for the API server, answering with scripted responses.
+#![allow(dead_code)]
+
+use k8s_sync::kubernetes::Kubernetes;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A scripted answer, sent back to the client for one connection.
+#[derive(Clone)]
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub delay: Duration,
+    /// When not empty, the body is sent chunked, each chunk after its delay.
+    pub chunks: Vec<(Duration, Vec<u8>)>,
+}
+
+impl Reply {
+    pub fn new(status: u16, body: &str) -> Self {
+        Reply {
+            status,
+            headers: vec![(
+                String::from("Content-Type"),
+                String::from("application/json"),
+            )],
+            body: body.as_bytes().to_vec(),
+            delay: Duration::from_secs(0),
+            chunks: vec![],
+        }
+    }
+
+    /// A chunked answer, each chunk being sent after its delay.
+    pub fn streamed(status: u16, chunks: Vec<(Duration, &str)>) -> Self {
+        let mut reply = Reply::new(status, "");
+        reply.chunks = chunks
+            .into_iter()
+            .map(|(delay, chunk)| (delay, chunk.as_bytes().to_vec()))
+            .collect();
+        reply
+    }
+
+    /// Waits for `delay` before answering.
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// A request as received by the stand-in.
+#[derive(Clone, Debug)]
+pub struct Recorded {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Recorded {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+pub struct StandIn {
+    pub port: u16,
+    requests: Arc<Mutex<Vec<Recorded>>>,
+}
+
+impl StandIn {
+    /// Serves each reply, in order, to one incoming connection.
+    pub fn start(replies: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for reply in replies {
+                let (stream, _) = match listener.accept() {
+                    Ok(conn) => conn,
+                    Err(_) => return,
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut writer = stream;
+                let request = match read_request(&mut reader, &mut writer) {
+                    Some(request) => request,
+                    None => continue,
+                };
+                recorded.lock().unwrap().push(request);
+                thread::sleep(reply.delay);
+                let mut head = format!("HTTP/1.1 {} Scripted\r\n", reply.status);
+                for (name, value) in &reply.headers {
+                    head.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                if reply.chunks.is_empty() {
+                    head.push_str(&format!(
+                        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                        reply.body.len()
+                    ));
+                    let _ = writer.write_all(head.as_bytes());
+                    let _ = writer.write_all(&reply.body);
+                } else {
+                    head.push_str("Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n");
+                    let _ = writer.write_all(head.as_bytes());
+                    let _ = writer.flush();
+                    for (delay, chunk) in &reply.chunks {
+                        thread::sleep(*delay);
+                        let _ = writer.write_all(format!("{:x}\r\n", chunk.len()).as_bytes());
+                        let _ = writer.write_all(chunk);
+                        let _ = writer.write_all(b"\r\n");
+                        let _ = writer.flush();
+                    }
+                    let _ = writer.write_all(b"0\r\n\r\n");
+                }
+                let _ = writer.flush();
+            }
+        });
+        StandIn { port, requests }
+    }
+
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// A client pointed at the stand-in, over plain HTTP.
+    pub fn client(&self) -> Kubernetes {
+        Kubernetes::connect(
+            Some(String::from("tests/fixtures/kubeconfig")),
+            Some(String::from("http")),
+            Some(String::from("127.0.0.1")),
+            Some(self.port as u32),
+            false,
+        )
+        .unwrap()
+    }
+}
+
+fn read_request<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> Option<Recorded> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+    let mut headers = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Recorded {
+        method,
+        path,
+        headers,
+        body: vec![],
+    };
+    if request.header("Expect") == Some("100-continue") {
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").ok()?;
+    }
+    if let Some(length) = request.header("Content-Length") {
+        let mut body = vec![0u8; length.parse().ok()?];
+        reader.read_exact(&mut body).ok()?;
+        request.body = body;
+    }
+    Some(request)
+}
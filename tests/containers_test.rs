@@ -0,0 +1,47 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_openapi::api::core::v1::Pod;
+use k8s_sync::containers::ContainerKind;
+use std::time::Duration;
+
+const PODS: &str = r#"{"apiVersion":"v1","kind":"PodList","metadata":{"resourceVersion":"20"},"items":[
+    {"metadata":{"name":"web-1","namespace":"shop","uid":"u1","labels":{"app":"web"}},"status":{"containerStatuses":[{"name":"web","image":"web","imageID":"","ready":true,"restartCount":0,"containerID":"containerd://c1"}]}}
+]}"#;
+const RESTARTED: &str = r#"{"type":"MODIFIED","object":{"metadata":{"name":"web-1","namespace":"shop","uid":"u1","labels":{"app":"web"}},"status":{"containerStatuses":[{"name":"web","image":"web","imageID":"","ready":true,"restartCount":1,"containerID":"containerd://c2","lastState":{"terminated":{"exitCode":137,"containerID":"containerd://c1"}}}]}}}
+"#;
+
+#[test]
+fn container_index_follows_watch() {
+    let server = StandIn::start(vec![
+        Reply::new(200, PODS),
+        Reply::streamed(200, vec![(Duration::from_millis(10), RESTARTED)]),
+    ]);
+    let client = server.client();
+    let mut index = client
+        .container_index(Some(String::from("node-1")))
+        .unwrap();
+    let web = index.get("containerd://c1").unwrap();
+    assert_eq!(web.pod_name, "web-1");
+    assert_eq!(web.kind, ContainerKind::Regular);
+
+    let events = client
+        .api::<Pod>(None)
+        .watch(k8s_openapi::WatchOptional {
+            field_selector: Some("spec.nodeName=node-1"),
+            resource_version: Some("20"),
+            ..Default::default()
+        })
+        .unwrap();
+    for event in events {
+        index.apply_event(&event.unwrap());
+    }
+    assert!(index.get("c1").unwrap().terminated);
+    assert_eq!(index.get("c2").unwrap().container_name, "web");
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].path,
+        "/api/v1/pods?fieldSelector=spec.nodeName%3Dnode-1"
+    );
+}
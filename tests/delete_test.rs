@@ -0,0 +1,125 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_openapi::api::core::v1::{Namespace, Pod};
+use k8s_sync::delete::{DeleteOptions, PropagationPolicy};
+use k8s_sync::retry::RetryPolicy;
+use std::time::Duration;
+
+const TERMINATING: &str = r#"{"apiVersion":"v1","kind":"Namespace","metadata":{"name":"e2e","uid":"a1","finalizers":["kubernetes"],"deletionTimestamp":"2021-08-02T10:00:00Z"},"status":{"phase":"Terminating"}}"#;
+const NOT_FOUND: &str = r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"namespaces \"e2e\" not found","reason":"NotFound","code":404}"#;
+
+#[test]
+fn delete_sends_options() {
+    let server = StandIn::start(vec![Reply::new(200, TERMINATING)]);
+    let client = server.client();
+    let namespace = client
+        .api::<Namespace>(None)
+        .delete(
+            "e2e",
+            &DeleteOptions {
+                propagation_policy: Some(PropagationPolicy::Foreground),
+                grace_period_seconds: Some(30),
+                uid: Some(String::from("a1")),
+                resource_version: Some(String::from("12")),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(namespace.metadata.finalizers.unwrap(), vec!["kubernetes"]);
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method, "DELETE");
+    assert_eq!(request.path, "/api/v1/namespaces/e2e");
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["kind"], "DeleteOptions");
+    assert_eq!(body["propagationPolicy"], "Foreground");
+    assert_eq!(body["gracePeriodSeconds"], 30);
+    assert_eq!(body["preconditions"]["uid"], "a1");
+    assert_eq!(body["preconditions"]["resourceVersion"], "12");
+}
+
+#[test]
+fn delete_waits_until_gone() {
+    let server = StandIn::start(vec![
+        Reply::new(200, TERMINATING),
+        Reply::new(200, TERMINATING),
+        Reply::new(404, NOT_FOUND),
+    ]);
+    let client = server.client();
+    let deleted = client
+        .api::<Namespace>(None)
+        .delete(
+            "e2e",
+            &DeleteOptions {
+                wait: Some(Duration::from_secs(5)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert!(deleted.is_none());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[2].method, "GET");
+}
+
+#[test]
+fn recreated_object_counts_as_deleted() {
+    let recreated = TERMINATING.replace(r#""uid":"a1""#, r#""uid":"b2""#);
+    let server = StandIn::start(vec![Reply::new(200, &recreated)]);
+    server
+        .client()
+        .api::<Namespace>(None)
+        .wait_deleted("e2e", Some("a1"), Duration::from_secs(5))
+        .unwrap();
+}
+
+#[test]
+fn wait_times_out() {
+    let server = StandIn::start(vec![Reply::new(200, TERMINATING); 10]);
+    let client = server.client().with_retry_policy(RetryPolicy::none());
+    let err = client
+        .api::<Namespace>(None)
+        .wait_deleted("e2e", None, Duration::from_millis(300))
+        .unwrap_err();
+    assert!(err.is_timeout());
+    assert!(err.to_string().contains("deletion of namespaces e2e"));
+}
+
+#[test]
+fn delete_collection_with_selector() {
+    let server = StandIn::start(vec![
+        Reply::new(
+            200,
+            r#"{"apiVersion":"v1","kind":"PodList","metadata":{},"items":[{"metadata":{"name":"job-1"}},{"metadata":{"name":"job-2"}}]}"#,
+        ),
+        Reply::new(
+            200,
+            r#"{"apiVersion":"v1","kind":"PodList","metadata":{},"items":[]}"#,
+        ),
+    ]);
+    let client = server.client();
+    let deleted = client
+        .api::<Pod>(Some("e2e"))
+        .delete_collection(
+            k8s_sync::ListOptional {
+                label_selector: Some("job=cleanup"),
+                ..Default::default()
+            },
+            &DeleteOptions {
+                wait: Some(Duration::from_secs(5)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(deleted.len(), 2);
+    let requests = server.requests();
+    assert_eq!(requests[0].method, "DELETE");
+    assert_eq!(
+        requests[0].path,
+        "/api/v1/namespaces/e2e/pods?labelSelector=job%3Dcleanup"
+    );
+    assert_eq!(requests[1].method, "GET");
+    assert_eq!(requests[1].path, requests[0].path);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::discovery::DiscoveryCache;
+use k8s_sync::retry::RetryPolicy;
+use std::time::Duration;
+
+const API_VERSIONS: &str =
+    r#"{"kind":"APIVersions","versions":["v1"],"serverAddressByClientCIDRs":[]}"#;
+const CORE_RESOURCES: &str = r#"{"kind":"APIResourceList","apiVersion":"v1","groupVersion":"v1","resources":[{"name":"pods","singularName":"","namespaced":true,"kind":"Pod","verbs":["get","list","watch","delete"],"shortNames":["po"]},{"name":"pods/log","singularName":"","namespaced":true,"kind":"Pod","verbs":["get"]},{"name":"nodes","singularName":"","namespaced":false,"kind":"Node","verbs":["get","list"],"shortNames":["no"]}]}"#;
+const API_GROUPS: &str = r#"{"kind":"APIGroupList","apiVersion":"v1","groups":[{"name":"stable.example.com","versions":[{"groupVersion":"stable.example.com/v2","version":"v2"},{"groupVersion":"stable.example.com/v1","version":"v1"}],"preferredVersion":{"groupVersion":"stable.example.com/v2","version":"v2"}}]}"#;
+const CRONTABS_V2: &str = r#"{"kind":"APIResourceList","apiVersion":"v1","groupVersion":"stable.example.com/v2","resources":[{"name":"crontabs","singularName":"crontab","namespaced":true,"kind":"CronTab","verbs":["get","list","patch"],"shortNames":["ct"]}]}"#;
+const CRONTABS_V1: &str = r#"{"kind":"APIResourceList","apiVersion":"v1","groupVersion":"stable.example.com/v1","resources":[{"name":"crontabs","singularName":"crontab","namespaced":true,"kind":"CronTab","verbs":["get","list"]}]}"#;
+
+#[test]
+fn legacy_discovery_and_cache() {
+    let server = StandIn::start(vec![
+        Reply::new(200, API_VERSIONS),
+        Reply::new(200, CORE_RESOURCES),
+        Reply::new(200, API_GROUPS),
+        Reply::new(200, CRONTABS_V2),
+        Reply::new(200, CRONTABS_V1),
+    ]);
+    let cache_dir = tempfile::tempdir().unwrap();
+    let client = server
+        .client()
+        .with_retry_policy(RetryPolicy::none())
+        .with_discovery_cache(Some(DiscoveryCache::new(
+            cache_dir.path(),
+            Duration::from_secs(60),
+        )));
+
+    let discovery = client.discovery().unwrap();
+    let pods = discovery.resolve("v1", "Pod").unwrap();
+    assert!(pods.namespaced);
+    assert_eq!(pods.subresources, vec!["log"]);
+    assert!(!discovery.resolve("v1", "Node").unwrap().namespaced);
+    assert_eq!(
+        discovery.preferred_version("stable.example.com"),
+        Some("v2")
+    );
+    let crontabs = discovery
+        .resolve_preferred("stable.example.com", "CronTab")
+        .unwrap();
+    assert_eq!(crontabs.version, "v2");
+    assert!(crontabs.supports("patch"));
+    assert!(!discovery
+        .resolve("stable.example.com/v1", "CronTab")
+        .unwrap()
+        .supports("patch"));
+    assert_eq!(discovery.resolve_name("ct"), Some(crontabs));
+
+    let requests = server.requests();
+    let paths: Vec<_> = requests.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/api",
+            "/api/v1",
+            "/apis",
+            "/apis/stable.example.com/v2",
+            "/apis/stable.example.com/v1"
+        ]
+    );
+    assert!(requests[0]
+        .header("Accept")
+        .unwrap()
+        .contains("as=APIGroupDiscoveryList"));
+
+    let host_dir = cache_dir.path().join(format!("127.0.0.1_{}", server.port));
+    assert!(host_dir.join("servergroups.json").exists());
+    assert!(host_dir
+        .join("stable.example.com/v2/serverresources.json")
+        .exists());
+
+    // The stand-in is done answering: this comes from the cache.
+    assert_eq!(client.discovery().unwrap(), discovery);
+}
+
+#[test]
+fn unwritable_cache() {
+    let server = StandIn::start(vec![
+        Reply::new(200, API_VERSIONS),
+        Reply::new(200, CORE_RESOURCES),
+        Reply::new(
+            200,
+            r#"{"kind":"APIGroupList","apiVersion":"v1","groups":[]}"#,
+        ),
+    ]);
+    // A directory can't be created under a file, even by root.
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let client = server
+        .client()
+        .with_retry_policy(RetryPolicy::none())
+        .with_discovery_cache(Some(DiscoveryCache::new(
+            file.path().join("discovery"),
+            Duration::from_secs(60),
+        )));
+
+    let discovery = client.refresh_discovery().unwrap();
+    assert!(discovery.resolve("v1", "Pod").unwrap().namespaced);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn failing_group_version_is_skipped() {
+    let groups = r#"{"kind":"APIGroupList","apiVersion":"v1","groups":[{"name":"metrics.k8s.io","versions":[{"groupVersion":"metrics.k8s.io/v1beta1","version":"v1beta1"}],"preferredVersion":{"groupVersion":"metrics.k8s.io/v1beta1","version":"v1beta1"}}]}"#;
+    let server = StandIn::start(vec![
+        Reply::new(200, API_VERSIONS),
+        Reply::new(200, CORE_RESOURCES),
+        Reply::new(200, groups),
+        Reply::new(
+            503,
+            r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"service unavailable","reason":"ServiceUnavailable","code":503}"#,
+        ),
+    ]);
+    let cache_dir = tempfile::tempdir().unwrap();
+    let client = server
+        .client()
+        .with_retry_policy(RetryPolicy::none())
+        .with_discovery_cache(Some(DiscoveryCache::new(
+            cache_dir.path(),
+            Duration::from_secs(60),
+        )));
+
+    let discovery = client.discovery().unwrap();
+    assert!(discovery.resolve("v1", "Pod").is_some());
+    assert_eq!(discovery.failed_groups.len(), 1);
+    let failed = discovery.failed("metrics.k8s.io/v1beta1").unwrap();
+    assert!(failed.message.contains("503"), "{}", failed.message);
+    assert_eq!(discovery.failed("v1"), None);
+    // Incomplete documents aren't cached.
+    assert!(!cache_dir
+        .path()
+        .join(format!("127.0.0.1_{}", server.port))
+        .join("servergroups.json")
+        .exists());
+}
+
+#[test]
+fn aggregated_discovery() {
+    let core = r#"{"kind":"APIGroupDiscoveryList","apiVersion":"apidiscovery.k8s.io/v2","metadata":{},"items":[{"metadata":{"creationTimestamp":null},"versions":[{"version":"v1","resources":[{"resource":"pods","responseKind":{"group":"","version":"v1","kind":"Pod"},"scope":"Namespaced","singularResource":"pod","verbs":["get","list"],"subresources":[{"subresource":"eviction","responseKind":{"group":"policy","version":"v1","kind":"Eviction"},"verbs":["create"]}]}]}]}]}"#;
+    let groups = r#"{"kind":"APIGroupDiscoveryList","apiVersion":"apidiscovery.k8s.io/v2","metadata":{},"items":[{"metadata":{"name":"apps","creationTimestamp":null},"versions":[{"version":"v1","resources":[{"resource":"deployments","responseKind":{"group":"apps","version":"v1","kind":"Deployment"},"scope":"Namespaced","singularResource":"deployment","shortNames":["deploy"],"verbs":["get","list","patch"]}]}]}]}"#;
+    let server = StandIn::start(vec![Reply::new(200, core), Reply::new(200, groups)]);
+    let client = server.client().with_discovery_cache(None);
+
+    let discovery = client.discovery().unwrap();
+    assert_eq!(
+        discovery.resolve("v1", "Pod").unwrap().subresources,
+        vec!["eviction"]
+    );
+    let deployments = discovery.resolve_name("deploy").unwrap();
+    assert_eq!(deployments.api_version(), "apps/v1");
+    assert_eq!(server.requests().len(), 2);
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::drain::DrainOptions;
+use k8s_sync::errors::KubernetesError;
+use std::time::Duration;
+
+const NODE: &str = r#"{"apiVersion":"v1","kind":"Node","metadata":{"name":"node-1"},"spec":{"unschedulable":true}}"#;
+const PODS: &str = r#"{"apiVersion":"v1","kind":"PodList","metadata":{},"items":[
+    {"metadata":{"name":"web-1","namespace":"default","uid":"u1","ownerReferences":[{"apiVersion":"apps/v1","kind":"ReplicaSet","name":"web","uid":"r1","controller":true}]}},
+    {"metadata":{"name":"fluentd-x","namespace":"logging","uid":"u2","ownerReferences":[{"apiVersion":"apps/v1","kind":"DaemonSet","name":"fluentd","uid":"d1","controller":true}]}},
+    {"metadata":{"name":"etcd-node-1","namespace":"kube-system","uid":"u3","annotations":{"kubernetes.io/config.mirror":"abc"}}}
+]}"#;
+const POLICY: &str = r#"{"kind":"APIGroup","apiVersion":"v1","name":"policy","versions":[{"groupVersion":"policy/v1","version":"v1"},{"groupVersion":"policy/v1beta1","version":"v1beta1"}],"preferredVersion":{"groupVersion":"policy/v1","version":"v1"}}"#;
+const PDB_REFUSED: &str = r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"Cannot evict pod as it would violate the pod's disruption budget.","reason":"TooManyRequests","code":429}"#;
+const NOT_FOUND: &str = r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","reason":"NotFound","code":404}"#;
+
+#[test]
+fn drain_evicts_and_waits() {
+    let server = StandIn::start(vec![
+        Reply::new(200, NODE),
+        Reply::new(200, PODS),
+        Reply::new(200, POLICY),
+        Reply::new(429, PDB_REFUSED),
+        Reply::new(
+            201,
+            r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Success"}"#,
+        ),
+        Reply::new(404, NOT_FOUND),
+    ]);
+    let report = server
+        .client()
+        .drain(
+            "node-1",
+            &DrainOptions {
+                ignore_daemonsets: true,
+                timeout: Some(Duration::from_secs(10)),
+                eviction_retry_interval: Duration::from_millis(10),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(report.evicted, vec!["default/web-1"]);
+    assert_eq!(
+        report.skipped,
+        vec!["logging/fluentd-x", "kube-system/etcd-node-1"]
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method, "PATCH");
+    assert_eq!(requests[0].path, "/api/v1/nodes/node-1");
+    assert_eq!(
+        requests[0].header("Content-Type"),
+        Some("application/strategic-merge-patch+json")
+    );
+    assert_eq!(
+        requests[1].path,
+        "/api/v1/pods?fieldSelector=spec.nodeName%3Dnode-1"
+    );
+    assert_eq!(requests[3].method, "POST");
+    assert_eq!(
+        requests[3].path,
+        "/api/v1/namespaces/default/pods/web-1/eviction"
+    );
+    let eviction: serde_json::Value = serde_json::from_slice(&requests[3].body).unwrap();
+    assert_eq!(eviction["apiVersion"], "policy/v1");
+    assert_eq!(eviction["metadata"]["name"], "web-1");
+    assert_eq!(requests[4].path, requests[3].path);
+    assert_eq!(requests[5].path, "/api/v1/namespaces/default/pods/web-1");
+}
+
+#[test]
+fn drain_refuses_daemonsets_by_default() {
+    let server = StandIn::start(vec![Reply::new(200, NODE), Reply::new(200, PODS)]);
+    let err = server
+        .client()
+        .drain("node-1", &DrainOptions::default())
+        .unwrap_err();
+    match err {
+        KubernetesError::DrainError { node, blocked } => {
+            assert_eq!(node, "node-1");
+            assert_eq!(
+                blocked,
+                vec!["logging/fluentd-x: managed by a DaemonSet (use ignore_daemonsets)"]
+            );
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    // Nothing evicted.
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn drain_dry_run_does_not_wait() {
+    let server = StandIn::start(vec![
+        Reply::new(200, NODE),
+        Reply::new(200, PODS),
+        Reply::new(200, POLICY),
+        Reply::new(
+            201,
+            r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Success"}"#,
+        ),
+    ]);
+    let report = server
+        .client()
+        .drain(
+            "node-1",
+            &DrainOptions {
+                ignore_daemonsets: true,
+                dry_run: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(report.evicted, vec!["default/web-1"]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[0].path, "/api/v1/nodes/node-1?dryRun=All");
+    let eviction: serde_json::Value = serde_json::from_slice(&requests[3].body).unwrap();
+    assert_eq!(eviction["deleteOptions"]["dryRun"][0], "All");
+}
+
+#[test]
+fn drain_with_unbounded_timeout() {
+    let server = StandIn::start(vec![
+        Reply::new(200, NODE),
+        Reply::new(200, PODS),
+        Reply::new(200, POLICY),
+        Reply::new(
+            201,
+            r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Success"}"#,
+        ),
+        Reply::new(404, NOT_FOUND),
+    ]);
+    let report = server
+        .client()
+        .drain(
+            "node-1",
+            &DrainOptions {
+                ignore_daemonsets: true,
+                timeout: Some(Duration::MAX),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(report.evicted, vec!["default/web-1"]);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::WatchEvent;
+use k8s_sync::dynamic::{DynamicObject, GroupVersionResource};
+use k8s_sync::patch::{JsonPatch, Patch};
+
+const CERTIFICATE: &str = r#"{"apiVersion":"cert-manager.io/v1","kind":"Certificate","metadata":{"name":"web","namespace":"default","resourceVersion":"42"},"spec":{"secretName":"web-tls"},"status":{"conditions":[{"type":"Ready","status":"True"}]}}"#;
+
+fn certificates() -> GroupVersionResource {
+    GroupVersionResource::new("cert-manager.io", "v1", "certificates")
+}
+
+#[test]
+fn get_and_list_custom_resources() {
+    let list = format!(
+        r#"{{"apiVersion":"cert-manager.io/v1","kind":"CertificateList","metadata":{{"resourceVersion":"43"}},"items":[{}]}}"#,
+        CERTIFICATE
+    );
+    let server = StandIn::start(vec![Reply::new(200, CERTIFICATE), Reply::new(200, &list)]);
+    let client = server.client();
+    let api = client.dynamic(certificates(), Some("default"));
+
+    let certificate = api.get("web").unwrap();
+    assert_eq!(certificate.resource_version(), Some("42"));
+    assert_eq!(certificate.pointer("/spec/secretName").unwrap(), "web-tls");
+
+    let list = api
+        .list(k8s_sync::ListOptional {
+            label_selector: Some("app=web"),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(list.metadata.resource_version.as_deref(), Some("43"));
+    assert_eq!(list.items.len(), 1);
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].path,
+        "/apis/cert-manager.io/v1/namespaces/default/certificates/web"
+    );
+    assert_eq!(
+        requests[1].path,
+        "/apis/cert-manager.io/v1/namespaces/default/certificates?labelSelector=app%3Dweb"
+    );
+}
+
+#[test]
+fn create_patch_and_delete() {
+    let server = StandIn::start(vec![
+        Reply::new(201, CERTIFICATE),
+        Reply::new(200, CERTIFICATE),
+        Reply::new(
+            200,
+            r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Success"}"#,
+        ),
+    ]);
+    let client = server.client();
+    let api = client.dynamic(certificates(), Some("default"));
+
+    let mut certificate = DynamicObject::new("cert-manager.io/v1", "Certificate", "web");
+    certificate.data = serde_json::json!({"spec": {"secretName": "web-tls"}});
+    api.create(
+        &certificate,
+        k8s_openapi::CreateOptional {
+            field_manager: Some("tests"),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let patch = Patch::Merge(serde_json::json!({"spec": {"secretName": "other"}}));
+    api.patch("web", &patch, Default::default()).unwrap();
+
+    assert!(api.delete("web", &Default::default()).unwrap().is_none());
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method, "POST");
+    assert!(requests[0]
+        .path
+        .ends_with("/certificates?fieldManager=tests"));
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["spec"]["secretName"], "web-tls");
+    assert_eq!(body["metadata"]["name"], "web");
+    assert_eq!(requests[1].method, "PATCH");
+    assert_eq!(
+        requests[1].header("Content-Type"),
+        Some("application/merge-patch+json")
+    );
+    assert_eq!(requests[2].method, "DELETE");
+}
+
+#[test]
+fn typed_api() {
+    let server = StandIn::start(vec![Reply::new(
+        200,
+        r#"{"apiVersion":"v1","kind":"ConfigMap","metadata":{"name":"conf","namespace":"default"},"data":{"key":"value"}}"#,
+    )]);
+    let client = server.client();
+    let config_map = client
+        .api::<ConfigMap>(Some("default"))
+        .get("conf")
+        .unwrap();
+    assert_eq!(config_map.data.unwrap()["key"], "value");
+    assert_eq!(
+        server.requests()[0].path,
+        "/api/v1/namespaces/default/configmaps/conf"
+    );
+}
+
+#[test]
+fn watch_custom_resources() {
+    let events = format!(
+        "{{\"type\":\"ADDED\",\"object\":{}}}\n{{\"type\":\"BOOKMARK\",\"object\":{{\"kind\":\"Certificate\",\"apiVersion\":\"cert-manager.io/v1\",\"metadata\":{{\"resourceVersion\":\"50\"}}}}}}\n{{\"type\":\"DELETED\",\"object\":{}}}\n",
+        CERTIFICATE, CERTIFICATE
+    );
+    let server = StandIn::start(vec![Reply::new(200, &events)]);
+    let client = server.client();
+    let api = client.dynamic(certificates(), None);
+    let events: Vec<_> = api
+        .watch(k8s_openapi::WatchOptional {
+            allow_watch_bookmarks: Some(true),
+            resource_version: Some("42"),
+            ..Default::default()
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(events.len(), 3);
+    match &events[0] {
+        WatchEvent::Added(object) => assert_eq!(object.name(), Some("web")),
+        other => panic!("unexpected event {:?}", other),
+    }
+    match &events[1] {
+        WatchEvent::Bookmark { resource_version } => assert_eq!(resource_version, "50"),
+        other => panic!("unexpected event {:?}", other),
+    }
+    assert_eq!(
+        server.requests()[0].path,
+        "/apis/cert-manager.io/v1/certificates?watch=true&allowWatchBookmarks=true&resourceVersion=42"
+    );
+}
+
+#[test]
+fn json_patch_with_test_operation() {
+    let server = StandIn::start(vec![Reply::new(
+        422,
+        r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"the server rejected our request due to an error in our request","reason":"Invalid","code":422}"#,
+    )]);
+    let client = server.client();
+    let patch = JsonPatch::new()
+        .test("/metadata/labels/tier", serde_json::json!("front"))
+        .replace("/data/key", serde_json::json!("other"));
+    let err = client
+        .api::<ConfigMap>(Some("default"))
+        .patch("conf", &patch.into(), Default::default())
+        .unwrap_err();
+    assert_eq!(err.reason(), Some("Invalid"));
+
+    let request = &server.requests()[0];
+    assert_eq!(
+        request.header("Content-Type"),
+        Some("application/json-patch+json")
+    );
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body[0]["op"], "test");
+    assert_eq!(body[1]["path"], "/data/key");
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{Reply, StandIn};
+
+#[test]
+fn api_error_carries_status() {
+    let server = StandIn::start(vec![Reply::new(
+        404,
+        r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"namespaces \"nope\" not found","reason":"NotFound","details":{"name":"nope","kind":"namespaces"},"code":404}"#,
+    )]);
+    let err = server
+        .client()
+        .list_pods(String::from("nope"), Default::default())
+        .unwrap_err();
+    assert!(err.is_not_found());
+    assert!(!err.is_conflict());
+    assert_eq!(err.reason(), Some("NotFound"));
+    let status = err.status().unwrap();
+    assert_eq!(
+        status.details.as_ref().unwrap().name.as_deref(),
+        Some("nope")
+    );
+    match err {
+        k8s_sync::errors::KubernetesError::ApiError { verb, url, .. } => {
+            assert_eq!(verb, http::Method::GET);
+            assert!(url.ends_with("/api/v1/namespaces/nope/pods?"));
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn api_error_without_status_body() {
+    let server = StandIn::start(vec![Reply::new(403, "forbidden")]);
+    let err = server
+        .client()
+        .list_pods(String::from("default"), Default::default())
+        .unwrap_err();
+    assert!(err.is_forbidden());
+    assert!(err.status().is_none());
+}
+
+#[test]
+fn transport_error_keeps_source() {
+    use std::error::Error;
+    let client = k8s_sync::kubernetes::Kubernetes::connect(
+        Some(String::from("tests/fixtures/kubeconfig")),
+        Some(String::from("http")),
+        Some(String::from("127.0.0.1")),
+        Some(1),
+        false,
+    )
+    .unwrap()
+    .with_retry_policy(k8s_sync::retry::RetryPolicy::none());
+    let err = client
+        .list_pods(String::from("default"), Default::default())
+        .unwrap_err();
+    assert!(err.source().is_some());
+    assert!(err.status_code().is_none());
+}
@@ -0,0 +1,207 @@
+use k8s_sync::exec::ExecOptions;
+use k8s_sync::kubernetes::Kubernetes;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message, WebSocket};
+
+/// Stand-in for the API server side of one exec or attach connection. `script`
+/// runs once the handshake is done, and the path requested is sent on the
+/// returned channel.
+// The handshake callback signature is imposed by tungstenite.
+#[allow(clippy::result_large_err)]
+fn stand_in<F>(protocol: &'static str, script: F) -> (u16, Receiver<String>)
+where
+    F: FnOnce(&mut WebSocket<TcpStream>) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (paths, received) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let callback = |request: &Request, mut response: Response| {
+            let _ = paths.send(request.uri().to_string());
+            let offered = request
+                .headers()
+                .get("Sec-WebSocket-Protocol")
+                .and_then(|p| p.to_str().ok())
+                .unwrap_or_default();
+            if !offered.split(", ").any(|p| p == protocol) {
+                let mut refused = ErrorResponse::new(Some(String::from(
+                    r#"{"kind":"Status","metadata":{},"status":"Failure","message":"upgrade refused","code":400}"#,
+                )));
+                *refused.status_mut() = StatusCode::BAD_REQUEST;
+                return Err(refused);
+            }
+            response
+                .headers_mut()
+                .insert("Sec-WebSocket-Protocol", protocol.parse().unwrap());
+            Ok(response)
+        };
+        let mut socket = match tungstenite::accept_hdr(stream, callback) {
+            Ok(socket) => socket,
+            Err(_) => return,
+        };
+        script(&mut socket);
+        let _ = socket.close(None);
+        // Let the client answer the close frame.
+        while socket.read().is_ok() {}
+    });
+    (port, received)
+}
+
+fn frame(channel: u8, data: &[u8]) -> Message {
+    let mut frame = vec![channel];
+    frame.extend_from_slice(data);
+    Message::Binary(frame)
+}
+
+fn client(port: u16) -> Kubernetes {
+    Kubernetes::connect(
+        Some(String::from("tests/fixtures/kubeconfig")),
+        Some(String::from("http")),
+        Some(String::from("127.0.0.1")),
+        Some(port as u32),
+        false,
+    )
+    .unwrap()
+}
+
+#[test]
+fn exec_reads_output_and_exit_code() {
+    let (port, paths) = stand_in("v4.channel.k8s.io", |socket| {
+        socket.send(frame(1, b"")).unwrap();
+        socket.send(frame(1, b"hello\n")).unwrap();
+        socket.send(frame(2, b"oops\n")).unwrap();
+        socket
+            .send(frame(
+                3,
+                br#"{"metadata":{},"status":"Failure","reason":"NonZeroExitCode","details":{"causes":[{"reason":"ExitCode","message":"3"}]}}"#,
+            ))
+            .unwrap();
+    });
+    let mut process = client(port)
+        .exec(
+            "default",
+            "web-0",
+            &["sh", "-c", "echo hello; echo oops >&2; exit 3"],
+            &ExecOptions {
+                container: Some(String::from("app")),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(process.protocol.as_deref(), Some("v4.channel.k8s.io"));
+    assert!(process.stdin.is_none());
+    let mut stdout = process.stdout.take().unwrap();
+    let mut stderr = process.stderr.take().unwrap();
+    let status = process.wait().unwrap();
+    assert_eq!(status.code, Some(3));
+    assert!(!status.success());
+
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "hello\n");
+    let mut errors = String::new();
+    stderr.read_to_string(&mut errors).unwrap();
+    assert_eq!(errors, "oops\n");
+    assert_eq!(
+        paths.recv().unwrap(),
+        "/api/v1/namespaces/default/pods/web-0/exec?command=sh&command=-c&command=echo+hello%3B+echo+oops+%3E%262%3B+exit+3&container=app&stdout=true&stderr=true"
+    );
+}
+
+#[test]
+fn exec_with_stdin_and_tty() {
+    let (port, _) = stand_in("v5.channel.k8s.io", |socket| {
+        let mut resized = None;
+        loop {
+            let data = match socket.read().unwrap() {
+                Message::Binary(data) => data,
+                _ => continue,
+            };
+            match data[0] {
+                // Echo stdin back on stdout, as a terminal would.
+                0 => socket.send(frame(1, &data[1..])).unwrap(),
+                4 => resized = Some(String::from_utf8(data[1..].to_vec()).unwrap()),
+                255 if data[1] == 0 => break,
+                _ => {}
+            }
+        }
+        let resized = resized.unwrap();
+        assert!(resized.contains(r#""Width":120"#) && resized.contains(r#""Height":40"#));
+        socket
+            .send(frame(3, br#"{"metadata":{},"status":"Success"}"#))
+            .unwrap();
+    });
+    let mut process = client(port)
+        .exec(
+            "default",
+            "web-0",
+            &["cat"],
+            &ExecOptions {
+                stdin: true,
+                tty: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert!(process.stderr.is_none());
+    process.resize(120, 40).unwrap();
+    let mut stdin = process.stdin.take().unwrap();
+    stdin.write_all(b"ping").unwrap();
+    drop(stdin);
+    let mut stdout = process.stdout.take().unwrap();
+    assert!(process.wait().unwrap().success());
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "ping");
+}
+
+#[test]
+fn attach_without_tty_cannot_resize() {
+    let (port, paths) = stand_in("v5.channel.k8s.io", |socket| {
+        socket.send(frame(1, b"log line\n")).unwrap();
+    });
+    let process = client(port)
+        .attach("default", "web-0", &ExecOptions::default())
+        .unwrap();
+    assert!(process.resize(80, 24).is_err());
+    // The stand-in closes the connection without sending a status.
+    let status = process.wait().unwrap();
+    assert_eq!(status.code, None);
+    assert_eq!(status.status, None);
+    assert_eq!(
+        paths.recv().unwrap(),
+        "/api/v1/namespaces/default/pods/web-0/attach?stdout=true&stderr=true"
+    );
+}
+
+#[test]
+fn refused_upgrade_is_an_api_error() {
+    let (port, _) = stand_in("v3.channel.k8s.io", |_| {});
+    let err = client(port)
+        .exec("default", "web-0", &["true"], &ExecOptions::default())
+        .unwrap_err();
+    assert_eq!(err.status_code().map(|s| s.as_u16()), Some(400));
+    assert_eq!(
+        err.status().and_then(|s| s.message.as_deref()),
+        Some("upgrade refused")
+    );
+}
+
+#[test]
+fn pod_name_is_encoded_in_path() {
+    let (port, paths) = stand_in("v5.channel.k8s.io", |_| {});
+    let process = client(port)
+        .attach("default", "web 0/x", &ExecOptions::default())
+        .unwrap();
+    process.wait().unwrap();
+    assert_eq!(
+        paths.recv().unwrap(),
+        "/api/v1/namespaces/default/pods/web%200%2Fx/attach?stdout=true&stderr=true"
+    );
+}
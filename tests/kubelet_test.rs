@@ -0,0 +1,305 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::errors::KubernetesError;
+use k8s_sync::kubelet::metrics::ResourceMetrics;
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::ssl::{SslAcceptor, SslMethod, SslVerifyMode};
+use openssl::x509::{X509NameBuilder, X509};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+const PODS: &str = r#"{"kind":"PodList","apiVersion":"v1","metadata":{},"items":[
+    {"metadata":{"name":"kube-proxy-7xk2p","namespace":"kube-system","uid":"5b1f6d2c-3a4e-4f7b-8c9d-0e1f2a3b4c5d"},
+     "spec":{"nodeName":"node-1","containers":[{"name":"kube-proxy","image":"k8s.gcr.io/kube-proxy:v1.21.3"}]},
+     "status":{"phase":"Running","qosClass":"BestEffort"}},
+    {"metadata":{"name":"etcd-node-1","namespace":"kube-system","uid":"c4a8e2f0b6d14a3e9f7c5b3d1e0a2f4c"},
+     "spec":{"nodeName":"node-1","containers":[{"name":"etcd","image":"k8s.gcr.io/etcd:3.4.13-0"}]},
+     "status":{"phase":"Running","qosClass":"Burstable"}}
+]}"#;
+
+const CONFIGZ: &str = r#"{"kubeletconfig":{"enableServer":true,"staticPodPath":"/etc/kubernetes/manifests","syncFrequency":"1m0s","readOnlyPort":0,"cgroupDriver":"systemd","maxPods":110}}"#;
+
+const SUMMARY: &str = include_str!("fixtures/kubelet/stats_summary.json");
+const METRICS: &str = include_str!("fixtures/kubelet/metrics_resource.txt");
+
+/// Stand-in for a kubelet served over HTTPS with a self-signed certificate. It
+/// answers one request with `body`, and sends the common name of the client
+/// certificate, if any, on the returned channel.
+fn tls_stand_in(body: &'static str) -> (u16, Receiver<Option<String>>) {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_nid(Nid::COMMONNAME, "127.0.0.1")
+        .unwrap();
+    let name = name.build();
+    let mut certificate = X509::builder().unwrap();
+    certificate.set_version(2).unwrap();
+    certificate.set_subject_name(&name).unwrap();
+    certificate.set_issuer_name(&name).unwrap();
+    certificate.set_pubkey(&key).unwrap();
+    certificate
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    certificate
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    certificate.sign(&key, MessageDigest::sha256()).unwrap();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_certificate(&certificate.build()).unwrap();
+    // Ask for a client certificate, whoever signed it.
+    acceptor.set_verify_callback(SslVerifyMode::PEER, |_, _| true);
+    let acceptor = acceptor.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (peers, received) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match acceptor.accept(stream.unwrap()) {
+                Ok(stream) => stream,
+                // The client refused our certificate.
+                Err(_) => continue,
+            };
+            let peer = stream.ssl().peer_certificate().and_then(|c| {
+                let entry = c.subject_name().entries_by_nid(Nid::COMMONNAME).next()?;
+                entry.data().to_string().ok()
+            });
+            let mut request = vec![];
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => request.extend_from_slice(&buf[..read]),
+                }
+            }
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.shutdown();
+            let _ = peers.send(peer);
+        }
+    });
+    (port, received)
+}
+
+#[test]
+fn kubelet_endpoints() {
+    let server = StandIn::start(vec![
+        Reply::new(200, PODS),
+        Reply::new(200, CONFIGZ),
+        Reply::new(200, METRICS).header("Content-Type", "text/plain; version=0.0.4"),
+        Reply::new(200, SUMMARY),
+        Reply::new(200, "ok"),
+    ]);
+    let kubelet = server.client().kubelet(
+        Some(String::from("http")),
+        Some(String::from("127.0.0.1")),
+        Some(server.port as u32),
+    );
+
+    let pods = kubelet.pods().unwrap();
+    let names: Vec<_> = pods
+        .iter()
+        .filter_map(|p| p.metadata.name.as_deref())
+        .collect();
+    assert_eq!(names, vec!["kube-proxy-7xk2p", "etcd-node-1"]);
+    assert_eq!(kubelet.configz().unwrap()["cgroupDriver"], "systemd");
+    assert!(kubelet
+        .metrics_resource()
+        .unwrap()
+        .contains("node_cpu_usage_seconds_total 357.35491"));
+    let summary = kubelet.stats_summary().unwrap();
+    assert_eq!(summary.node.node_name, "node-1");
+    assert!(kubelet.healthz(false).unwrap().healthy);
+
+    let paths: Vec<_> = server.requests().into_iter().map(|r| r.path).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/pods",
+            "/configz",
+            "/metrics/resource",
+            "/stats/summary",
+            "/healthz"
+        ]
+    );
+}
+
+#[test]
+fn kubelet_errors() {
+    let server = StandIn::start(vec![Reply::new(
+        403,
+        "Forbidden (user=system:serviceaccount:monitoring:agent, verb=get, resource=nodes, subresource=proxy)",
+    )]);
+    let kubelet = server
+        .client()
+        .kubelet_read_only(Some(String::from("127.0.0.1")), Some(server.port as u32))
+        .unwrap();
+    assert_eq!(
+        kubelet.base_uri(),
+        format!("http://127.0.0.1:{}", server.port)
+    );
+    let err = kubelet.pods().unwrap_err();
+    assert_eq!(err.status_code().map(|s| s.as_u16()), Some(403));
+    assert_eq!(server.requests()[0].header("Authorization"), None);
+}
+
+#[test]
+fn kubelet_ipv6_host() {
+    let client = StandIn::start(vec![]).client();
+    let kubelet = client.kubelet(None, Some(String::from("fd00::1")), None);
+    assert_eq!(kubelet.base_uri(), "https://[fd00::1]:10250");
+    let kubelet = client
+        .kubelet_read_only(Some(String::from("[::1]")), None)
+        .unwrap();
+    assert_eq!(kubelet.base_uri(), "http://[::1]:10255");
+}
+
+#[test]
+fn kubelet_client_certificate() {
+    let server = StandIn::start(vec![Reply::new(200, PODS)]);
+    let kubelet = server
+        .client()
+        .kubelet_with_client_certificate(
+            Some(String::from("http")),
+            Some(String::from("127.0.0.1")),
+            Some(server.port as u32),
+            "tests/fixtures/kubelet/admin-client.pem",
+            None,
+            Some("tests/fixtures/kubelet/ca.crt"),
+        )
+        .unwrap();
+    assert_eq!(kubelet.pods().unwrap().len(), 2);
+    assert_eq!(server.requests()[0].header("Authorization"), None);
+
+    let err = server
+        .client()
+        .kubelet_with_client_certificate(
+            None,
+            None,
+            None,
+            "tests/fixtures/kubelet/missing.pem",
+            None,
+            None,
+        )
+        .unwrap_err();
+    assert!(matches!(err, KubernetesError::IoError { .. }));
+}
+
+#[test]
+fn kubelet_client_certificate_over_tls() {
+    let (port, peers) = tls_stand_in(PODS);
+    let client = StandIn::start(vec![]).client();
+    let kubelet = client
+        .kubelet_with_client_certificate(
+            None,
+            Some(String::from("127.0.0.1")),
+            Some(port as u32),
+            "tests/fixtures/kubelet/admin-client.pem",
+            None,
+            None,
+        )
+        .unwrap();
+    assert_eq!(kubelet.base_uri(), format!("https://127.0.0.1:{}", port));
+    // Without a CA, the self-signed serving certificate is accepted.
+    assert_eq!(kubelet.pods().unwrap().len(), 2);
+    assert_eq!(peers.recv().unwrap().as_deref(), Some("kubernetes-admin"));
+
+    // With one, it is checked.
+    let kubelet = client
+        .kubelet_with_client_certificate(
+            None,
+            Some(String::from("127.0.0.1")),
+            Some(port as u32),
+            "tests/fixtures/kubelet/admin-client.pem",
+            None,
+            Some("tests/fixtures/kubelet/ca.crt"),
+        )
+        .unwrap();
+    assert!(kubelet.pods().is_err());
+}
+
+#[test]
+fn stats_summary_document() {
+    let server = StandIn::start(vec![Reply::new(200, SUMMARY)]);
+    let summary = server
+        .client()
+        .kubelet(
+            Some(String::from("http")),
+            Some(String::from("127.0.0.1")),
+            Some(server.port as u32),
+        )
+        .stats_summary()
+        .unwrap();
+
+    let node = &summary.node;
+    assert_eq!(node.system_containers[0].name, "kubelet");
+    assert_eq!(
+        node.memory.as_ref().unwrap().working_set_bytes,
+        Some(1077923840)
+    );
+    let network = node.network.as_ref().unwrap();
+    assert_eq!(network.default_interface.name, "eth0");
+    assert_eq!(network.interfaces[0].rx_bytes, Some(182953417));
+    let runtime = node.runtime.as_ref().unwrap();
+    assert_eq!(
+        runtime.image_fs.as_ref().unwrap().used_bytes,
+        Some(1254719488)
+    );
+    assert_eq!(node.rlimit.as_ref().unwrap().curproc, Some(512));
+
+    let pod = summary.pod("shop", "web-6d4cf56db6-8gk2x").unwrap();
+    assert_eq!(pod.pod_ref.uid, "0f3a9c2e-5b1d-4e8f-9a7c-2d6b8e1f4a3c");
+    let web = pod.container("web").unwrap();
+    let cpu = web.cpu.as_ref().unwrap();
+    assert_eq!(cpu.usage_core_nano_seconds, Some(4031337052));
+    assert_eq!(cpu.usage_cores(), Some(0.001203442));
+    assert_eq!(web.logs.as_ref().unwrap().used_bytes, Some(28672));
+    assert_eq!(pod.volume[0].pvc_ref, None);
+    assert_eq!(pod.volume[1].pvc_ref.as_ref().unwrap().name, "data-web");
+    assert_eq!(pod.volume[1].fs.used_bytes, Some(250748928));
+    assert_eq!(
+        pod.ephemeral_storage.as_ref().unwrap().used_bytes,
+        Some(81920)
+    );
+    assert_eq!(pod.process_stats.as_ref().unwrap().process_count, Some(3));
+}
+
+#[test]
+fn resource_metrics_per_container() {
+    let metrics = ResourceMetrics::parse(METRICS).unwrap();
+    let node_cpu = metrics.node_cpu_usage_seconds.unwrap();
+    assert_eq!(node_cpu.value, 357.35491);
+    assert_eq!(
+        node_cpu.timestamp.unwrap().to_rfc3339(),
+        "2021-10-03T09:36:52.125+00:00"
+    );
+    assert_eq!(
+        metrics.node_memory_working_set_bytes.unwrap().value,
+        1077923840.0
+    );
+
+    // Pod level samples aren't containers.
+    assert_eq!(metrics.containers.len(), 2);
+    assert_eq!(metrics.containers[0].namespace, "kube-system");
+    let web = metrics
+        .container("shop", "web-6d4cf56db6-8gk2x", "web")
+        .unwrap();
+    assert_eq!(web.cpu_usage_seconds.unwrap().value, 4.031337052);
+    assert_eq!(web.memory_working_set_bytes.unwrap().value, 24924160.0);
+    assert_eq!(
+        web.start_time.unwrap().to_rfc3339(),
+        "2021-10-03T08:00:01+00:00"
+    );
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::logs::LogOptions;
+use std::io::{BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+#[test]
+fn read_logs() {
+    let server = StandIn::start(vec![Reply::new(200, "line 1\nline 2\n")]);
+    let logs = server
+        .client()
+        .pod_logs_string(
+            "default",
+            "web-0",
+            &LogOptions {
+                container: Some(String::from("nginx")),
+                previous: true,
+                tail_lines: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(logs, "line 1\nline 2\n");
+    assert_eq!(
+        server.requests()[0].path,
+        "/api/v1/namespaces/default/pods/web-0/log?container=nginx&previous=true&tailLines=2"
+    );
+}
+
+#[test]
+fn read_logs_ignores_follow() {
+    let server = StandIn::start(vec![Reply::new(200, "done\n")]);
+    let logs = server
+        .client()
+        .pod_logs_string(
+            "default",
+            "web 0/..",
+            &LogOptions {
+                follow: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(logs, "done\n");
+    assert_eq!(
+        server.requests()[0].path,
+        "/api/v1/namespaces/default/pods/web%200%2F../log"
+    );
+}
+
+#[test]
+fn follow_logs_streams() {
+    let server = StandIn::start(vec![Reply::streamed(
+        200,
+        vec![
+            (Duration::from_millis(0), "first\n"),
+            (Duration::from_millis(800), "second\n"),
+        ],
+    )]);
+    let client = server.client();
+    let start = Instant::now();
+    let stream = client
+        .pod_logs(
+            "default",
+            "web-0",
+            &LogOptions {
+                follow: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let mut lines = BufReader::new(stream).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), "first");
+    // The first line is available before the server sends the rest.
+    assert!(start.elapsed() < Duration::from_millis(700));
+    assert_eq!(lines.next().unwrap().unwrap(), "second");
+    assert!(lines.next().is_none());
+}
+
+#[test]
+fn logs_of_missing_pod() {
+    let server = StandIn::start(vec![Reply::new(
+        404,
+        r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"pods \"nope\" not found","reason":"NotFound","code":404}"#,
+    )]);
+    let err = server
+        .client()
+        .pod_logs("default", "nope", &LogOptions::default())
+        .unwrap_err();
+    assert!(err.is_not_found());
+}
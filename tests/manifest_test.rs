@@ -0,0 +1,278 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::errors::KubernetesError;
+use k8s_sync::manifest::{parse_manifests, ApplyOutcome, ApplySet, ManifestOptions};
+use k8s_sync::retry::RetryPolicy;
+
+const CORE: &str = r#"{"kind":"APIGroupDiscoveryList","apiVersion":"apidiscovery.k8s.io/v2","metadata":{},"items":[{"metadata":{"creationTimestamp":null},"versions":[{"version":"v1","resources":[
+    {"resource":"namespaces","responseKind":{"group":"","version":"v1","kind":"Namespace"},"scope":"Cluster","singularResource":"namespace","verbs":["get","patch"]},
+    {"resource":"configmaps","responseKind":{"group":"","version":"v1","kind":"ConfigMap"},"scope":"Namespaced","singularResource":"configmap","verbs":["get","list","patch","delete"]},
+    {"resource":"services","responseKind":{"group":"","version":"v1","kind":"Service"},"scope":"Namespaced","singularResource":"service","verbs":["get","list","patch","delete"]},
+    {"resource":"secrets","responseKind":{"group":"","version":"v1","kind":"Secret"},"scope":"Namespaced","singularResource":"secret","verbs":["get","patch"]}
+]}]}]}"#;
+const GROUPS: &str = r#"{"kind":"APIGroupDiscoveryList","apiVersion":"apidiscovery.k8s.io/v2","metadata":{},"items":[{"metadata":{"name":"apiextensions.k8s.io","creationTimestamp":null},"versions":[{"version":"v1","resources":[{"resource":"customresourcedefinitions","responseKind":{"group":"apiextensions.k8s.io","version":"v1","kind":"CustomResourceDefinition"},"scope":"Cluster","singularResource":"customresourcedefinition","verbs":["get","list","watch","patch"]}]}]}]}"#;
+const GROUPS_WITH_CRONTABS: &str = r#"{"kind":"APIGroupDiscoveryList","apiVersion":"apidiscovery.k8s.io/v2","metadata":{},"items":[{"metadata":{"name":"apiextensions.k8s.io","creationTimestamp":null},"versions":[{"version":"v1","resources":[{"resource":"customresourcedefinitions","responseKind":{"group":"apiextensions.k8s.io","version":"v1","kind":"CustomResourceDefinition"},"scope":"Cluster","singularResource":"customresourcedefinition","verbs":["get","list","watch","patch"]}]}]},{"metadata":{"name":"stable.example.com","creationTimestamp":null},"versions":[{"version":"v1","resources":[{"resource":"crontabs","responseKind":{"group":"stable.example.com","version":"v1","kind":"CronTab"},"scope":"Namespaced","singularResource":"crontab","verbs":["get","patch"]}]}]}]}"#;
+const NOT_FOUND: &str = r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","reason":"NotFound","code":404}"#;
+
+const MANIFESTS: &str = r#"
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: settings
+  namespace: shop
+data:
+  mode: fast
+---
+apiVersion: stable.example.com/v1
+kind: CronTab
+metadata:
+  name: nightly
+  namespace: shop
+spec:
+  schedule: "0 3 * * *"
+---
+apiVersion: v1
+kind: Namespace
+metadata:
+  name: shop
+---
+apiVersion: apiextensions.k8s.io/v1
+kind: CustomResourceDefinition
+metadata:
+  name: crontabs.stable.example.com
+spec:
+  group: stable.example.com
+"#;
+
+fn object(api_version: &str, kind: &str, name: &str, resource_version: &str) -> String {
+    format!(
+        r#"{{"apiVersion":"{}","kind":"{}","metadata":{{"name":"{}","namespace":"shop","resourceVersion":"{}"}}}}"#,
+        api_version, kind, name, resource_version
+    )
+}
+
+#[test]
+fn apply_manifests_in_dependency_order() {
+    let crd = r#"{"apiVersion":"apiextensions.k8s.io/v1","kind":"CustomResourceDefinition","metadata":{"name":"crontabs.stable.example.com","resourceVersion":"6"},"status":{"conditions":[{"type":"Established","status":"True"}]}}"#;
+    let server = StandIn::start(vec![
+        Reply::new(200, CORE),
+        Reply::new(200, GROUPS),
+        Reply::new(404, NOT_FOUND),
+        Reply::new(201, &object("v1", "Namespace", "shop", "1")),
+        Reply::new(200, &crd.replace(r#""6""#, r#""5""#)),
+        Reply::new(200, crd),
+        Reply::new(
+            200,
+            &format!(
+                r#"{{"kind":"CustomResourceDefinitionList","apiVersion":"apiextensions.k8s.io/v1","metadata":{{"resourceVersion":"7"}},"items":[{}]}}"#,
+                crd
+            ),
+        ),
+        Reply::new(200, CORE),
+        Reply::new(200, GROUPS_WITH_CRONTABS),
+        Reply::new(200, &object("v1", "ConfigMap", "settings", "3")),
+        Reply::new(200, &object("v1", "ConfigMap", "settings", "3")),
+        Reply::new(404, NOT_FOUND),
+        Reply::new(
+            201,
+            &object("stable.example.com/v1", "CronTab", "nightly", "8"),
+        ),
+    ]);
+    let client = server.client().with_discovery_cache(None);
+    let objects = parse_manifests(MANIFESTS).unwrap();
+    let results = client
+        .apply_manifests(&objects, &ManifestOptions::new("deployer"))
+        .unwrap();
+    let report: Vec<String> = results.iter().map(|r| r.to_string()).collect();
+    assert_eq!(
+        report,
+        vec![
+            "namespace/shop created",
+            "customresourcedefinition.apiextensions.k8s.io/crontabs.stable.example.com configured",
+            "configmap/settings unchanged",
+            "crontab.stable.example.com/nightly created",
+        ]
+    );
+
+    let requests = server.requests();
+    let calls: Vec<String> = requests
+        .iter()
+        .map(|r| format!("{} {}", r.method, r.path))
+        .collect();
+    assert_eq!(
+        calls,
+        vec![
+            "GET /api",
+            "GET /apis",
+            "GET /api/v1/namespaces/shop",
+            "PATCH /api/v1/namespaces/shop?fieldManager=deployer&force=false",
+            "GET /apis/apiextensions.k8s.io/v1/customresourcedefinitions/crontabs.stable.example.com",
+            "PATCH /apis/apiextensions.k8s.io/v1/customresourcedefinitions/crontabs.stable.example.com?fieldManager=deployer&force=false",
+            "GET /apis/apiextensions.k8s.io/v1/customresourcedefinitions?fieldSelector=metadata.name%3Dcrontabs.stable.example.com",
+            "GET /api",
+            "GET /apis",
+            "GET /api/v1/namespaces/shop/configmaps/settings",
+            "PATCH /api/v1/namespaces/shop/configmaps/settings?fieldManager=deployer&force=false",
+            "GET /apis/stable.example.com/v1/namespaces/shop/crontabs/nightly",
+            "PATCH /apis/stable.example.com/v1/namespaces/shop/crontabs/nightly?fieldManager=deployer&force=false",
+        ]
+    );
+    assert_eq!(
+        requests[12].header("Content-Type"),
+        Some("application/apply-patch+yaml")
+    );
+}
+
+#[test]
+fn unknown_kind_fails_alone() {
+    let server = StandIn::start(vec![
+        Reply::new(200, CORE),
+        Reply::new(200, GROUPS),
+        Reply::new(200, &object("v1", "ConfigMap", "settings", "3")),
+        Reply::new(200, &object("v1", "ConfigMap", "settings", "4")),
+        Reply::new(200, CORE),
+        Reply::new(200, GROUPS),
+    ]);
+    let client = server.client().with_discovery_cache(None);
+    let objects = parse_manifests(MANIFESTS).unwrap();
+    let results = client
+        .apply_manifests(
+            &objects[..2],
+            &ManifestOptions::new("deployer").namespace("shop"),
+        )
+        .unwrap();
+    assert!(matches!(results[0].outcome, ApplyOutcome::Configured));
+    assert_eq!(
+        results[1].to_string(),
+        "crontab.stable.example.com/nightly failed: No resource serves kind CronTab in stable.example.com/v1"
+    );
+}
+
+#[test]
+fn failing_group_only_fails_its_kinds() {
+    let api = r#"{"kind":"APIVersions","versions":["v1"],"serverAddressByClientCIDRs":[]}"#;
+    let core = r#"{"kind":"APIResourceList","apiVersion":"v1","groupVersion":"v1","resources":[{"name":"configmaps","singularName":"configmap","namespaced":true,"kind":"ConfigMap","verbs":["get","patch"]}]}"#;
+    let groups = r#"{"kind":"APIGroupList","apiVersion":"v1","groups":[{"name":"stable.example.com","versions":[{"groupVersion":"stable.example.com/v1","version":"v1"}]}]}"#;
+    let unavailable = r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","reason":"ServiceUnavailable","code":503}"#;
+    let server = StandIn::start(vec![
+        Reply::new(200, api),
+        Reply::new(200, core),
+        Reply::new(200, groups),
+        Reply::new(503, unavailable),
+        Reply::new(200, &object("v1", "ConfigMap", "settings", "3")),
+        Reply::new(200, &object("v1", "ConfigMap", "settings", "4")),
+        Reply::new(200, api),
+        Reply::new(200, core),
+        Reply::new(200, groups),
+        Reply::new(503, unavailable),
+    ]);
+    let client = server
+        .client()
+        .with_retry_policy(RetryPolicy::none())
+        .with_discovery_cache(None);
+    let objects = parse_manifests(MANIFESTS).unwrap();
+    let results = client
+        .apply_manifests(
+            &objects[..2],
+            &ManifestOptions::new("deployer").namespace("shop"),
+        )
+        .unwrap();
+    assert!(matches!(results[0].outcome, ApplyOutcome::Configured));
+    assert!(matches!(
+        &results[1].outcome,
+        ApplyOutcome::Failed(KubernetesError::GroupDiscoveryError { group_version, .. })
+            if group_version == "stable.example.com/v1"
+    ));
+}
+
+#[test]
+fn prune_applyset_members() {
+    let applyset = ApplySet::new("shop-set", "shop");
+    let parent = format!(
+        r#"{{"apiVersion":"v1","kind":"Secret","metadata":{{"name":"shop-set","namespace":"shop","labels":{{"applyset.kubernetes.io/id":"{}"}},"annotations":{{"applyset.kubernetes.io/contains-group-kinds":"ConfigMap,Service"}}}}}}"#,
+        applyset.id()
+    );
+    let member = |kind: &str, name: &str| {
+        format!(
+            r#"{{"apiVersion":"v1","kind":"{}","metadata":{{"name":"{}","namespace":"shop"}}}}"#,
+            kind, name
+        )
+    };
+    let server = StandIn::start(vec![
+        Reply::new(200, CORE),
+        Reply::new(200, GROUPS),
+        Reply::new(200, &parent),
+        Reply::new(200, &parent),
+        Reply::new(404, NOT_FOUND),
+        Reply::new(201, &object("v1", "ConfigMap", "settings", "3")),
+        Reply::new(
+            200,
+            &format!(
+                r#"{{"kind":"ConfigMapList","apiVersion":"v1","metadata":{{}},"items":[{},{}]}}"#,
+                member("ConfigMap", "settings"),
+                member("ConfigMap", "old-settings")
+            ),
+        ),
+        Reply::new(200, &member("ConfigMap", "old-settings")),
+        Reply::new(
+            200,
+            &format!(
+                r#"{{"kind":"ServiceList","apiVersion":"v1","metadata":{{}},"items":[{}]}}"#,
+                member("Service", "web")
+            ),
+        ),
+        Reply::new(200, &member("Service", "web")),
+        Reply::new(200, &parent),
+    ]);
+    let client = server.client().with_discovery_cache(None);
+    let objects = parse_manifests(MANIFESTS).unwrap();
+    let results = client
+        .apply_manifests(
+            &objects[..1],
+            &ManifestOptions::new("deployer").prune(applyset.clone()),
+        )
+        .unwrap();
+    let report: Vec<String> = results.iter().map(|r| r.to_string()).collect();
+    assert_eq!(
+        report,
+        vec![
+            "configmap/settings created",
+            "configmap/old-settings pruned",
+            "service/web pruned",
+        ]
+    );
+
+    let requests = server.requests();
+    let body =
+        |i: usize| -> serde_json::Value { serde_json::from_slice(&requests[i].body).unwrap() };
+    assert_eq!(
+        requests[3].path,
+        "/api/v1/namespaces/shop/secrets/shop-set?fieldManager=deployer&force=false"
+    );
+    assert_eq!(
+        body(3)["metadata"]["annotations"]["applyset.kubernetes.io/contains-group-kinds"],
+        "ConfigMap,Service"
+    );
+    assert_eq!(
+        body(5)["metadata"]["labels"]["applyset.kubernetes.io/part-of"],
+        applyset.id()
+    );
+    assert_eq!(
+        requests[6].path,
+        format!(
+            "/api/v1/namespaces/shop/configmaps?labelSelector=applyset.kubernetes.io%2Fpart-of%3D{}",
+            applyset.id()
+        )
+    );
+    assert_eq!(requests[7].method, "DELETE");
+    assert_eq!(
+        requests[7].path,
+        "/api/v1/namespaces/shop/configmaps/old-settings"
+    );
+    assert_eq!(requests[9].path, "/api/v1/namespaces/shop/services/web");
+    assert_eq!(
+        body(10)["metadata"]["annotations"]["applyset.kubernetes.io/contains-group-kinds"],
+        "ConfigMap"
+    );
+}
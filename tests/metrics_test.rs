@@ -0,0 +1,63 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::metrics::{sort_node_metrics, SortBy};
+use k8s_sync::ListOptional;
+
+const NODES: &str = r#"{"kind":"NodeMetricsList","apiVersion":"metrics.k8s.io/v1beta1","metadata":{},"items":[
+    {"metadata":{"name":"node-1","creationTimestamp":"2021-10-03T09:37:10Z"},"timestamp":"2021-10-03T09:36:52Z","window":"10s","usage":{"cpu":"124531765n","memory":"1052660Ki"}},
+    {"metadata":{"name":"node-2","creationTimestamp":"2021-10-03T09:37:10Z"},"timestamp":"2021-10-03T09:36:55Z","window":"10s","usage":{"cpu":"1204117603n","memory":"812044Ki"}}
+]}"#;
+
+const PODS: &str = r#"{"kind":"PodMetricsList","apiVersion":"metrics.k8s.io/v1beta1","metadata":{},"items":[
+    {"metadata":{"name":"web-6d4cf56db6-8gk2x","namespace":"shop","labels":{"app":"web"}},"timestamp":"2021-10-03T09:36:45Z","window":"10s",
+     "containers":[{"name":"web","usage":{"cpu":"1203442n","memory":"24340Ki"}},{"name":"proxy","usage":{"cpu":"3001207n","memory":"8192Ki"}}]}
+]}"#;
+
+#[test]
+fn node_metrics() {
+    let server = StandIn::start(vec![Reply::new(200, NODES)]);
+    let mut nodes = server
+        .client()
+        .list_node_metrics(Default::default())
+        .unwrap();
+    sort_node_metrics(&mut nodes, SortBy::Cpu);
+    assert_eq!(nodes[0].name(), Some("node-2"));
+    assert_eq!(nodes[0].cpu_millicores(), Some(1205));
+    assert_eq!(nodes[1].memory_bytes(), Some(1077923840));
+    assert_eq!(
+        server.requests()[0].path,
+        "/apis/metrics.k8s.io/v1beta1/nodes"
+    );
+}
+
+#[test]
+fn pod_metrics() {
+    let server = StandIn::start(vec![Reply::new(200, PODS), Reply::new(200, PODS)]);
+    let client = server.client();
+    let pods = client
+        .list_pod_metrics(
+            Some("shop"),
+            ListOptional {
+                label_selector: Some("app=web"),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let web = &pods[0];
+    assert_eq!(web.namespace(), Some("shop"));
+    assert_eq!(web.container("web").unwrap().cpu_millicores(), Some(2));
+    // 1203442n + 3001207n, rounded up once.
+    assert_eq!(web.cpu_millicores(), Some(5));
+    assert_eq!(web.memory_bytes(), Some(33312768));
+    client.list_pod_metrics(None, Default::default()).unwrap();
+
+    let paths: Vec<_> = server.requests().into_iter().map(|r| r.path).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/apis/metrics.k8s.io/v1beta1/namespaces/shop/pods?labelSelector=app%3Dweb",
+            "/apis/metrics.k8s.io/v1beta1/pods"
+        ]
+    );
+}
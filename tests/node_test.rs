@@ -0,0 +1,39 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::ListOptional;
+
+const PODS: &str = r#"{"apiVersion":"v1","kind":"PodList","metadata":{},"items":[
+    {"metadata":{"name":"web-1","namespace":"default"},"spec":{"nodeName":"node-1","containers":[]}},
+    {"metadata":{"name":"coredns-x","namespace":"kube-system"},"spec":{"nodeName":"node-1","containers":[]}}
+]}"#;
+
+#[test]
+fn list_pods_on_node_in_all_namespaces() {
+    let server = StandIn::start(vec![Reply::new(200, PODS), Reply::new(200, PODS)]);
+    let client = server.client();
+    let pods = client
+        .list_pods_on_node(Some(String::from("node-1")), Default::default())
+        .unwrap();
+    assert_eq!(pods.len(), 2);
+    client
+        .list_pods_on_node(
+            Some(String::from("node-1")),
+            ListOptional {
+                field_selector: Some("status.phase=Running"),
+                label_selector: Some("app=web"),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].path,
+        "/api/v1/pods?fieldSelector=spec.nodeName%3Dnode-1"
+    );
+    assert_eq!(
+        requests[1].path,
+        "/api/v1/pods?fieldSelector=spec.nodeName%3Dnode-1%2Cstatus.phase%3DRunning&labelSelector=app%3Dweb"
+    );
+}
@@ -0,0 +1,163 @@
+use k8s_sync::kubernetes::Kubernetes;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::handshake::server::{Request, Response};
+use tungstenite::Message;
+
+/// Stand-in for the API server side of the portforward subresource. Every
+/// connection gets the port prefix frames, then `greeting` on the error channel
+/// when set, then has its data echoed back with a prefix naming the port.
+// The handshake callback signature is imposed by tungstenite.
+#[allow(clippy::result_large_err)]
+fn stand_in(greeting: Option<&'static str>) -> (u16, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (paths, received) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let paths = paths.clone();
+            thread::spawn(move || {
+                let mut path = String::new();
+                let callback = |request: &Request, mut response: Response| {
+                    path = request.uri().to_string();
+                    response.headers_mut().insert(
+                        "Sec-WebSocket-Protocol",
+                        "portforward.k8s.io".parse().unwrap(),
+                    );
+                    Ok(response)
+                };
+                let mut socket = match tungstenite::accept_hdr(stream.unwrap(), callback) {
+                    Ok(socket) => socket,
+                    Err(_) => return,
+                };
+                let _ = paths.send(path.clone());
+                let remote: u16 = path.rsplit('=').next().unwrap().parse().unwrap();
+                let prefix = remote.to_le_bytes();
+                socket
+                    .send(Message::Binary(vec![0, prefix[0], prefix[1]]))
+                    .unwrap();
+                socket
+                    .send(Message::Binary(vec![1, prefix[0], prefix[1]]))
+                    .unwrap();
+                if let Some(greeting) = greeting {
+                    let mut frame = vec![1];
+                    frame.extend_from_slice(greeting.as_bytes());
+                    socket.send(Message::Binary(frame)).unwrap();
+                }
+                while let Ok(message) = socket.read() {
+                    if let Message::Binary(data) = message {
+                        let mut frame = vec![0];
+                        frame.extend_from_slice(format!("{}:", remote).as_bytes());
+                        frame.extend_from_slice(&data[1..]);
+                        if socket.send(Message::Binary(frame)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    (port, received)
+}
+
+fn client(port: u16) -> Kubernetes {
+    Kubernetes::connect(
+        Some(String::from("tests/fixtures/kubeconfig")),
+        Some(String::from("http")),
+        Some(String::from("127.0.0.1")),
+        Some(port as u32),
+        false,
+    )
+    .unwrap()
+}
+
+fn exchange(port: u16, message: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(message.as_bytes()).unwrap();
+    let mut buf = [0u8; 64];
+    let read = stream.read(&mut buf).unwrap();
+    String::from_utf8(buf[..read].to_vec()).unwrap()
+}
+
+#[test]
+fn forwards_several_ports() {
+    let (port, paths) = stand_in(None);
+    let forward = client(port)
+        .port_forward("default", "web-0", &[(0, 80), (0, 9090)])
+        .unwrap();
+    let web = forward.local_port(80).unwrap();
+    let metrics = forward.local_port(9090).unwrap();
+    assert_ne!(web, 0);
+    assert_eq!(forward.ports(), &[(web, 80), (metrics, 9090)]);
+
+    assert_eq!(exchange(web, "GET /"), "80:GET /");
+    assert_eq!(exchange(metrics, "scrape"), "9090:scrape");
+    // Each local connection has its own WebSocket.
+    assert_eq!(exchange(web, "again"), "80:again");
+    let mut paths: Vec<String> = paths.try_iter().collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            "/api/v1/namespaces/default/pods/web-0/portforward?ports=80",
+            "/api/v1/namespaces/default/pods/web-0/portforward?ports=80",
+            "/api/v1/namespaces/default/pods/web-0/portforward?ports=9090",
+        ]
+    );
+    assert!(forward.errors().is_empty());
+}
+
+#[test]
+fn reports_errors_from_the_pod() {
+    let (port, _) = stand_in(Some("error forwarding port 80: connection refused"));
+    let forward = client(port)
+        .port_forward("default", "web-0", &[(0, 80)])
+        .unwrap();
+    exchange(forward.local_port(80).unwrap(), "hello");
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while forward.errors().is_empty() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        forward.errors(),
+        vec!["error forwarding port 80: connection refused"]
+    );
+}
+
+#[test]
+fn dropping_the_handle_stops_forwarding() {
+    let (port, _) = stand_in(None);
+    let forward = client(port)
+        .port_forward("default", "web-0", &[(0, 80)])
+        .unwrap();
+    let local = forward.local_port(80).unwrap();
+    let mut open = TcpStream::connect(("127.0.0.1", local)).unwrap();
+    open.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    open.write_all(b"ping").unwrap();
+    let mut buf = [0u8; 16];
+    assert_eq!(open.read(&mut buf).unwrap(), 7);
+
+    drop(forward);
+    // The open connection is closed and nothing listens anymore.
+    assert_eq!(open.read(&mut buf).unwrap(), 0);
+    assert!(TcpStream::connect(("127.0.0.1", local)).is_err());
+}
+
+#[test]
+fn pod_name_is_encoded_in_path() {
+    let (port, paths) = stand_in(None);
+    let forward = client(port)
+        .port_forward("default", "web 0/x", &[(0, 80)])
+        .unwrap();
+    assert_eq!(exchange(forward.local_port(80).unwrap(), "hi"), "80:hi");
+    assert_eq!(
+        paths.recv().unwrap(),
+        "/api/v1/namespaces/default/pods/web%200%2Fx/portforward?ports=80"
+    );
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::ratelimit::RateLimiter;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const EMPTY_POD_LIST: &str =
+    r#"{"kind":"PodList","apiVersion":"v1","metadata":{"resourceVersion":"1"},"items":[]}"#;
+
+#[test]
+fn requests_wait_for_tokens() {
+    let server = StandIn::start((0..3).map(|_| Reply::new(200, EMPTY_POD_LIST)).collect());
+    let waits = Arc::new(Mutex::new(vec![]));
+    let observed = waits.clone();
+    let limiter = RateLimiter::new(10.0, 1).with_observer(move |wait| {
+        observed.lock().unwrap().push(wait);
+    });
+    let client = server.client().with_rate_limiter(Arc::new(limiter));
+    for _ in 0..3 {
+        client
+            .list_pods(String::from("default"), Default::default())
+            .unwrap();
+    }
+    let waits = waits.lock().unwrap();
+    assert_eq!(waits.len(), 3);
+    assert_eq!(waits[0], Duration::from_secs(0));
+    assert!(waits[1] > Duration::from_millis(50));
+    assert_eq!(
+        client.rate_limiter.as_ref().unwrap().throttled_requests(),
+        2
+    );
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::retry::RetryPolicy;
+use std::time::{Duration, Instant};
+
+const EMPTY_POD_LIST: &str =
+    r#"{"kind":"PodList","apiVersion":"v1","metadata":{"resourceVersion":"1"},"items":[]}"#;
+
+fn fast_policy() -> RetryPolicy {
+    RetryPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(50),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn retries_transient_failures() {
+    let server = StandIn::start(vec![
+        Reply::new(503, "upgrading"),
+        Reply::new(
+            500,
+            r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","message":"etcdserver: leader changed","code":500}"#,
+        ),
+        Reply::new(200, EMPTY_POD_LIST),
+    ]);
+    let client = server.client().with_retry_policy(fast_policy());
+    let pods = client.list_pods(String::from("default"), Default::default());
+    assert!(pods.unwrap().is_empty());
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let server = StandIn::start(vec![
+        Reply::new(503, "upgrading"),
+        Reply::new(503, "upgrading"),
+        Reply::new(200, EMPTY_POD_LIST),
+    ]);
+    let client = server.client().with_retry_policy(RetryPolicy {
+        max_attempts: 2,
+        ..fast_policy()
+    });
+    let err = client
+        .list_pods(String::from("default"), Default::default())
+        .unwrap_err();
+    assert_eq!(
+        err.status_code(),
+        Some(http::StatusCode::SERVICE_UNAVAILABLE)
+    );
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn honors_retry_after() {
+    let server = StandIn::start(vec![
+        Reply::new(429, "too many requests").header("Retry-After", "1"),
+        Reply::new(200, EMPTY_POD_LIST),
+    ]);
+    // Retry-After is waited for even when longer than `max_backoff`.
+    let client = server.client().with_retry_policy(fast_policy());
+    let start = Instant::now();
+    assert!(client
+        .list_pods(String::from("default"), Default::default())
+        .is_ok());
+    assert!(start.elapsed() >= Duration::from_secs(1));
+}
+
+#[test]
+fn gives_up_on_long_retry_after() {
+    let server = StandIn::start(vec![
+        Reply::new(429, "too many requests").header("Retry-After", "120"),
+        Reply::new(200, EMPTY_POD_LIST),
+    ]);
+    let client = server.client().with_retry_policy(fast_policy());
+    let start = Instant::now();
+    let err = client
+        .list_pods(String::from("default"), Default::default())
+        .unwrap_err();
+    assert_eq!(err.status_code().map(|s| s.as_u16()), Some(429));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn does_not_retry_client_errors() {
+    let server = StandIn::start(vec![
+        Reply::new(404, "not found"),
+        Reply::new(200, EMPTY_POD_LIST),
+    ]);
+    let client = server.client().with_retry_policy(fast_policy());
+    let err = client
+        .list_pods(String::from("default"), Default::default())
+        .unwrap_err();
+    assert!(err.is_not_found());
+    assert_eq!(server.requests().len(), 1);
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_openapi::api::apps::v1::Deployment;
+use std::time::Duration;
+
+const ROLLING: &str = r#"{"apiVersion":"apps/v1","kind":"Deployment","metadata":{"name":"web","namespace":"default","generation":2},"spec":{"replicas":2,"selector":{},"template":{}},"status":{"observedGeneration":2,"replicas":3,"updatedReplicas":2,"readyReplicas":3,"availableReplicas":3}}"#;
+const ROLLED_OUT: &str = r#"{"apiVersion":"apps/v1","kind":"Deployment","metadata":{"name":"web","namespace":"default","generation":2},"spec":{"replicas":2,"selector":{},"template":{}},"status":{"observedGeneration":2,"replicas":2,"updatedReplicas":2,"readyReplicas":2,"availableReplicas":2}}"#;
+const DEPLOYMENT: &str = r#"{"apiVersion":"apps/v1","kind":"Deployment","metadata":{"name":"web","namespace":"default","uid":"d1","resourceVersion":"40","annotations":{"deployment.kubernetes.io/revision":"3"}},"spec":{"selector":{"matchLabels":{"app":"web"}},"template":{"metadata":{"labels":{"app":"web"}},"spec":{"containers":[{"name":"web","image":"web:3"}]}}}}"#;
+const REPLICA_SETS: &str = r#"{"apiVersion":"apps/v1","kind":"ReplicaSetList","metadata":{},"items":[
+    {"metadata":{"name":"web-c","annotations":{"deployment.kubernetes.io/revision":"3"},"ownerReferences":[{"apiVersion":"apps/v1","kind":"Deployment","name":"web","uid":"d1","controller":true}]},"spec":{"selector":{},"template":{"metadata":{"labels":{"app":"web","pod-template-hash":"c"}},"spec":{"containers":[{"name":"web","image":"web:3"}]}}}},
+    {"metadata":{"name":"web-a","annotations":{"deployment.kubernetes.io/revision":"1"},"ownerReferences":[{"apiVersion":"apps/v1","kind":"Deployment","name":"web","uid":"d1","controller":true}]},"spec":{"selector":{},"template":{"metadata":{"labels":{"app":"web","pod-template-hash":"a"}},"spec":{"containers":[{"name":"web","image":"web:1"}]}}}},
+    {"metadata":{"name":"web-b","annotations":{"deployment.kubernetes.io/revision":"2","kubernetes.io/change-cause":"bump to 2"},"ownerReferences":[{"apiVersion":"apps/v1","kind":"Deployment","name":"web","uid":"d1","controller":true}]},"spec":{"selector":{},"template":{"metadata":{"labels":{"app":"web","pod-template-hash":"b"}},"spec":{"containers":[{"name":"web","image":"web:2"}]}}}},
+    {"metadata":{"name":"other","annotations":{"deployment.kubernetes.io/revision":"9"},"ownerReferences":[{"apiVersion":"apps/v1","kind":"Deployment","name":"web","uid":"old","controller":true}]},"spec":{"selector":{}}}
+]}"#;
+
+#[test]
+fn rollout_status_reports_progress() {
+    let server = StandIn::start(vec![
+        Reply::new(200, ROLLING),
+        Reply::new(200, ROLLING),
+        Reply::new(200, ROLLED_OUT),
+    ]);
+    let client = server.client();
+    let mut messages = vec![];
+    let deployment = client
+        .api::<Deployment>(Some("default"))
+        .rollout_status("web", Duration::from_secs(5), |progress| {
+            messages.push(progress.message.clone())
+        })
+        .unwrap();
+    assert_eq!(deployment.status.unwrap().replicas, Some(2));
+    assert_eq!(
+        messages,
+        vec![
+            "Waiting for deployment \"web\" rollout to finish: 1 old replicas are pending termination...",
+            "deployment \"web\" successfully rolled out",
+        ]
+    );
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn rollout_history_lists_owned_revisions() {
+    let server = StandIn::start(vec![
+        Reply::new(200, DEPLOYMENT),
+        Reply::new(200, REPLICA_SETS),
+    ]);
+    let client = server.client();
+    let history = client
+        .api::<Deployment>(Some("default"))
+        .rollout_history("web")
+        .unwrap();
+    let revisions: Vec<(i64, &str)> = history
+        .iter()
+        .map(|r| (r.revision, r.replica_set.as_str()))
+        .collect();
+    assert_eq!(revisions, vec![(1, "web-a"), (2, "web-b"), (3, "web-c")]);
+    assert_eq!(history[1].change_cause.as_deref(), Some("bump to 2"));
+    assert_eq!(
+        server.requests()[1].path,
+        "/apis/apps/v1/namespaces/default/replicasets?labelSelector=app%3Dweb"
+    );
+}
+
+#[test]
+fn rollout_undo_restores_previous_template() {
+    let previous = r#"{"apiVersion":"apps/v1","kind":"ReplicaSet","metadata":{"name":"web-b","annotations":{"deployment.kubernetes.io/revision":"2","kubernetes.io/change-cause":"bump to 2"}}}"#;
+    let server = StandIn::start(vec![
+        Reply::new(200, DEPLOYMENT),
+        Reply::new(200, REPLICA_SETS),
+        Reply::new(200, previous),
+        Reply::new(200, DEPLOYMENT),
+    ]);
+    let client = server.client();
+    client
+        .api::<Deployment>(Some("default"))
+        .rollout_undo("web", None)
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[2].path,
+        "/apis/apps/v1/namespaces/default/replicasets/web-b"
+    );
+    assert_eq!(requests[3].method, "PATCH");
+    assert_eq!(
+        requests[3].header("Content-Type"),
+        Some("application/json-patch+json")
+    );
+    let patch: serde_json::Value = serde_json::from_slice(&requests[3].body).unwrap();
+    assert_eq!(
+        patch,
+        serde_json::json!([
+            {"op": "test", "path": "/metadata/resourceVersion", "value": "40"},
+            {"op": "replace", "path": "/spec/template", "value": {
+                "metadata": {"labels": {"app": "web"}},
+                "spec": {"containers": [{"name": "web", "image": "web:2"}]}
+            }},
+            {"op": "add", "path": "/metadata/annotations", "value": {
+                "deployment.kubernetes.io/revision": "3",
+                "kubernetes.io/change-cause": "bump to 2"
+            }}
+        ])
+    );
+}
+
+#[test]
+fn rollout_undo_to_unknown_revision() {
+    let server = StandIn::start(vec![
+        Reply::new(200, DEPLOYMENT),
+        Reply::new(200, REPLICA_SETS),
+    ]);
+    let err = server
+        .client()
+        .api::<Deployment>(Some("default"))
+        .rollout_undo("web", Some(7))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Rollout of web failed: revision 7 not found"
+    );
+}
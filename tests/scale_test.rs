@@ -0,0 +1,90 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_sync::dynamic::GroupVersionResource;
+use k8s_sync::rollout::RESTARTED_AT_ANNOTATION;
+
+const SCALE: &str = r#"{"kind":"Scale","apiVersion":"autoscaling/v1","metadata":{"name":"web","namespace":"default","resourceVersion":"5"},"spec":{"replicas":3},"status":{"replicas":3,"selector":"app=web"}}"#;
+
+#[test]
+fn get_and_update_scale() {
+    let server = StandIn::start(vec![
+        Reply::new(200, SCALE),
+        Reply::new(
+            200,
+            &SCALE.replace(r#""spec":{"replicas":3}"#, r#""spec":{"replicas":5}"#),
+        ),
+        Reply::new(200, SCALE),
+    ]);
+    let client = server.client();
+    let api = client.api::<Deployment>(Some("default"));
+
+    let mut scale = api.get_scale("web").unwrap();
+    assert_eq!(
+        scale.status.as_ref().unwrap().selector.as_deref(),
+        Some("app=web")
+    );
+    scale.spec.as_mut().unwrap().replicas = Some(5);
+    let scale = api.replace_scale("web", &scale).unwrap();
+    assert_eq!(scale.spec.unwrap().replicas, Some(5));
+    api.scale("web", 3).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].path,
+        "/apis/apps/v1/namespaces/default/deployments/web/scale"
+    );
+    assert_eq!(requests[1].method, "PUT");
+    let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(body["metadata"]["resourceVersion"], "5");
+    assert_eq!(body["spec"]["replicas"], 5);
+    assert_eq!(requests[2].method, "PATCH");
+    assert_eq!(
+        requests[2].header("Content-Type"),
+        Some("application/merge-patch+json")
+    );
+    assert_eq!(requests[2].body, br#"{"spec":{"replicas":3}}"#);
+}
+
+#[test]
+fn scale_custom_resource() {
+    let server = StandIn::start(vec![Reply::new(200, SCALE)]);
+    let client = server.client();
+    client
+        .dynamic(
+            GroupVersionResource::new("example.com", "v1", "workers"),
+            Some("default"),
+        )
+        .scale("pool", 3)
+        .unwrap();
+    assert_eq!(
+        server.requests()[0].path,
+        "/apis/example.com/v1/namespaces/default/workers/pool/scale"
+    );
+}
+
+#[test]
+fn rollout_restart_sets_annotation() {
+    let server = StandIn::start(vec![Reply::new(
+        200,
+        r#"{"apiVersion":"apps/v1","kind":"StatefulSet","metadata":{"name":"db","namespace":"default"}}"#,
+    )]);
+    let client = server.client();
+    client
+        .api::<StatefulSet>(Some("default"))
+        .rollout_restart("db")
+        .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method, "PATCH");
+    assert_eq!(
+        request.path,
+        "/apis/apps/v1/namespaces/default/statefulsets/db"
+    );
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    let restarted_at = body["spec"]["template"]["metadata"]["annotations"][RESTARTED_AT_ANNOTATION]
+        .as_str()
+        .unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(restarted_at).is_ok());
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{Reply, StandIn};
+
+#[test]
+fn server_version() {
+    let server = StandIn::start(vec![Reply::new(
+        200,
+        r#"{"major":"1","minor":"21","gitVersion":"v1.21.3","gitCommit":"ca643a4d1f7bfe34773c74f79527be4afd95bf39","gitTreeState":"clean","buildDate":"2021-07-15T20:59:07Z","goVersion":"go1.16.6","compiler":"gc","platform":"linux/amd64"}"#,
+    )]);
+    let version = server.client().server_version().unwrap();
+    assert!(version.at_least(1, 19));
+    assert_eq!(version.to_string(), "v1.21.3");
+    assert_eq!(server.requests()[0].path, "/version");
+}
+
+#[test]
+fn health_endpoints() {
+    let server = StandIn::start(vec![
+        Reply::new(200, "ok"),
+        Reply::new(
+            500,
+            "[+]ping ok\n[-]etcd failed: reason withheld\nlivez check failed\n",
+        ),
+    ]);
+    let client = server.client();
+    let readyz = client.readyz(false).unwrap();
+    assert!(readyz.healthy);
+    assert!(readyz.checks.is_empty());
+
+    // A failing check is a result, not an error, and is not retried.
+    let livez = client.livez(true).unwrap();
+    assert!(!livez.healthy);
+    assert_eq!(livez.failed_checks().next().unwrap().name, "etcd");
+
+    let paths: Vec<_> = server.requests().into_iter().map(|r| r.path).collect();
+    assert_eq!(paths, vec!["/readyz", "/livez?verbose"]);
+}
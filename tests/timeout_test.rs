@@ -0,0 +1,48 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_sync::kubernetes::Timeouts;
+use k8s_sync::retry::RetryPolicy;
+use std::time::{Duration, Instant};
+
+const EMPTY_POD_LIST: &str =
+    r#"{"kind":"PodList","apiVersion":"v1","metadata":{"resourceVersion":"1"},"items":[]}"#;
+
+#[test]
+fn request_timeout() {
+    let server = StandIn::start(vec![
+        Reply::new(200, EMPTY_POD_LIST).delayed(Duration::from_secs(1))
+    ]);
+    let client = server
+        .client()
+        .with_retry_policy(RetryPolicy::none())
+        .with_timeouts(Timeouts {
+            request: Some(Duration::from_millis(200)),
+            ..Default::default()
+        });
+    let start = Instant::now();
+    let err = client
+        .list_pods(String::from("default"), Default::default())
+        .unwrap_err();
+    assert!(err.is_timeout(), "unexpected error {:?}", err);
+    assert!(start.elapsed() < Duration::from_millis(900));
+}
+
+#[test]
+fn per_call_timeout() {
+    let server = StandIn::start(vec![
+        Reply::new(200, EMPTY_POD_LIST).delayed(Duration::from_secs(1)),
+        Reply::new(200, EMPTY_POD_LIST).delayed(Duration::from_millis(100)),
+    ]);
+    let client = server.client().with_retry_policy(RetryPolicy::none());
+    let err = client
+        .with_timeout(Duration::from_millis(200), |c| {
+            c.list_pods(String::from("default"), Default::default())
+        })
+        .unwrap_err();
+    assert!(err.is_timeout());
+    // The override only applies within the closure.
+    assert!(client
+        .list_pods(String::from("default"), Default::default())
+        .is_ok());
+}
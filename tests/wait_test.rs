@@ -0,0 +1,165 @@
+mod common;
+
+use common::{Reply, StandIn};
+use k8s_openapi::api::core::v1::Pod;
+use k8s_sync::wait::WaitCondition;
+use std::time::Duration;
+
+const PENDING: &str = r#"{"metadata":{"name":"web","namespace":"default","uid":"p1"},"status":{"phase":"Pending","conditions":[{"type":"Ready","status":"False"}]}}"#;
+const READY: &str = r#"{"metadata":{"name":"web","namespace":"default","uid":"p1"},"status":{"phase":"Running","conditions":[{"type":"Ready","status":"True"}]}}"#;
+const METHOD_NOT_ALLOWED: &str = r#"{"kind":"Status","apiVersion":"v1","metadata":{},"status":"Failure","reason":"MethodNotAllowed","code":405}"#;
+
+fn pod_list(items: &[&str]) -> String {
+    format!(
+        r#"{{"apiVersion":"v1","kind":"PodList","metadata":{{"resourceVersion":"10"}},"items":[{}]}}"#,
+        items.join(",")
+    )
+}
+
+fn event(type_: &str, object: &str) -> String {
+    format!("{{\"type\":\"{}\",\"object\":{}}}\n", type_, object)
+}
+
+#[test]
+fn wait_for_condition_watches_changes() {
+    let modified = event("MODIFIED", PENDING);
+    let ready = event("MODIFIED", READY);
+    let server = StandIn::start(vec![
+        Reply::new(200, &pod_list(&[PENDING])),
+        Reply::streamed(
+            200,
+            vec![
+                (Duration::from_millis(10), modified.as_str()),
+                (Duration::from_millis(10), ready.as_str()),
+            ],
+        ),
+    ]);
+    let client = server.client();
+    let pod = client
+        .api::<Pod>(Some("default"))
+        .wait_for(
+            "web",
+            &WaitCondition::condition("Ready"),
+            Duration::from_secs(5),
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(pod.status.unwrap().phase.as_deref(), Some("Running"));
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].path,
+        "/api/v1/namespaces/default/pods?fieldSelector=metadata.name%3Dweb"
+    );
+    assert_eq!(
+        requests[1].path,
+        "/api/v1/namespaces/default/pods?watch=true&fieldSelector=metadata.name%3Dweb&resourceVersion=10&timeoutSeconds=5"
+    );
+}
+
+#[test]
+fn wait_for_already_met_condition() {
+    let server = StandIn::start(vec![Reply::new(200, &pod_list(&[READY]))]);
+    let client = server.client();
+    let condition = "jsonpath={.status.phase}=Running".parse().unwrap();
+    let pod = client
+        .api::<Pod>(Some("default"))
+        .wait_for("web", &condition, Duration::from_secs(5))
+        .unwrap();
+    assert!(pod.is_some());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn wait_for_delete() {
+    let deleted = event("DELETED", READY);
+    let server = StandIn::start(vec![
+        Reply::new(200, &pod_list(&[READY])),
+        Reply::streamed(200, vec![(Duration::from_millis(10), deleted.as_str())]),
+    ]);
+    let client = server.client();
+    let pod = client
+        .api::<Pod>(Some("default"))
+        .wait_for("web", &WaitCondition::Delete, Duration::from_secs(5))
+        .unwrap();
+    assert!(pod.is_none());
+}
+
+#[test]
+fn wait_for_polls_when_watch_is_refused() {
+    let server = StandIn::start(vec![
+        Reply::new(200, &pod_list(&[PENDING])),
+        Reply::new(405, METHOD_NOT_ALLOWED),
+        Reply::new(200, PENDING),
+        Reply::new(200, READY),
+    ]);
+    let client = server.client();
+    let pod = client
+        .api::<Pod>(Some("default"))
+        .wait_for(
+            "web",
+            &WaitCondition::jsonpath("{.status.phase}", "Running"),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+    assert!(pod.is_some());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[3].path, "/api/v1/namespaces/default/pods/web");
+}
+
+#[test]
+fn wait_for_times_out() {
+    let bookmark = r#"{"type":"BOOKMARK","object":{"kind":"Pod","apiVersion":"v1","metadata":{"resourceVersion":"12"}}}
+"#;
+    let server = StandIn::start(vec![
+        Reply::new(200, &pod_list(&[PENDING])),
+        Reply::streamed(200, vec![(Duration::from_millis(1200), bookmark)]),
+    ]);
+    let client = server.client();
+    let err = client
+        .api::<Pod>(Some("default"))
+        .wait_for(
+            "web",
+            &WaitCondition::condition("Ready"),
+            Duration::from_secs(1),
+        )
+        .unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(
+        err.to_string(),
+        "Timed out after 1s waiting for condition=Ready on pods web"
+    );
+}
+
+#[test]
+fn wait_for_without_deadline() {
+    let ready = event("MODIFIED", READY);
+    let server = StandIn::start(vec![
+        Reply::new(200, &pod_list(&[PENDING])),
+        Reply::streamed(200, vec![(Duration::from_millis(10), ready.as_str())]),
+    ]);
+    let client = server.client();
+    let pod = client
+        .api::<Pod>(Some("default"))
+        .wait_for("web", &WaitCondition::condition("Ready"), Duration::MAX)
+        .unwrap();
+    assert!(pod.is_some());
+    // The watch is left to the server's own timeout.
+    assert_eq!(
+        server.requests()[1].path,
+        "/api/v1/namespaces/default/pods?watch=true&fieldSelector=metadata.name%3Dweb&resourceVersion=10"
+    );
+
+    let server = StandIn::start(vec![
+        Reply::new(200, &pod_list(&[PENDING])),
+        Reply::new(405, METHOD_NOT_ALLOWED),
+        Reply::new(200, READY),
+    ]);
+    let pod = server
+        .client()
+        .api::<Pod>(Some("default"))
+        .wait_for("web", &WaitCondition::condition("Ready"), Duration::MAX)
+        .unwrap();
+    assert!(pod.is_some());
+}